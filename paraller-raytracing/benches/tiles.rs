@@ -0,0 +1,155 @@
+//! Compares the tile sizes and orders against rendering pixel by pixel, and
+//! the cost of supersampling, on the native threads of the machine.
+//!
+//! Run with `cargo bench --bench tiles`.
+
+use paraller_raytracing::progress::Progress;
+use paraller_raytracing::render::{self, Frame, SamplePattern, Sampling, TileOrder};
+use raytracer::scene::Scene;
+use std::time::{Duration, Instant};
+
+const RUNS: usize = 5;
+
+const SCENE: &str = r#"{
+    "width": 800,
+    "height": 800,
+    "fov": 90.0,
+    "shadow_bias": 1e-13,
+    "max_recursion_depth": 20,
+    "elements": [
+        {
+            "Sphere": {
+                "center": { "x": 0.0, "y": 0.0, "z": -5.0 },
+                "radius": 1.0,
+                "material": {
+                    "coloration": { "Color": { "red": 0.2, "green": 1.0, "blue": 0.2 } },
+                    "albedo": 0.18,
+                    "surface": { "Reflective": { "reflectivity": 0.7 } }
+                }
+            }
+        },
+        {
+            "Sphere": {
+                "center": { "x": -3.0, "y": 1.0, "z": -6.0 },
+                "radius": 2.0,
+                "material": {
+                    "coloration": { "Color": { "red": 1.0, "green": 1.0, "blue": 1.0 } },
+                    "albedo": 0.58,
+                    "surface": "Diffuse"
+                }
+            }
+        },
+        {
+            "Sphere": {
+                "center": { "x": 2.0, "y": 1.0, "z": -4.0 },
+                "radius": 1.5,
+                "material": {
+                    "coloration": { "Color": { "red": 1.0, "green": 1.0, "blue": 1.0 } },
+                    "albedo": 0.18,
+                    "surface": { "Refractive": { "index": 1.5, "transparency": 1.0 } }
+                }
+            }
+        },
+        {
+            "Plane": {
+                "origin": { "x": 0.0, "y": -2.0, "z": -5.0 },
+                "normal": { "x": 0.0, "y": -1.0, "z": 0.0 },
+                "material": {
+                    "coloration": { "Color": { "red": 0.5, "green": 0.5, "blue": 0.5 } },
+                    "albedo": 0.18,
+                    "surface": { "Reflective": { "reflectivity": 0.5 } }
+                }
+            }
+        }
+    ],
+    "lights": [
+        {
+            "Spherical": {
+                "position": { "x": -2.0, "y": 10.0, "z": -3.0 },
+                "color": { "red": 0.3, "green": 0.8, "blue": 0.3 },
+                "intensity": 10000.0
+            }
+        },
+        {
+            "Directional": {
+                "direction": { "x": 0.0, "y": 0.0, "z": -1.0 },
+                "color": { "red": 0.8, "green": 0.8, "blue": 0.8 },
+                "intensity": 0.2
+            }
+        }
+    ]
+}"#;
+
+/// The fastest of `RUNS` renders, after one to warm up.
+fn best(mut render: impl FnMut()) -> Duration {
+    render();
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            render();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+/// Prints the time taken to trace `rays` rays, one per pixel unless sampled.
+fn report(name: &str, rays: u32, time: Duration) {
+    let seconds = time.as_secs() as f64 + f64::from(time.subsec_nanos()) * 1e-9;
+    println!(
+        "{:<16} {:>9.2} ms {:>9.2} Mrays/s",
+        name,
+        seconds * 1e3,
+        f64::from(rays) / seconds / 1e6
+    );
+}
+
+fn main() {
+    let scene: Scene = serde_json::from_str(SCENE).unwrap();
+    let pixels = scene.width * scene.height;
+    let mut rgba = vec![0; 4 * pixels as usize];
+    // Reused, as allocating it would take longer than some of the renders.
+    // The sums it accumulates across runs do not change the work done.
+    let mut frame = Frame::new(scene.width, scene.height);
+
+    report(
+        "pixels",
+        pixels,
+        best(|| render::render_pixels(&scene, &mut rgba)),
+    );
+    for &order in &[TileOrder::Spiral, TileOrder::Hilbert, TileOrder::Rows] {
+        for &size in &[8, 16, 32, 64, 128] {
+            let tiles = render::tiles(scene.width, scene.height, size, order);
+            let time = best(|| {
+                let progress = Progress::new(tiles.clone());
+                render::render_pass(
+                    &scene,
+                    &mut frame,
+                    Sampling::default(),
+                    0,
+                    &tiles,
+                    &progress,
+                    || false,
+                );
+            });
+            report(&format!("{:?} {}", order, size), pixels, time);
+        }
+    }
+
+    let tiles = render::tiles(scene.width, scene.height, 32, TileOrder::Spiral);
+    for &pattern in &[SamplePattern::Grid, SamplePattern::Stochastic] {
+        for &grid in &[2, 4] {
+            let sampling = Sampling {
+                pattern,
+                grid,
+                passes: 1,
+            };
+            let time = best(|| {
+                let progress = Progress::new(tiles.clone());
+                render::render_pass(&scene, &mut frame, sampling, 0, &tiles, &progress, || false);
+            });
+            let samples = pixels * sampling.per_pass();
+            report(&format!("{:?} {}x{}", pattern, grid, grid), samples, time);
+        }
+    }
+}
@@ -1,14 +1,25 @@
 use futures::Future;
 use js_sys::{Promise, Uint8ClampedArray, WebAssembly};
-use rayon::prelude::*;
+use pool::{CancellationToken, JobError, JobOptions};
+use progress::{Progress, Rect, Reporter, Reporting};
+use render::{Frame, SamplePattern, Sampling, TileOrder};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use threads::ThreadPool;
 use wasm_bindgen::prelude::*;
-// use wasm_bindgen::JsCast;
+use wasm_bindgen::JsCast;
 
 macro_rules! console_log {
     ($($t:tt)*) => (crate::log(&format_args!($($t)*).to_string()))
 }
 
+mod panic;
 mod pool;
+pub mod progress;
+pub mod render;
+mod threads;
+mod validate;
 
 #[wasm_bindgen]
 extern "C" {
@@ -21,79 +32,194 @@ extern "C" {
 #[wasm_bindgen]
 pub struct Scene {
     inner: raytracer::scene::Scene,
+    tile_size: u32,
+    tile_order: TileOrder,
+    sampling: Sampling,
 }
 
 #[wasm_bindgen]
 impl Scene {
+    /// Parses `object`, failing with an `InvalidScene` error listing every
+    /// problem with it and where it is, e.g. `elements[3].Sphere.radius`.
     #[wasm_bindgen(constructor)]
     pub fn new(object: &JsValue) -> Result<Scene, JsValue> {
-        console_error_panic_hook::set_once();
+        panic::set_hook();
         Ok(Scene {
-            inner: object
-                .into_serde()
-                .map_err(|e| JsValue::from(e.to_string))?,
+            inner: validate::scene(object)?,
+            tile_size: render::DEFAULT_TILE_SIZE,
+            tile_order: TileOrder::Spiral,
+            sampling: Sampling::default(),
         })
     }
 
-    pub fn render(
+    /// Renders in tiles of `size` by `size` pixels, handed out to the
+    /// threads in `order`. Defaults to 32 pixel tiles in a spiral.
+    #[wasm_bindgen(js_name = setTiles)]
+    pub fn set_tiles(&mut self, size: u32, order: TileOrder) -> Result<(), JsValue> {
+        if size == 0 {
+            return Err(JsValue::from("tile size must be positive"));
+        }
+        self.tile_size = size;
+        self.tile_order = order;
+        Ok(())
+    }
+
+    /// Averages `grid * grid` samples per pixel, taken where `pattern` puts
+    /// them, in each of `passes` passes over the image. The image is shown
+    /// after every pass, getting smoother as the passes add up. Defaults to
+    /// one sample at the center of each pixel, in one pass.
+    #[wasm_bindgen(js_name = setSampling)]
+    pub fn set_sampling(
+        &mut self,
+        pattern: SamplePattern,
+        grid: u32,
+        passes: u32,
+    ) -> Result<(), JsValue> {
+        if grid == 0 || passes == 0 {
+            return Err(JsValue::from("samples and passes must be positive"));
+        }
+        self.sampling = Sampling {
+            pattern,
+            grid,
+            passes,
+        };
+        Ok(())
+    }
+
+    /// Renders on `threads`, which may be shared with other renders.
+    pub fn render(self, threads: &ThreadPool) -> Result<RenderingScene, JsValue> {
+        self.start(None, threads)
+    }
+}
+
+impl Scene {
+    /// Starts rendering into `frame`, or a new frame if `None`.
+    fn start(
         self,
-        concurreny: usize,
-        pool: &pool::WorkerPool,
+        frame: Option<SharedFrame>,
+        threads: &ThreadPool,
     ) -> Result<RenderingScene, JsValue> {
+        let thread_pool = threads.rayon()?;
         let scene = self.inner;
         let height = scene.height;
         let width = scene.width;
 
-        let pixels = (width * height) as usize;
-        let mut rgb_data = vec![0; 4 * pixels];
-        let base = rgb_data.as_ptr() as usize;
-        let len = rgb_data.len();
+        let sampling = self.sampling;
+        let frame = frame.unwrap_or_else(|| SharedFrame::new(width, height));
+        let rendered = frame.clone();
+        let kept = frame.clone();
+        let tiles = render::tiles(width, height, self.tile_size, self.tile_order);
+        let units = (0..sampling.passes).flat_map(|_| tiles.iter().cloned());
+        let progress = Arc::new(Progress::new(units.collect()));
+        let counters = progress.clone();
+        let tiles_len = tiles.len();
 
-        let thread_pool = rayon::ThreadPoolBuilder::new()
-            .num_threads(concurreny - 1)
-            .spwan_handler(|thread| Ok(pool.run(|| thread.run().unwrap())))
-            .build()
-            .unwrap();
-
-        let done = pool
-            .run_notify(move || {
+        let token = CancellationToken::new();
+        let stop = token.clone();
+        let failed = token.clone();
+        let options = JobOptions {
+            token: Some(token.clone()),
+            ..JobOptions::default()
+        };
+        let reporting = Rc::new(RefCell::new(None::<Reporting>));
+        let finished = reporting.clone();
+        let done = threads
+            .workers()
+            .run_notify_with(options, move || {
+                // Waits for a cancelled render of the same frame to finish
+                // the tiles it had started. A panic in it poisons the lock,
+                // but the samples are cleared anyway.
+                let mut guard = rendered
+                    .frame
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                let frame: &mut Frame = &mut guard;
+                frame.clear();
                 thread_pool.install(|| {
-                    rgb_data
-                        .par_chunks_mut(4)
-                        .enumerate()
-                        .for_each(|(i, chunk)| {
-                            let i = i as u32;
-                            let x = i % width;
-                            let y = i / width;
-                            let ray = raytracer::Ray::create_prime(x, y, &scene);
-                            let result = raytracer::cast_ray(&scene, &ray, 0).to_rgba();
-                            chunk[0] = result.data[0];
-                            chunk[1] = result.data[1];
-                            chunk[2] = result.data[2];
-                            chunk[3] = result.data[3];
-                        });
+                    for pass in 0..sampling.passes {
+                        if stop.is_cancelled() {
+                            break;
+                        }
+                        render::render_pass(
+                            &scene,
+                            frame,
+                            sampling,
+                            pass,
+                            &tiles,
+                            &counters,
+                            || stop.is_cancelled(),
+                        );
+                    }
                 });
-                rgb_data
             })?
-            .map(move |_data| image_data(base, len, width, height).into());
+            // A rayon thread dying leaves the job waiting on it forever.
+            .select(threads.broken())
+            .map(|((), _)| ())
+            .map_err(move |(err, _)| {
+                failed.cancel();
+                err
+            })
+            .then(move |result| {
+                let reporting = finished.borrow_mut().take();
+                result?;
+                if let Some(reporting) = reporting {
+                    reporting.finish();
+                }
+                Ok(image_data(kept.base, kept.len, width, height).into())
+            })
+            .map_err(|err: JobError| JsValue::from(err));
 
         Ok(RenderingScene {
             promise: wasm_bindgen_futures::future_to_promise(done),
-            base,
-            len,
+            frame,
             height,
             width,
+            progress,
+            reporting,
+            tiles: tiles_len,
+            samples_per_pass: sampling.per_pass(),
+            token,
         })
     }
 }
 
-#[wasm_bindgen]
-pub struct RenderingScene {
+/// The image of a render, kept by its `RenderingScene` so that it can be read
+/// after the render and reused by the next one.
+#[derive(Clone)]
+struct SharedFrame {
+    /// Locked by the job rendering it. The main thread never locks it, as
+    /// it may not block; it reads the pixels at `base` instead.
+    frame: Arc<Mutex<Frame>>,
     base: usize,
     len: usize,
+}
+
+impl SharedFrame {
+    fn new(width: u32, height: u32) -> SharedFrame {
+        let frame = Frame::new(width, height);
+        let base = frame.rgba().as_ptr() as usize;
+        let len = frame.rgba().len();
+        SharedFrame {
+            frame: Arc::new(Mutex::new(frame)),
+            base,
+            len,
+        }
+    }
+}
+
+#[wasm_bindgen]
+pub struct RenderingScene {
+    frame: SharedFrame,
     promise: Promise,
     width: u32,
     height: u32,
+    progress: Arc<Progress>,
+    /// Set by `on_progress` until the render is done.
+    reporting: Rc<RefCell<Option<Reporting>>>,
+    /// Tiles per pass.
+    tiles: usize,
+    samples_per_pass: u32,
+    token: CancellationToken,
 }
 
 #[wasm_bindgen]
@@ -114,12 +240,105 @@ impl RenderingScene {
     /// Return a progressive rendering of the image so far
     #[wasm_bindgen(js_name = imageSoFar)]
     pub fn image_so_far(&self) -> ImageData {
-        image_data(self.base, self.len, self.width, self.height)
+        image_data(self.frame.base, self.frame.len, self.width, self.height)
+    }
+
+    /// Returns the rendering so far of a part of the image, e.g. the
+    /// rectangle passed to the `onProgress` callback.
+    #[wasm_bindgen(js_name = imageRect)]
+    pub fn image_rect(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<ImageData, JsValue> {
+        let rect = Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+        if !rect.fits_in(self.width, self.height) {
+            return Err(JsValue::from("rectangle outside the image"));
+        }
+        Ok(image_rect(self.frame.base, self.width, rect))
+    }
+
+    /// Returns `{ completed, total }`, the tiles rendered so far and in all,
+    /// counting each tile once per pass.
+    pub fn progress(&self) -> JsValue {
+        self.progress.to_js()
+    }
+
+    /// Returns the number of samples averaged by every pixel so far, which
+    /// grows as passes complete.
+    pub fn samples(&self) -> u32 {
+        // An empty image has no tiles.
+        let passes = self.progress.completed() / self.tiles.max(1);
+        passes as u32 * self.samples_per_pass
+    }
+
+    /// Stops the render. The promise rejects with a `Cancelled` error and
+    /// no more tiles start; the threads are free for other renders once the
+    /// tiles they were rendering are done. The image keeps what was rendered.
+    pub fn cancel(&self) {
+        self.token.cancel();
+        // Dropping it stops its timer.
+        self.reporting.borrow_mut().take();
+    }
+
+    /// Cancels the render and starts rendering `scene`, e.g. this one
+    /// edited, on `threads`. The image is reused if `scene` is the same
+    /// size, showing the previous render until the new tiles replace it.
+    /// The other methods then follow the new render, which has a new
+    /// promise, and the `onProgress` callback must be set again.
+    pub fn restart(&mut self, scene: Scene, threads: &ThreadPool) -> Result<(), JsValue> {
+        self.cancel();
+        let reused = scene.inner.width == self.width && scene.inner.height == self.height;
+        let frame = if reused {
+            Some(self.frame.clone())
+        } else {
+            None
+        };
+        *self = scene.start(frame, threads)?;
+        Ok(())
+    }
+
+    /// Calls `callback` at most every `interval` milliseconds while tiles
+    /// get done, with the progress and the bounds of the tiles done since the
+    /// last call: `{ completed, total, x, y, width, height }`. Replaces any
+    /// previous callback.
+    #[wasm_bindgen(js_name = onProgress)]
+    pub fn on_progress(&self, callback: js_sys::Function, interval: f64) -> Result<(), JsValue> {
+        let reporter = Reporter::new(self.progress.clone(), callback);
+        // Dropping the previous one stops its timer.
+        self.reporting.borrow_mut().take();
+        if self.progress.completed() == self.progress.total() {
+            reporter.report_once();
+            return Ok(());
+        }
+        *self.reporting.borrow_mut() = Some(Reporting::start(reporter, interval)?);
+        Ok(())
     }
 }
 
 fn image_data(base: usize, len: usize, width: u32, height: u32) -> ImageData {
-    let mem = wasm_bindgen::memory().unchekced_into::<WebAssembly::Memory>();
+    let mem = wasm_bindgen::memory().unchecked_into::<WebAssembly::Memory>();
     let mem = Uint8ClampedArray::new(&mem.buffer()).slice(base as u32, (base + len) as u32);
     ImageData::new(&mem, width as f64, height as f64).unwrap()
 }
+
+/// Copies `rect` of an image `width` pixels wide out of the shared memory,
+/// which `ImageData` cannot use directly.
+fn image_rect(base: usize, width: u32, rect: Rect) -> ImageData {
+    let mem = wasm_bindgen::memory().unchecked_into::<WebAssembly::Memory>();
+    let mem = Uint8ClampedArray::new(&mem.buffer());
+    let row_len = 4 * rect.width;
+    let data = Uint8ClampedArray::new_with_length(row_len * rect.height);
+    for row in 0..rect.height {
+        let start = base as u32 + 4 * ((rect.y + row) * width + rect.x);
+        data.set(&mem.subarray(start, start + row_len), row * row_len);
+    }
+    ImageData::new(&data, rect.width as f64, rect.height as f64).unwrap()
+}
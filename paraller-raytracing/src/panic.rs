@@ -0,0 +1,131 @@
+//! Panics in pool jobs, reported back to whoever submitted the job.
+
+use std::cell::Cell;
+use std::panic::{self, PanicInfo};
+use std::sync::Once;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::DedicatedWorkerGlobalScope;
+
+thread_local! {
+  /// Whether this thread is a worker running a pool job.
+  static IN_JOB: Cell<bool> = Cell::new(false);
+}
+
+#[wasm_bindgen]
+extern "C" {
+  type Error;
+
+  #[wasm_bindgen(constructor)]
+  fn new() -> Error;
+
+  #[wasm_bindgen(structural, method, getter)]
+  fn stack(error: &Error) -> String;
+}
+
+/// A panic in a pool job, as posted by its worker.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkerPanic {
+  pub message: String,
+  /// `file:line:column` of the panic.
+  pub location: Option<String>,
+  /// The JS stack of the worker, wasm frames included where the browser
+  /// names them.
+  pub backtrace: Option<String>,
+}
+
+impl WorkerPanic {
+  fn new(info: &PanicInfo) -> WorkerPanic {
+    let payload = info.payload();
+    let message = match payload.downcast_ref::<&str>() {
+      Some(message) => message.to_string(),
+      None => match payload.downcast_ref::<String>() {
+        Some(message) => message.clone(),
+        None => "Box<Any>".to_string(),
+      },
+    };
+    let location = info
+      .location()
+      .map(|location| format!("{}:{}:{}", location.file(), location.line(), location.column()));
+    let backtrace = Some(Error::new().stack()).filter(|stack| !stack.is_empty());
+    WorkerPanic {
+      message,
+      location,
+      backtrace,
+    }
+  }
+
+  /// The message a worker posts instead of its usual reply: an object with
+  /// the report under `panic`.
+  fn to_message(&self) -> JsValue {
+    let report = js_sys::Object::new();
+    set(&report, "message", &JsValue::from(self.message.as_str()));
+    set(&report, "location", &to_js(&self.location));
+    set(&report, "backtrace", &to_js(&self.backtrace));
+    let message = js_sys::Object::new();
+    set(&message, "panic", &report);
+    message.into()
+  }
+
+  /// The report in a message from a worker, if it is one.
+  pub fn from_message(data: &JsValue) -> Option<WorkerPanic> {
+    if !data.is_object() {
+      return None;
+    }
+    let report = js_sys::Reflect::get(data, &JsValue::from("panic")).ok()?;
+    if !report.is_object() {
+      return None;
+    }
+    let get = |key: &str| {
+      js_sys::Reflect::get(&report, &JsValue::from(key))
+        .ok()
+        .and_then(|value| value.as_string())
+    };
+    Some(WorkerPanic {
+      message: get("message").unwrap_or_default(),
+      location: get("location"),
+      backtrace: get("backtrace"),
+    })
+  }
+
+  /// Adds the report to a JS error, as `location` and `backtrace`.
+  pub fn annotate(&self, error: &js_sys::Error) {
+    set(error, "location", &to_js(&self.location));
+    set(error, "backtrace", &to_js(&self.backtrace));
+  }
+}
+
+fn to_js(value: &Option<String>) -> JsValue {
+  match value {
+    Some(value) => JsValue::from(value.as_str()),
+    None => JsValue::null(),
+  }
+}
+
+fn set(target: &JsValue, key: &str, value: &JsValue) {
+  let _ = js_sys::Reflect::set(target, &JsValue::from(key), value);
+}
+
+/// Installs the panic hook of every thread, once. Panics are logged to the
+/// console, and those in a pool job also posted to the main thread: wasm
+/// aborts on panic, so the hook is the last code that runs on the worker.
+pub fn set_hook() {
+  static SET_HOOK: Once = Once::new();
+  SET_HOOK.call_once(|| panic::set_hook(Box::new(hook)));
+}
+
+fn hook(info: &PanicInfo) {
+  console_error_panic_hook::hook(info);
+  if IN_JOB.with(Cell::get) {
+    let global = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
+    let _ = global.post_message(&WorkerPanic::new(info).to_message());
+  }
+}
+
+/// Runs a job on this worker. There is no unwinding on wasm: if the job
+/// panics, only the hook runs, reporting it, and the worker aborts.
+pub fn run_job(f: impl FnOnce()) {
+  IN_JOB.with(|in_job| in_job.set(true));
+  f();
+  IN_JOB.with(|in_job| in_job.set(false));
+}
@@ -1,137 +1,431 @@
+use futures::future::{self, Either};
 use futures::sync::oneshot;
 use futures::Future;
-use std::cell::{RefCell, UnsafeCell};
+use std::cell::{Cell, RefCell, UnsafeCell};
+use std::collections::VecDeque;
+use std::fmt;
 use std::mem;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering::SeqCst};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use crate::panic::{self, WorkerPanic};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
 use web_sys::{ErrorEvent, Event, Worker};
 
+/// How long a surplus worker may sit idle before it is terminated, in
+/// milliseconds.
+const DEFAULT_IDLE_TIMEOUT: f64 = 10_000.0;
+
+/// How many jobs may wait for a worker, see `WorkerPool::set_queue_limit`.
+const DEFAULT_QUEUE_LIMIT: usize = 256;
+
+/// The number of logical cores of the machine, or 1 if the browser does not
+/// tell.
+fn hardware_concurrency() -> usize {
+  let cores = web_sys::window().map_or(0.0, |window| window.navigator().hardware_concurrency());
+  (cores as usize).max(1)
+}
+
+/// A pool of web workers running closures on the shared wasm memory.
+///
+/// The pool keeps at least `min` workers alive and spawns more on demand, up
+/// to `max`. Work submitted while all `max` workers are busy waits in a
+/// bounded queue, highest priority first, and is handed to the next worker
+/// that finishes. Once the queue is full, the `RejectionPolicy` decides which
+/// job is refused. Workers above `min` that stay idle for the idle timeout are
+/// terminated.
+///
+/// Clones share the same workers.
 #[wasm_bindgen]
+#[derive(Clone)]
 pub struct WorkerPool {
   state: Rc<PoolState>,
 }
 
+/// A snapshot of what a `WorkerPool` is doing, see `WorkerPool::stats`.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug)]
+pub struct PoolStats {
+  /// Workers running a job.
+  pub busy: usize,
+  /// Workers waiting for a job.
+  pub idle: usize,
+  /// Jobs waiting for a worker.
+  pub queued: usize,
+  /// Workers spawned over the lifetime of the pool, including terminated
+  /// ones.
+  pub spawned: usize,
+  /// Jobs refused or dropped because the queue was full.
+  pub rejected: usize,
+}
+
+/// What happens to a job submitted while the queue is full.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RejectionPolicy {
+  /// The new job is refused: submitting it fails.
+  Reject,
+  /// The oldest of the lowest priority jobs is dropped to make room, and its
+  /// future fails. A new job of lower priority than every queued one is
+  /// refused instead.
+  DropOldest,
+}
+
+/// Why a job submitted with `WorkerPool::run_notify` produced no value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobError {
+  /// Its `CancellationToken` was cancelled.
+  Cancelled,
+  /// It ran longer than its timeout, and its worker was terminated.
+  TimedOut,
+  /// It was dropped from a full queue, see `RejectionPolicy::DropOldest`.
+  Dropped,
+  /// It panicked. Its worker was replaced.
+  Panicked(WorkerPanic),
+  /// Its worker raised an error other than a panic, e.g. failed to load.
+  /// The worker was replaced.
+  Failed(String),
+}
+
+impl fmt::Display for JobError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      JobError::Cancelled => write!(f, "job was cancelled"),
+      JobError::TimedOut => write!(f, "job timed out"),
+      JobError::Dropped => write!(f, "job dropped from the worker pool queue"),
+      JobError::Panicked(panic) => match panic.location {
+        Some(ref location) => write!(f, "job panicked at {}: {}", location, panic.message),
+        None => write!(f, "job panicked: {}", panic.message),
+      },
+      JobError::Failed(message) => write!(f, "job failed: {}", message),
+    }
+  }
+}
+
+/// A JS `Error` named after the variant, so JS can tell them apart. Panics
+/// carry their `location` and `backtrace`.
+impl From<JobError> for JsValue {
+  fn from(err: JobError) -> JsValue {
+    let error = js_sys::Error::new(&err.to_string());
+    error.set_name(match err {
+      JobError::Cancelled => "Cancelled",
+      JobError::TimedOut => "TimedOut",
+      JobError::Dropped => "Dropped",
+      JobError::Panicked(_) => "Panicked",
+      JobError::Failed(_) => "Failed",
+    });
+    if let JobError::Panicked(panic) = &err {
+      panic.annotate(&error);
+    }
+    error.into()
+  }
+}
+
+/// Cancels the jobs it is passed to. Jobs still queued never run, and the
+/// future of a running one fails with `JobError::Cancelled` right away; the
+/// job itself is expected to check `is_cancelled` and return early.
+#[wasm_bindgen]
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+  inner: Arc<TokenState>,
+}
+
+#[derive(Default)]
+struct TokenState {
+  cancelled: AtomicBool,
+  /// Futures waiting for `cancel`. Only the main thread touches them, so the
+  /// lock is never contended there.
+  waiting: Mutex<Vec<oneshot::Sender<()>>>,
+}
+
+#[wasm_bindgen]
+impl CancellationToken {
+  #[wasm_bindgen(constructor)]
+  pub fn new() -> CancellationToken {
+    CancellationToken::default()
+  }
+
+  pub fn cancel(&self) {
+    self.inner.cancelled.store(true, SeqCst);
+    for tx in self.inner.waiting.lock().unwrap().drain(..) {
+      let _ = tx.send(());
+    }
+  }
+
+  #[wasm_bindgen(js_name = isCancelled)]
+  pub fn is_cancelled(&self) -> bool {
+    self.inner.cancelled.load(SeqCst)
+  }
+}
+
+impl CancellationToken {
+  /// Resolves once the token is cancelled, and never if it is dropped
+  /// without.
+  fn cancelled(&self) -> impl Future<Item = (), Error = ()> {
+    if self.is_cancelled() {
+      return Either::A(future::ok(()));
+    }
+    let (tx, rx) = oneshot::channel();
+    self.inner.waiting.lock().unwrap().push(tx);
+    Either::B(rx.or_else(|_| future::empty()))
+  }
+}
+
+/// How to run a job, see `WorkerPool::run_notify_with`.
+#[derive(Clone, Default)]
+pub struct JobOptions {
+  /// Queued jobs run in descending priority, 0 by default.
+  pub priority: i32,
+  /// Milliseconds the job may run before its worker is terminated and
+  /// replaced. Terminating a worker loses whatever it was doing, so this is
+  /// for jobs that share no locks or state with other jobs.
+  pub timeout: Option<f64>,
+  pub token: Option<CancellationToken>,
+}
+
+/// Runs on the main thread once a job is done, or failed to finish.
+type OnFinish = Box<dyn FnOnce(Result<(), JobError>)>;
+
 struct PoolState {
-  worker: RefCell<Vec<Worker>>,
+  min: usize,
+  max: usize,
+  idle_timeout: f64,
+  /// Idle workers with the time they went idle, longest idle first.
+  idle: RefCell<Vec<(Worker, f64)>>,
+  /// Jobs waiting for a worker, by descending priority and in submission
+  /// order within a priority.
+  queue: RefCell<VecDeque<Job>>,
+  queue_limit: Cell<usize>,
+  policy: Cell<RejectionPolicy>,
+  busy: Cell<usize>,
+  spawned: Cell<usize>,
+  rejected: Cell<usize>,
   callback: Closure<dyn FnMut(Event)>,
+  /// Terminates surplus idle workers, armed by `PoolState::schedule_reap`.
+  reaper: RefCell<Option<Closure<dyn FnMut()>>>,
+  reap_timer: Cell<Option<i32>>,
 }
 
 struct Work {
   func: Box<dyn FnOnce() + Send>,
 }
 
+/// Work that has not been posted to a worker yet, and what to run on the
+/// main thread once it is done.
+struct Job {
+  work: Box<Work>,
+  on_finish: OnFinish,
+  options: JobOptions,
+}
+
+/// Where `queue_slot` puts a new job.
+#[derive(Debug, PartialEq)]
+enum Slot {
+  /// At `index` of the queue, once the job at `drop`, if any, is dropped.
+  Insert { index: usize, drop: Option<usize> },
+  Refuse,
+}
+
+/// Where a job of `priority` goes in a queue holding jobs of the `queued`
+/// priorities, in queue order, at most `limit` of them. A full queue makes
+/// room as `policy` says.
+fn queue_slot(queued: &[i32], priority: i32, limit: usize, policy: RejectionPolicy) -> Slot {
+  let mut drop = None;
+  if queued.len() >= limit {
+    match (policy, queued.last()) {
+      (RejectionPolicy::DropOldest, Some(&lowest)) if lowest <= priority => {
+        drop = queued.iter().position(|&queued| queued == lowest);
+      }
+      _ => return Slot::Refuse,
+    }
+  }
+  let index = queued
+    .iter()
+    .enumerate()
+    .filter(|&(i, _)| Some(i) != drop)
+    .position(|(_, &queued)| queued < priority)
+    .unwrap_or(queued.len() - drop.map_or(0, |_| 1));
+  Slot::Insert { index, drop }
+}
+
+impl Job {
+  fn is_cancelled(&self) -> bool {
+    self.options.token.as_ref().map_or(false, CancellationToken::is_cancelled)
+  }
+}
+
 #[wasm_bindgen]
 impl WorkerPool {
+  /// Creates a pool with `initial` workers, which never shrinks below that
+  /// and spawns more workers while all of them are busy, up to one per
+  /// logical core (`navigator.hardwareConcurrency`) or `initial`, whichever
+  /// is more. Work beyond that waits in the queue.
   #[wasm_bindgen(constructor)]
   pub fn new(initial: usize) -> Result<WorkerPool, JsValue> {
+    let max = initial.max(hardware_concurrency());
+    WorkerPool::with_bounds(initial, max, DEFAULT_IDLE_TIMEOUT)
+  }
+
+  /// Creates a pool of `min` to `max` workers. Workers above `min` are
+  /// terminated after `idle_timeout` milliseconds without work.
+  #[wasm_bindgen(js_name = withBounds)]
+  pub fn with_bounds(min: usize, max: usize, idle_timeout: f64) -> Result<WorkerPool, JsValue> {
+    panic::set_hook();
+    if max == 0 || min > max {
+      return Err(JsValue::from(format!(
+        "invalid worker pool bounds: min {}, max {}",
+        min, max
+      )));
+    }
     let pool = WorkerPool {
       state: Rc::new(PoolState {
-        worker: RefCell::new(Vec::with_capacity(initial)),
+        min,
+        max,
+        idle_timeout,
+        idle: RefCell::new(Vec::with_capacity(min)),
+        queue: RefCell::new(VecDeque::new()),
+        queue_limit: Cell::new(DEFAULT_QUEUE_LIMIT),
+        policy: Cell::new(RejectionPolicy::Reject),
+        busy: Cell::new(0),
+        spawned: Cell::new(0),
+        rejected: Cell::new(0),
         callback: Closure::wrap(Box::new(|event: Event| {
           console_log!("unhandled event: {}", event.type_());
           crate::logv(&event);
         }) as Box<dyn FnMut(Event)>),
+        reaper: RefCell::new(None),
+        reap_timer: Cell::new(None),
       }),
     };
 
-    for _ in 0..initial {
-      let worker = pool.spawn()?;
+    let state = Rc::downgrade(&pool.state);
+    *pool.state.reaper.borrow_mut() = Some(Closure::wrap(Box::new(move || {
+      if let Some(state) = state.upgrade() {
+        state.reap_timer.set(None);
+        state.reap();
+      }
+    }) as Box<dyn FnMut()>));
+
+    for _ in 0..min {
+      let worker = pool.state.spawn()?;
       pool.state.push(worker);
     }
 
     Ok(pool)
   }
 
-  fn spawn(&self) -> Result<Worker, JsValue> {
-    console_log!("spawning new worker");
-
-    let worker = Worker::new("./worker.js")?;
-    let array = js_sys::Array::new();
-
-    array.push(&wasm_bindgen::module());
-    array.push(&wasm_bindgen::module());
-    worker.post_message(&array)?;
-
-    Ok(worker)
-  }
-
-  fn worker(&self, f: impl FnOnce() + Send + 'static) -> Result<Worker, JsValue> {
-    let worker = self.worker()?;
-    let work = Box::new(Work { func: Box::new(f) });
-    let ptr = Box::into_raw(work);
-
-    match worker.post_message(&JsValue::from(ptr as u32)) {
-      Ok(()) => Ok(worker),
-      Err(e) => {
-        unsafe {
-          drop(Box::from_raw(ptr));
-        }
-        Err(e)
-      }
+  pub fn stats(&self) -> PoolStats {
+    PoolStats {
+      busy: self.state.busy.get(),
+      idle: self.state.idle.borrow().len(),
+      queued: self.state.queue.borrow().len(),
+      spawned: self.state.spawned.get(),
+      rejected: self.state.rejected.get(),
     }
   }
 
-  fn reclaim_on_message(&self, worker: Worker, on_finish: impl FnOnce() + 'static) {
-    let state = Rc::downgrade(&self.state);
-    let worker2 = worker.clone();
-    let reclaim_slot = Rc::new(RefCell::new(None));
-    let slot2 = reclaim_slot.clone();
-    let mut on_finish = Some(on_finish);
-    let reclaim = Closure::Wrap(Box::new(move |event: Event| {
-      if let Some(error) = event.dyn_ref::<ErrorEvent>() {
-        console_log!("error in worker: {}", error.message());
-        return;
-      }
-
-      if let Some(_msg) = event.dyn_ref::<MessageEvent>() {
-        on_finish.take().unwrap()();
-        if let Some(state) = state.upgrade() {
-          state.push(worker.clone());
-        }
-
-        *slot2.borrow_mut() = None;
-        return;
-      }
+  /// Sets how many jobs may wait for a worker, 256 by default. Jobs already
+  /// queued are kept.
+  #[wasm_bindgen(js_name = setQueueLimit)]
+  pub fn set_queue_limit(&self, limit: usize) {
+    self.state.queue_limit.set(limit);
+  }
 
-      console_log!("unhandled event: {}", event.type_());
-      crate::logv(&event);
-    }) as   Box<dyn FnMut(Event)>);
+  #[wasm_bindgen(js_name = setRejectionPolicy)]
+  pub fn set_rejection_policy(&self, policy: RejectionPolicy) {
+    self.state.policy.set(policy);
+  }
 
-    worker.set_onmessage(Some(reclaim.as_ref().unchecked_ref()));
-    *reclaim_slot.borrow_mut() = Some(reclaim);
+  /// Runs `f` on an idle worker, a new one or the first one to finish its
+  /// current job, and `on_finish` on the main thread once `f` returned or
+  /// failed to.
+  fn execute(
+    &self,
+    options: JobOptions,
+    f: impl FnOnce() + Send + 'static,
+    on_finish: impl FnOnce(Result<(), JobError>) + 'static,
+  ) -> Result<(), JsValue> {
+    let job = Job {
+      work: Box::new(Work { func: Box::new(f) }),
+      on_finish: Box::new(on_finish),
+      options,
+    };
+    if job.is_cancelled() {
+      (job.on_finish)(Err(JobError::Cancelled));
+      return Ok(());
+    }
+    let worker = self.state.idle.borrow_mut().pop().map(|(worker, _)| worker);
+    let worker = match worker {
+      Some(worker) => worker,
+      None if self.state.len() < self.state.max => self.state.spawn()?,
+      None => return self.state.enqueue(job),
+    };
+    PoolState::start(&self.state, worker, job)
   }
 }
 
 impl WorkerPool {
+  /// The most workers the pool runs at once.
+  pub fn max(&self) -> usize {
+    self.state.max
+  }
+
   pub fn run(&self, f: impl FnOnce() + Send + 'static) -> Result<(), JsValue> {
-    let worker = self.execute(f)?;
-    self.reclaim_on_message(worker, || {});
-    Ok(())
+    self.execute(JobOptions::default(), f, |_| {})
   }
 
   pub fn run_notify<T>(
         &self,
         f: impl FnOnce() -> T + Send + 'static,
-    ) -> Result<impl Future<Item = T, Error = JsValue> + 'static, JsValue>
+    ) -> Result<impl Future<Item = T, Error = JobError> + 'static, JsValue>
+    where
+        T: Send + 'static,
+    {
+        self.run_notify_with(JobOptions::default(), f)
+    }
+
+    /// Like `run_notify`, with a priority, timeout or cancellation token.
+    pub fn run_notify_with<T>(
+        &self,
+        options: JobOptions,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Result<impl Future<Item = T, Error = JobError> + 'static, JsValue>
     where
         T: Send + 'static,
     {
         let (tx, rx) = oneshot::channel();
         let storage = Arc::new(AtomicValue::new(None));
         let storage2 = storage.clone();
-        let worker = self.execute(move || {
-            assert!(storage2.replace(Some(f())).is_ok());
-        })?;
-        self.reclaim_on_message(worker, move || match storage.replace(None) {
-            Ok(Some(val)) => drop(tx.send(val)),
-            _ => unreachable!(),
-        });
+        let token = options.token.clone();
+        self.execute(
+            options,
+            move || {
+                assert!(storage2.replace(Some(f())).is_ok());
+            },
+            move |result| match result {
+                Ok(()) => match storage.replace(None) {
+                    Ok(Some(val)) => drop(tx.send(Ok(val))),
+                    _ => unreachable!(),
+                },
+                Err(err) => drop(tx.send(Err(err))),
+            },
+        )?;
 
-        Ok(rx.map_err(|_| JsValue::undefined()))
+        // The sender is dropped along with a job that never runs.
+        let done = rx.then(|result| match result {
+            Ok(result) => result,
+            Err(_) => Err(JobError::Dropped),
+        });
+        Ok(match token {
+            Some(token) => Either::A(done.select2(token.cancelled()).then(|result| match result {
+                Ok(Either::A((val, _))) => Ok(val),
+                Err(Either::A((err, _))) => Err(err),
+                Ok(Either::B(_)) | Err(Either::B(_)) => Err(JobError::Cancelled),
+            })),
+            None => Either::B(done),
+        })
     }
 }
 
@@ -162,17 +456,272 @@ impl<T> AtomicValue<T> {
 }
 
 impl PoolState {
-    fn push(&self, worker: Worker) {
-        worker.set_onmessage(Some(self.callback.as_ref().unchecked_ref()));
-        worker.set_onerror(Some(self.callback.as_ref().unchecked_ref()));
-        let mut workers = self.workers.borrow_mut();
-        for prev in workers.iter() {
-            let prev: &JsValue = prev;
-            let worker: &JsValue = &worker;
-            assert!(prev != worker);
+  /// Workers alive, busy or idle.
+  fn len(&self) -> usize {
+    self.busy.get() + self.idle.borrow().len()
+  }
+
+  fn spawn(&self) -> Result<Worker, JsValue> {
+    console_log!("spawning new worker");
+
+    let worker = Worker::new("./worker.js")?;
+    let array = js_sys::Array::new();
+
+    array.push(&wasm_bindgen::module());
+    array.push(&wasm_bindgen::memory());
+    worker.post_message(&array)?;
+    self.spawned.set(self.spawned.get() + 1);
+
+    Ok(worker)
+  }
+
+  /// Queues `job` until a worker is free, applying the rejection policy if
+  /// the queue is full.
+  fn enqueue(&self, job: Job) -> Result<(), JsValue> {
+    let mut queue = self.queue.borrow_mut();
+    let priorities: Vec<i32> = queue.iter().map(|queued| queued.options.priority).collect();
+    let slot = queue_slot(
+      &priorities,
+      job.options.priority,
+      self.queue_limit.get(),
+      self.policy.get(),
+    );
+    let (index, dropped) = match slot {
+      Slot::Insert { index, drop } => (index, drop.and_then(|oldest| queue.remove(oldest))),
+      Slot::Refuse => {
+        self.rejected.set(self.rejected.get() + 1);
+        return Err(JsValue::from(format!(
+          "worker pool queue is full ({} jobs)",
+          queue.len()
+        )));
+      }
+    };
+    queue.insert(index, job);
+    drop(queue);
+    if let Some(dropped) = dropped {
+      self.rejected.set(self.rejected.get() + 1);
+      (dropped.on_finish)(Err(JobError::Dropped));
+    }
+    Ok(())
+  }
+
+  /// Posts `job` to `worker`, which must not be idle.
+  fn start(state: &Rc<PoolState>, worker: Worker, job: Job) -> Result<(), JsValue> {
+    let ptr = Box::into_raw(job.work);
+    match worker.post_message(&JsValue::from(ptr as u32)) {
+      Ok(()) => {
+        state.busy.set(state.busy.get() + 1);
+        PoolState::reclaim_on_message(state, worker, job.on_finish, job.options.timeout);
+        Ok(())
+      }
+      Err(e) => {
+        unsafe {
+          drop(Box::from_raw(ptr));
         }
-        workers.push(worker);
+        state.push(worker);
+        Err(e)
+      }
     }
+  }
+
+  /// Waits for `worker` to finish its job, then runs `on_finish` and gives
+  /// the worker the next queued job, or puts it back in the pool. A worker
+  /// that panics, raises an error or is still busy after `timeout`
+  /// milliseconds is terminated and replaced instead, and the job fails.
+  fn reclaim_on_message(
+    state: &Rc<PoolState>,
+    worker: Worker,
+    on_finish: OnFinish,
+    timeout: Option<f64>,
+  ) {
+    let weak = Rc::downgrade(state);
+    // Taken by whichever comes first: the reply, an error or the timeout.
+    let on_finish = Rc::new(RefCell::new(Some(on_finish)));
+    let timer = Rc::new(Cell::new(None));
+    let reclaim_slot = Rc::new(RefCell::new(None));
+    let timeout_slot = Rc::new(RefCell::new(None));
+
+    let finish = {
+      let state = weak.clone();
+      let worker = worker.clone();
+      let timer = timer.clone();
+      let reclaim_slot = reclaim_slot.clone();
+      let timeout_slot = timeout_slot.clone();
+      Rc::new(move |result: Result<(), JobError>| {
+        if let (Some(window), Some(timer)) = (web_sys::window(), timer.take()) {
+          window.clear_timeout_with_handle(timer);
+        }
+        let on_finish = on_finish.borrow_mut().take();
+        if let Some(on_finish) = on_finish {
+          let failed = result.is_err();
+          if failed {
+            worker.set_onmessage(None);
+            worker.set_onerror(None);
+            worker.terminate();
+          }
+          on_finish(result);
+          if let Some(state) = state.upgrade() {
+            state.busy.set(state.busy.get() - 1);
+            if failed {
+              PoolState::replace(&state);
+            } else {
+              PoolState::release(&state, worker.clone());
+            }
+          }
+        }
+
+        *reclaim_slot.borrow_mut() = None;
+        *timeout_slot.borrow_mut() = None;
+      })
+    };
+
+    let finished = finish.clone();
+    let reclaim = Closure::wrap(Box::new(move |event: Event| {
+      if let Some(error) = event.dyn_ref::<ErrorEvent>() {
+        console_log!("error in worker: {}", error.message());
+        finished(Err(JobError::Failed(error.message())));
+        return;
+      }
+
+      if let Some(msg) = event.dyn_ref::<MessageEvent>() {
+        match WorkerPanic::from_message(&msg.data()) {
+          Some(panic) => finished(Err(JobError::Panicked(panic))),
+          None => finished(Ok(())),
+        }
+        return;
+      }
+
+      console_log!("unhandled event: {}", event.type_());
+      crate::logv(&event);
+    }) as Box<dyn FnMut(Event)>);
+
+    worker.set_onmessage(Some(reclaim.as_ref().unchecked_ref()));
+    worker.set_onerror(Some(reclaim.as_ref().unchecked_ref()));
+    *reclaim_slot.borrow_mut() = Some(reclaim);
+
+    let (timeout, window) = match (timeout, web_sys::window()) {
+      (Some(timeout), Some(window)) => (timeout, window),
+      _ => return,
+    };
+    let on_timeout = Closure::wrap(Box::new(move || {
+      console_log!("terminating worker after its job timed out");
+      finish(Err(JobError::TimedOut));
+    }) as Box<dyn FnMut()>);
+    match window.set_timeout_with_callback_and_timeout_and_arguments_0(
+      on_timeout.as_ref().unchecked_ref(),
+      timeout as i32,
+    ) {
+      Ok(handle) => {
+        timer.set(Some(handle));
+        *timeout_slot.borrow_mut() = Some(on_timeout);
+      }
+      Err(e) => {
+        console_log!("failed to arm job timeout");
+        crate::logv(&e);
+      }
+    }
+  }
+
+  /// Hands a worker that finished its job the next queued one, in priority
+  /// order. Queued jobs cancelled in the meantime are skipped.
+  fn release(state: &Rc<PoolState>, worker: Worker) {
+    loop {
+      let next = state.queue.borrow_mut().pop_front();
+      match next {
+        Some(job) if job.is_cancelled() => (job.on_finish)(Err(JobError::Cancelled)),
+        // A job that cannot be posted is dropped, which fails the future of
+        // `run_notify` instead of leaving it pending.
+        Some(job) => {
+          if let Err(e) = PoolState::start(state, worker, job) {
+            console_log!("failed to start queued job");
+            crate::logv(&e);
+          }
+          return;
+        }
+        None => return state.push(worker),
+      }
+    }
+  }
+
+  /// Spawns a worker in place of a terminated one, if there is work waiting
+  /// or the pool fell below its minimum.
+  fn replace(state: &Rc<PoolState>) {
+    if state.queue.borrow().is_empty() && state.len() >= state.min {
+      return;
+    }
+    match state.spawn() {
+      Ok(worker) => PoolState::release(state, worker),
+      Err(e) => {
+        console_log!("failed to replace terminated worker");
+        crate::logv(&e);
+      }
+    }
+  }
+
+  fn push(&self, worker: Worker) {
+    worker.set_onmessage(Some(self.callback.as_ref().unchecked_ref()));
+    worker.set_onerror(Some(self.callback.as_ref().unchecked_ref()));
+    let mut workers = self.idle.borrow_mut();
+    for (prev, _) in workers.iter() {
+      let prev: &JsValue = prev;
+      let worker: &JsValue = &worker;
+      assert!(prev != worker);
+    }
+    workers.push((worker, js_sys::Date::now()));
+    drop(workers);
+    self.schedule_reap();
+  }
+
+  /// Arms the reaper for the longest idle worker, if the pool is above its
+  /// minimum.
+  fn schedule_reap(&self) {
+    if self.reap_timer.get().is_some() || self.len() <= self.min {
+      return;
+    }
+    let since = match self.idle.borrow().first() {
+      Some((_, since)) => *since,
+      None => return,
+    };
+    let delay = (since + self.idle_timeout - js_sys::Date::now()).max(0.0);
+    let (window, reaper) = match (web_sys::window(), self.reaper.borrow().as_ref()) {
+      (Some(window), Some(reaper)) => (
+        window,
+        reaper.as_ref().unchecked_ref::<js_sys::Function>().clone(),
+      ),
+      _ => return,
+    };
+    if let Ok(timer) =
+      window.set_timeout_with_callback_and_timeout_and_arguments_0(&reaper, delay as i32)
+    {
+      self.reap_timer.set(Some(timer));
+    }
+  }
+
+  /// Terminates workers idle for longer than the timeout, down to `min`.
+  fn reap(&self) {
+    let now = js_sys::Date::now();
+    let surplus = self.len().saturating_sub(self.min);
+    let mut idle = self.idle.borrow_mut();
+    let expired = idle
+      .iter()
+      .take(surplus)
+      .take_while(|(_, since)| since + self.idle_timeout <= now)
+      .count();
+    for (worker, _) in idle.drain(..expired) {
+      console_log!("terminating idle worker");
+      worker.terminate();
+    }
+    drop(idle);
+    self.schedule_reap();
+  }
+}
+
+impl Drop for PoolState {
+  fn drop(&mut self) {
+    if let (Some(window), Some(timer)) = (web_sys::window(), self.reap_timer.get()) {
+      window.clear_timeout_with_handle(timer);
+    }
+  }
 }
 
 /// Entry point invoked by `worker.js`, a bit of a hack but see the "TODO" above
@@ -181,8 +730,62 @@ impl PoolState {
 pub fn child_entry_point(ptr: u32) -> Result<(), JsValue> {
     let ptr = unsafe { Box::from_raw(ptr as *mut Work) };
     let global = js_sys::global().unchecked_into::<DedicatedWorkerGlobalScope>();
-    (ptr.func)();
+    // A job that panics posts its report instead and never gets here, see
+    // `panic::set_hook`.
+    panic::run_job(ptr.func);
     global.post_message(&JsValue::undefined())?;
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn slot(queued: &[i32], priority: i32, limit: usize, policy: RejectionPolicy) -> Slot {
+    queue_slot(queued, priority, limit, policy)
+  }
+
+  fn insert(index: usize, drop: Option<usize>) -> Slot {
+    Slot::Insert { index, drop }
+  }
+
+  #[test]
+  fn queues_by_priority_then_in_order() {
+    let reject = RejectionPolicy::Reject;
+    assert_eq!(slot(&[], 0, 8, reject), insert(0, None));
+    // After the jobs of the same priority.
+    assert_eq!(slot(&[0, 0], 0, 8, reject), insert(2, None));
+    assert_eq!(slot(&[5, 1, 1, 0], 1, 8, reject), insert(3, None));
+    // Before the lower ones.
+    assert_eq!(slot(&[5, 1, 0], 3, 8, reject), insert(1, None));
+    assert_eq!(slot(&[5, 1, 0], 9, 8, reject), insert(0, None));
+    assert_eq!(slot(&[5, 1, 0], -1, 8, reject), insert(3, None));
+  }
+
+  #[test]
+  fn full_queue_rejects() {
+    let reject = RejectionPolicy::Reject;
+    assert_eq!(slot(&[1, 0], 5, 2, reject), Slot::Refuse);
+    assert_eq!(slot(&[1, 0], 5, 3, reject), insert(0, None));
+  }
+
+  #[test]
+  fn full_queue_drops_the_oldest_of_the_lowest_priority() {
+    let drop = RejectionPolicy::DropOldest;
+    // The first of the two 0s goes, the new job lands before the other one.
+    assert_eq!(slot(&[2, 0, 0], 1, 3, drop), insert(1, Some(1)));
+    // Same priority as the lowest: it goes after the remaining ones.
+    assert_eq!(slot(&[2, 0, 0], 0, 3, drop), insert(2, Some(1)));
+    assert_eq!(slot(&[2, 2], 2, 2, drop), insert(1, Some(0)));
+    // Below every queued job: refused rather than dropping a better one.
+    assert_eq!(slot(&[2, 1], 0, 2, drop), Slot::Refuse);
+  }
+
+  #[test]
+  fn zero_limit_refuses_everything() {
+    for &policy in &[RejectionPolicy::Reject, RejectionPolicy::DropOldest] {
+      assert_eq!(slot(&[], 0, 0, policy), Slot::Refuse);
+      assert_eq!(slot(&[], 100, 0, policy), Slot::Refuse);
+    }
+  }
+}
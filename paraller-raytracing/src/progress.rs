@@ -0,0 +1,271 @@
+//! Render progress, counted by the rayon workers as they finish tiles and
+//! reported to JS from the main thread.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// A rectangle of the image, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    /// The smallest rectangle containing both.
+    pub fn union(self, other: Rect) -> Rect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+
+    /// Whether it is non-empty and inside a `width` by `height` image. The
+    /// rectangle may come from JS, so its ends can overflow.
+    pub fn fits_in(self, width: u32, height: u32) -> bool {
+        let fits = |start: u32, length: u32, limit: u32| {
+            length > 0 && start.checked_add(length).map_or(false, |end| end <= limit)
+        };
+        fits(self.x, self.width, width) && fits(self.y, self.height, height)
+    }
+}
+
+/// A part of the image rendered as one unit of work.
+struct Unit {
+    rect: Rect,
+    done: AtomicBool,
+}
+
+/// Per-tile progress of a render, shared with the rayon workers.
+pub struct Progress {
+    units: Vec<Unit>,
+    completed: AtomicUsize,
+}
+
+impl Progress {
+    /// Progress over `units`, counted by their index.
+    pub fn new(units: Vec<Rect>) -> Progress {
+        Progress {
+            units: units
+                .into_iter()
+                .map(|rect| Unit {
+                    rect,
+                    done: AtomicBool::new(false),
+                })
+                .collect(),
+            completed: AtomicUsize::new(0),
+        }
+    }
+
+    /// Counts unit `i` as done.
+    pub fn unit_done(&self, i: usize) {
+        if !self.units[i].done.swap(true, SeqCst) {
+            self.completed.fetch_add(1, SeqCst);
+        }
+    }
+
+    pub fn completed(&self) -> usize {
+        self.completed.load(SeqCst)
+    }
+
+    pub fn total(&self) -> usize {
+        self.units.len()
+    }
+
+    /// The area of the units done but not yet `reported`, marking them
+    /// reported, if any.
+    fn dirty(&self, reported: &mut [bool]) -> Option<Rect> {
+        let mut dirty: Option<Rect> = None;
+        for (unit, reported) in self.units.iter().zip(reported.iter_mut()) {
+            if *reported || !unit.done.load(SeqCst) {
+                continue;
+            }
+            *reported = true;
+            dirty = Some(match dirty {
+                Some(dirty) => dirty.union(unit.rect),
+                None => unit.rect,
+            });
+        }
+        dirty
+    }
+
+    /// `{ completed, total }`, in units of work.
+    pub fn to_js(&self) -> JsValue {
+        let object = js_sys::Object::new();
+        set(&object, "completed", self.completed() as f64);
+        set(&object, "total", self.total() as f64);
+        object.into()
+    }
+}
+
+fn set(object: &js_sys::Object, key: &str, value: f64) {
+    let _ = js_sys::Reflect::set(object, &JsValue::from(key), &JsValue::from(value));
+}
+
+/// Calls back into JS with what was rendered since the last call.
+pub struct Reporter {
+    progress: Arc<Progress>,
+    /// Which units were already reported.
+    reported: Vec<bool>,
+    callback: js_sys::Function,
+}
+
+impl Reporter {
+    pub fn new(progress: Arc<Progress>, callback: js_sys::Function) -> Reporter {
+        let reported = vec![false; progress.total()];
+        Reporter {
+            progress,
+            reported,
+            callback,
+        }
+    }
+
+    /// Reports what is already done, for a render that finished before the
+    /// callback was set.
+    pub fn report_once(mut self) {
+        self.report();
+    }
+
+    /// Calls the callback with `{ completed, total, x, y, width, height }`
+    /// if anything was finished since the last report.
+    pub fn report(&mut self) {
+        let rect = match self.progress.dirty(&mut self.reported) {
+            Some(rect) => rect,
+            None => return,
+        };
+        let update = self.progress.to_js();
+        let object: &js_sys::Object = update.unchecked_ref();
+        set(object, "x", rect.x as f64);
+        set(object, "y", rect.y as f64);
+        set(object, "width", rect.width as f64);
+        set(object, "height", rect.height as f64);
+        if let Err(e) = self.callback.call1(&JsValue::undefined(), &update) {
+            crate::logv(&e);
+        }
+    }
+}
+
+/// A `Reporter` called on an interval, until dropped.
+pub struct Reporting {
+    reporter: Rc<RefCell<Reporter>>,
+    interval: i32,
+    _tick: Closure<dyn FnMut()>,
+}
+
+impl Reporting {
+    /// Reports every `interval` milliseconds.
+    pub fn start(reporter: Reporter, interval: f64) -> Result<Reporting, JsValue> {
+        let window = web_sys::window().ok_or_else(|| JsValue::from("no window"))?;
+        let reporter = Rc::new(RefCell::new(reporter));
+        let ticking = reporter.clone();
+        let tick = Closure::wrap(Box::new(move || {
+            ticking.borrow_mut().report();
+        }) as Box<dyn FnMut()>);
+        let interval = window.set_interval_with_callback_and_timeout_and_arguments_0(
+            tick.as_ref().unchecked_ref(),
+            interval as i32,
+        )?;
+        Ok(Reporting {
+            reporter,
+            interval,
+            _tick: tick,
+        })
+    }
+
+    /// Reports what was finished since the last tick and stops.
+    pub fn finish(self) {
+        self.reporter.borrow_mut().report();
+    }
+}
+
+impl Drop for Reporting {
+    fn drop(&mut self) {
+        if let Some(window) = web_sys::window() {
+            window.clear_interval_with_handle(self.interval);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn union_contains_both() {
+        assert_eq!(rect(0, 0, 2, 2).union(rect(4, 1, 2, 3)), rect(0, 0, 6, 4));
+        assert_eq!(rect(4, 1, 2, 3).union(rect(0, 0, 2, 2)), rect(0, 0, 6, 4));
+        assert_eq!(rect(1, 1, 4, 4).union(rect(2, 2, 1, 1)), rect(1, 1, 4, 4));
+    }
+
+    #[test]
+    fn fits_in_only_inside_the_image() {
+        assert!(rect(0, 0, 4, 3).fits_in(4, 3));
+        assert!(rect(3, 2, 1, 1).fits_in(4, 3));
+        assert!(!rect(3, 2, 2, 1).fits_in(4, 3));
+        assert!(!rect(0, 3, 1, 1).fits_in(4, 3));
+        assert!(!rect(0, 0, 0, 3).fits_in(4, 3));
+        assert!(!rect(0, 0, 4, 0).fits_in(4, 3));
+        // Would wrap around to 0 and pass an unchecked `x + width > 4`.
+        assert!(!rect(1, 0, u32::max_value(), 1).fits_in(4, 3));
+        assert!(!rect(0, u32::max_value(), 1, 2).fits_in(4, 3));
+    }
+
+    #[test]
+    fn a_unit_done_twice_counts_once() {
+        let progress = Progress::new(vec![rect(0, 0, 2, 2), rect(2, 0, 2, 2)]);
+        progress.unit_done(1);
+        progress.unit_done(1);
+        assert_eq!(progress.completed(), 1);
+        assert_eq!(progress.total(), 2);
+    }
+
+    #[test]
+    fn dirty_is_the_union_of_the_units_not_yet_reported() {
+        let progress = Progress::new(vec![
+            rect(0, 0, 2, 2),
+            rect(2, 0, 2, 2),
+            rect(0, 2, 2, 2),
+            rect(2, 2, 2, 2),
+        ]);
+        let mut reported = vec![false; progress.total()];
+        progress.unit_done(0);
+        progress.unit_done(3);
+        assert_eq!(progress.dirty(&mut reported), Some(rect(0, 0, 4, 4)));
+        progress.unit_done(1);
+        assert_eq!(progress.dirty(&mut reported), Some(rect(2, 0, 2, 2)));
+        assert_eq!(reported, [true, true, false, true]);
+    }
+
+    #[test]
+    fn nothing_is_dirty_once_everything_was_reported() {
+        let progress = Progress::new(vec![rect(0, 0, 2, 2), rect(2, 0, 2, 2)]);
+        let mut reported = vec![false; progress.total()];
+        assert_eq!(progress.dirty(&mut reported), None);
+        progress.unit_done(0);
+        progress.unit_done(1);
+        assert!(progress.dirty(&mut reported).is_some());
+        assert_eq!(progress.dirty(&mut reported), None);
+        // Counting a unit again does not report it again.
+        progress.unit_done(0);
+        assert_eq!(progress.dirty(&mut reported), None);
+    }
+}
@@ -0,0 +1,449 @@
+//! The parallel render loop, apart from the wasm bindings so that
+//! `benches/tiles.rs` can run it natively.
+//!
+//! The image is split into square tiles, each rendered by one rayon task.
+//! Tiles are handed out in a configurable order, by default spiralling out
+//! from the center of the image, so the first results to show up are the
+//! interesting ones.
+//!
+//! Each pixel averages one or more samples, traced through points spread
+//! over its area. The samples can be taken over several passes, each one
+//! refining the whole image, so that a rough picture shows up early.
+
+use crate::progress::{Progress, Rect};
+use rayon::iter::ParallelBridge;
+use rayon::prelude::*;
+use raytracer::scene::{Color, Scene};
+use std::cmp::Ordering;
+use wasm_bindgen::prelude::*;
+
+/// Side of a tile when none is configured, in pixels.
+pub const DEFAULT_TILE_SIZE: u32 = 32;
+
+/// The order tiles are rendered in.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TileOrder {
+    /// Ring by ring around the center tile.
+    Spiral,
+    /// Along a Hilbert curve, starting at the center tile and alternating
+    /// both ways. Consecutive tiles are neighbours, which keeps the scene
+    /// data they touch warm in the cache.
+    Hilbert,
+    /// Row by row from the top left, like the image is stored.
+    Rows,
+}
+
+/// Where the samples of a pixel are taken.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SamplePattern {
+    /// At the centers of an N by N grid over the pixel in the first pass,
+    /// and anywhere in each cell of the grid in the following ones.
+    Grid,
+    /// At N * N random points of the pixel in every pass.
+    Stochastic,
+}
+
+/// How many samples each pixel gets, and where.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sampling {
+    pub pattern: SamplePattern,
+    /// Samples per side of the pixel in each pass.
+    pub grid: u32,
+    pub passes: u32,
+}
+
+impl Sampling {
+    pub fn per_pass(&self) -> u32 {
+        self.grid * self.grid
+    }
+
+    /// Where sample `sample` of pixel `(x, y)` is taken in pass `pass`,
+    /// relative to the top left corner of the pixel.
+    fn offset(&self, x: u32, y: u32, pass: u32, sample: u32) -> (f64, f64) {
+        let grid = f64::from(self.grid);
+        let cell = (f64::from(sample % self.grid), f64::from(sample / self.grid));
+        match self.pattern {
+            SamplePattern::Grid if pass == 0 => ((cell.0 + 0.5) / grid, (cell.1 + 0.5) / grid),
+            SamplePattern::Grid => {
+                let (dx, dy) = random(x, y, pass, sample);
+                ((cell.0 + dx) / grid, (cell.1 + dy) / grid)
+            }
+            SamplePattern::Stochastic => random(x, y, pass, sample),
+        }
+    }
+}
+
+/// One ray through the center of each pixel, as `Ray::create_prime` does.
+impl Default for Sampling {
+    fn default() -> Sampling {
+        Sampling {
+            pattern: SamplePattern::Grid,
+            grid: 1,
+            passes: 1,
+        }
+    }
+}
+
+/// Two numbers in `[0, 1)`, the same for the same arguments so that renders
+/// are reproducible. Hashed rather than drawn from a generator, which the
+/// threads would have to share.
+fn random(x: u32, y: u32, pass: u32, sample: u32) -> (f64, f64) {
+    let mut hash = u64::from(x) | u64::from(y) << 32;
+    hash ^= (u64::from(pass) << 32 | u64::from(sample)).wrapping_mul(0x9e37_79b9_7f4a_7c15);
+    // The finalizer of splitmix64.
+    hash = (hash ^ hash >> 30).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ hash >> 27).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^= hash >> 31;
+    let unit = |bits: u64| (bits & 0xffff_ffff) as f64 / 4_294_967_296.0;
+    (unit(hash), unit(hash >> 32))
+}
+
+/// Splits a `width` by `height` image into tiles of `size` pixels, smaller
+/// at the right and bottom edges, in rendering order.
+pub fn tiles(width: u32, height: u32, size: u32, order: TileOrder) -> Vec<Rect> {
+    // Not `(width + size - 1) / size`, which overflows for a large size.
+    let count = |length: u32| {
+        if length == 0 {
+            0
+        } else {
+            (length - 1) / size + 1
+        }
+    };
+    let columns = count(width);
+    let rows = count(height);
+    let grid: Vec<(u32, u32)> = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (column, row)))
+        .collect();
+    let center = ((columns as f64 - 1.0) / 2.0, (rows as f64 - 1.0) / 2.0);
+    let ordered = match order {
+        TileOrder::Rows => grid,
+        TileOrder::Spiral => spiral(grid, center),
+        TileOrder::Hilbert => hilbert(grid, columns.max(rows), center),
+    };
+    ordered
+        .into_iter()
+        .map(|(column, row)| Rect {
+            x: column * size,
+            y: row * size,
+            width: size.min(width - column * size),
+            height: size.min(height - row * size),
+        })
+        .collect()
+}
+
+fn spiral(mut grid: Vec<(u32, u32)>, center: (f64, f64)) -> Vec<(u32, u32)> {
+    let key = |&(column, row): &(u32, u32)| {
+        let dx = column as f64 - center.0;
+        let dy = row as f64 - center.1;
+        // Floored, so that the tiles half a tile off the center of an
+        // even grid make up the first ring.
+        (dx.abs().max(dy.abs()).floor(), dy.atan2(dx))
+    };
+    grid.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+    grid
+}
+
+fn hilbert(mut grid: Vec<(u32, u32)>, side: u32, center: (f64, f64)) -> Vec<(u32, u32)> {
+    let side = side.next_power_of_two();
+    grid.sort_by_key(|&(column, row)| hilbert_index(side, column, row));
+    let distance = |&(column, row): &(u32, u32)| {
+        (column as f64 - center.0).powi(2) + (row as f64 - center.1).powi(2)
+    };
+    let start = (0..grid.len())
+        .min_by(|&a, &b| {
+            distance(&grid[a])
+                .partial_cmp(&distance(&grid[b]))
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap_or(0);
+    // start, start + 1, start - 1, start + 2, ... until both ends are reached.
+    let mut ordered = Vec::with_capacity(grid.len());
+    for step in 0..grid.len() {
+        if let Some(&after) = grid.get(start + step) {
+            ordered.push(after);
+        }
+        if step > 0 && step <= start {
+            ordered.push(grid[start - step]);
+        }
+    }
+    ordered
+}
+
+/// Position of `(x, y)` along the Hilbert curve filling a `side` by `side`
+/// square, `side` being a power of two.
+fn hilbert_index(side: u32, mut x: u32, mut y: u32) -> u64 {
+    let mut index = 0;
+    let mut s = side / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as u32;
+        let ry = (y & s > 0) as u32;
+        index += u64::from(s) * u64::from(s) * u64::from((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = side - 1 - x;
+                y = side - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    index
+}
+
+/// An image being rendered: the RGBA bytes shown, and the sums of the
+/// linear colors of the samples they average.
+pub struct Frame {
+    width: u32,
+    rgba: Vec<u8>,
+    sums: Vec<[f32; 3]>,
+}
+
+impl Frame {
+    pub fn new(width: u32, height: u32) -> Frame {
+        let pixels = (width * height) as usize;
+        Frame {
+            width,
+            rgba: vec![0; 4 * pixels],
+            sums: vec![[0.0; 3]; pixels],
+        }
+    }
+
+    /// Four bytes per pixel, row by row. They stay at the same address for
+    /// the life of the frame, so JS can read them while it renders.
+    pub fn rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    /// Forgets the samples taken so far, for a new render. The image still
+    /// shows the previous one until the new samples replace it.
+    pub fn clear(&mut self) {
+        for sum in &mut self.sums {
+            *sum = [0.0; 3];
+        }
+    }
+}
+
+/// The pixels of a frame, written to by several tiles at once.
+#[derive(Clone, Copy)]
+struct Pixels {
+    rgba: *mut u8,
+    sums: *mut [f32; 3],
+    width: u32,
+}
+
+// Tiles never overlap, so no two threads write the same pixel.
+unsafe impl Send for Pixels {}
+unsafe impl Sync for Pixels {}
+
+impl Pixels {
+    /// Adds `color`, made of `samples` more samples, to pixel `(x, y)` and
+    /// shows the new average of its `total` samples.
+    fn add(self, x: u32, y: u32, color: [f32; 3], total: u32) {
+        let i = y as usize * self.width as usize + x as usize;
+        unsafe {
+            let sum = &mut *self.sums.add(i);
+            for (sum, channel) in sum.iter_mut().zip(color.iter()) {
+                *sum += channel;
+            }
+            let average = Color {
+                red: sum[0] / total as f32,
+                green: sum[1] / total as f32,
+                blue: sum[2] / total as f32,
+            };
+            let rgba = average.to_rgba().data;
+            std::ptr::copy_nonoverlapping(rgba.as_ptr(), self.rgba.add(4 * i), 4);
+        }
+    }
+}
+
+fn trace(scene: &Scene, x: u32, y: u32) -> [u8; 4] {
+    let ray = raytracer::Ray::create_prime(x, y, scene);
+    raytracer::cast_ray(scene, &ray, 0).to_rgba().data
+}
+
+/// Traces a ray through the point `(dx, dy)` of pixel `(x, y)`, where
+/// `(0.5, 0.5)` is the center that `Ray::create_prime` aims at.
+fn sample(scene: &Scene, x: u32, y: u32, (dx, dy): (f64, f64)) -> Color {
+    let mut ray = raytracer::Ray::create_prime(x, y, scene);
+    if dx != 0.5 || dy != 0.5 {
+        // Move the point the ray goes through on the sensor, at z = -1,
+        // where a pixel is 2 * tan(fov / 2) / height wide either way.
+        let pixel = 2.0 * (scene.fov.to_radians() / 2.0).tan() / f64::from(scene.height);
+        let direction = &mut ray.direction;
+        let scale = -1.0 / direction.z;
+        let sensor_x = direction.x * scale + (dx - 0.5) * pixel;
+        let sensor_y = direction.y * scale - (dy - 0.5) * pixel;
+        let length = (sensor_x * sensor_x + sensor_y * sensor_y + 1.0).sqrt();
+        direction.x = sensor_x / length;
+        direction.y = sensor_y / length;
+        direction.z = -1.0 / length;
+    }
+    raytracer::cast_ray(scene, &ray, 0)
+}
+
+/// Renders pass `pass` of `sampling` over `tiles` of `scene` into `frame`,
+/// marking each tile done in `progress`, whose units must be the tiles
+/// repeated once per pass. No more tiles start once `stop` returns true.
+/// Passes must be rendered in order, and the call must run in the rayon pool
+/// it should use.
+pub fn render_pass(
+    scene: &Scene,
+    frame: &mut Frame,
+    sampling: Sampling,
+    pass: u32,
+    tiles: &[Rect],
+    progress: &Progress,
+    stop: impl Fn() -> bool + Sync,
+) {
+    assert_eq!(frame.sums.len(), (scene.width * scene.height) as usize);
+    let pixels = Pixels {
+        rgba: frame.rgba.as_mut_ptr(),
+        sums: frame.sums.as_mut_ptr(),
+        width: frame.width,
+    };
+    let per_pass = sampling.per_pass();
+    let total = (pass + 1) * per_pass;
+    let first_unit = pass as usize * tiles.len();
+    // Bridging hands the tiles out one by one in their order, where
+    // splitting the slice would start each thread in a different part.
+    tiles.iter().enumerate().par_bridge().for_each(|(i, tile)| {
+        if stop() {
+            return;
+        }
+        for y in tile.y..tile.y + tile.height {
+            for x in tile.x..tile.x + tile.width {
+                let mut color = [0.0; 3];
+                for s in 0..per_pass {
+                    let c = sample(scene, x, y, sampling.offset(x, y, pass, s));
+                    color[0] += c.red;
+                    color[1] += c.green;
+                    color[2] += c.blue;
+                }
+                pixels.add(x, y, color, total);
+            }
+        }
+        progress.unit_done(first_unit + i);
+    });
+}
+
+/// One rayon task per pixel, as the example used to render. Kept as the
+/// baseline of `benches/tiles.rs`.
+pub fn render_pixels(scene: &Scene, rgba: &mut [u8]) {
+    let width = scene.width;
+    rgba.par_chunks_mut(4).enumerate().for_each(|(i, chunk)| {
+        let i = i as u32;
+        chunk.copy_from_slice(&trace(scene, i % width, i / width));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [TileOrder; 3] = [TileOrder::Spiral, TileOrder::Hilbert, TileOrder::Rows];
+
+    #[test]
+    fn tiles_cover_every_pixel_once() {
+        let (width, height) = (50, 37);
+        for &order in &ORDERS {
+            for &size in &[1, 7, 16, 36, 50, 64, u32::max_value()] {
+                let mut covered = vec![0; (width * height) as usize];
+                for tile in tiles(width, height, size, order) {
+                    assert!(tile.width > 0 && tile.height > 0);
+                    for y in tile.y..tile.y + tile.height {
+                        for x in tile.x..tile.x + tile.width {
+                            covered[(y * width + x) as usize] += 1;
+                        }
+                    }
+                }
+                assert!(
+                    covered.iter().all(|&count| count == 1),
+                    "{:?} tiles of {} pixels",
+                    order,
+                    size
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn an_empty_image_has_no_tiles() {
+        for &order in &ORDERS {
+            assert!(tiles(0, 10, 4, order).is_empty());
+        }
+    }
+
+    #[test]
+    fn the_first_tile_contains_the_center() {
+        for &(width, height, size) in &[(50, 37, 7), (64, 64, 16), (64, 48, 16), (33, 33, 11)] {
+            let center = (f64::from(width) / 2.0, f64::from(height) / 2.0);
+            for &order in &[TileOrder::Spiral, TileOrder::Hilbert] {
+                let first = tiles(width, height, size, order)[0];
+                // On a tile edge when the tiles split the image evenly.
+                let contains = |start: u32, length: u32, point: f64| {
+                    f64::from(start) <= point && point <= f64::from(start + length)
+                };
+                assert!(
+                    contains(first.x, first.width, center.0)
+                        && contains(first.y, first.height, center.1),
+                    "{:?} in {}x{} by {}: {:?}",
+                    order,
+                    width,
+                    height,
+                    size,
+                    first
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_index_is_a_bijection() {
+        for &side in &[1, 2, 4, 8, 16, 32] {
+            let cells = (side * side) as usize;
+            let mut seen = vec![false; cells];
+            for y in 0..side {
+                for x in 0..side {
+                    let index = hilbert_index(side, x, y) as usize;
+                    assert!(index < cells);
+                    assert!(!seen[index], "{} twice in a side of {}", index, side);
+                    seen[index] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn offsets_stay_in_the_pixel() {
+        for &pattern in &[SamplePattern::Grid, SamplePattern::Stochastic] {
+            for grid in 1..5 {
+                let sampling = Sampling {
+                    pattern,
+                    grid,
+                    passes: 4,
+                };
+                for pass in 0..sampling.passes {
+                    for &(x, y) in &[(0, 0), (1, 0), (799, 599), (u32::max_value(), 7)] {
+                        for sample in 0..sampling.per_pass() {
+                            let (dx, dy) = sampling.offset(x, y, pass, sample);
+                            assert!((0.0..1.0).contains(&dx), "{:?}: {}", sampling, dx);
+                            assert!((0.0..1.0).contains(&dy), "{:?}: {}", sampling, dy);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn random_stays_in_the_unit_square() {
+        for x in 0..64 {
+            for sample in 0..64 {
+                let (a, b) = random(x, x * 31, x % 3, sample);
+                assert!((0.0..1.0).contains(&a) && (0.0..1.0).contains(&b));
+            }
+        }
+    }
+}
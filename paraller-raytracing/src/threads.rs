@@ -0,0 +1,291 @@
+//! A rayon thread pool running on the workers of a `WorkerPool`, built once
+//! and shared by every render.
+
+use crate::pool::{JobError, WorkerPool};
+use futures::future::{self, Either, Future};
+use futures::sync::oneshot;
+use js_sys::Promise;
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::rc::Rc;
+use std::sync::Arc;
+use wasm_bindgen::prelude::*;
+
+/// A rayon thread pool whose threads each keep a worker of a `WorkerPool`
+/// busy until `shutdown`.
+///
+/// Each render also takes one more worker to drive the rayon threads from, so
+/// the worker pool must be allowed at least `threads + 1` workers.
+///
+/// A thread dying, e.g. because tracing panicked on it, breaks the pool: the
+/// renders running fail with its error, as do later renders and `shutdown`.
+/// Their driving workers stay blocked on the dead thread, so build a new
+/// worker pool and thread pool to carry on.
+#[wasm_bindgen]
+pub struct ThreadPool {
+    workers: WorkerPool,
+    /// `None` once shut down. Renders hold it too, so the threads only exit
+    /// once the last of them is done.
+    rayon: Option<Arc<rayon::ThreadPool>>,
+    threads: usize,
+    state: Rc<Threads>,
+}
+
+#[wasm_bindgen]
+impl ThreadPool {
+    /// Starts `threads` rayon threads on `workers`.
+    #[wasm_bindgen(constructor)]
+    pub fn new(workers: &WorkerPool, threads: usize) -> Result<ThreadPool, JsValue> {
+        if threads == 0 {
+            return Err(JsValue::from("a thread pool needs at least one thread"));
+        }
+        // Otherwise renders wait forever for a worker to drive them from.
+        if threads >= workers.max() {
+            return Err(JsValue::from(format!(
+                "a thread pool of {} threads needs a worker pool of at least {} workers, not {}",
+                threads,
+                threads + 1,
+                workers.max()
+            )));
+        }
+        let state = Rc::new(Threads::default());
+        let rayon = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .spawn_handler(|thread| {
+                let exit = workers
+                    .run_notify(move || thread.run())
+                    .map_err(|e| io::Error::new(io::ErrorKind::Other, format!("{:?}", e)))?;
+                state.running.set(state.running.get() + 1);
+                // Watched as it happens rather than at `shutdown`, so that a
+                // panic fails the renders waiting on the thread.
+                let exited = state.clone();
+                let watch = exit.then(move |result| {
+                    exited.exited(result);
+                    Ok(JsValue::undefined())
+                });
+                let _ = wasm_bindgen_futures::future_to_promise(watch);
+                Ok(())
+            })
+            .build()
+            .map_err(|e| JsValue::from(e.to_string()))?;
+        Ok(ThreadPool {
+            workers: workers.clone(),
+            rayon: Some(Arc::new(rayon)),
+            threads,
+            state,
+        })
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Stops the threads once the renders using them are done. Returns a
+    /// promise resolving when every thread has exited and given its worker
+    /// back to the pool, or rejecting if the pool is broken. Renders can no
+    /// longer use the pool after this.
+    pub fn shutdown(&mut self) -> Promise {
+        // The threads exit once every handle to the pool is dropped.
+        self.rayon.take();
+        let done = self
+            .state
+            .shutdown()
+            .map(|()| JsValue::undefined())
+            .map_err(JsValue::from);
+        wasm_bindgen_futures::future_to_promise(done)
+    }
+}
+
+impl ThreadPool {
+    /// The pool to run the job driving a render on.
+    pub fn workers(&self) -> &WorkerPool {
+        &self.workers
+    }
+
+    /// The rayon pool to render in, unless it was shut down or is broken.
+    pub fn rayon(&self) -> Result<Arc<rayon::ThreadPool>, JsValue> {
+        self.state.check()?;
+        self.rayon
+            .clone()
+            .ok_or_else(|| JsValue::from("thread pool was shut down"))
+    }
+
+    /// Fails with the error that breaks the pool, for a render to select
+    /// with its own future. Never resolves otherwise.
+    pub fn broken(&self) -> impl Future<Item = (), Error = JobError> {
+        self.state.broken()
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        // Its threads exiting from now on is no failure.
+        self.state.shut_down.set(true);
+    }
+}
+
+/// What the threads of a `ThreadPool` are up to, shared with the futures
+/// watching them exit.
+#[derive(Default)]
+struct Threads {
+    running: Cell<usize>,
+    /// Whether the threads were told to exit, once the renders are done.
+    shut_down: Cell<bool>,
+    /// The error of the first thread to exit without being told to.
+    failure: RefCell<Option<JobError>>,
+    /// Renders to fail once the pool breaks.
+    renders: RefCell<Vec<oneshot::Sender<JobError>>>,
+    /// `shutdown`s waiting for the threads to exit.
+    shutdowns: RefCell<Vec<oneshot::Sender<Result<(), JobError>>>>,
+}
+
+impl Threads {
+    fn check(&self) -> Result<(), JobError> {
+        match *self.failure.borrow() {
+            Some(ref err) => Err(err.clone()),
+            None => Ok(()),
+        }
+    }
+
+    fn broken(&self) -> impl Future<Item = (), Error = JobError> {
+        if let Err(err) = self.check() {
+            return Either::A(future::err(err));
+        }
+        let (tx, rx) = oneshot::channel();
+        let mut renders = self.renders.borrow_mut();
+        // Those of the renders that finished or were cancelled since.
+        renders.retain(|tx| !tx.is_canceled());
+        renders.push(tx);
+        Either::B(rx.then(|result| match result {
+            Ok(err) => Either::A(future::err(err)),
+            // The pool was dropped without breaking.
+            Err(oneshot::Canceled) => Either::B(future::empty()),
+        }))
+    }
+
+    /// Resolves once every thread has exited, or fails right away if the
+    /// pool is broken: the threads blocked by the dead one never exit.
+    fn shutdown(&self) -> impl Future<Item = (), Error = JobError> {
+        self.shut_down.set(true);
+        if let Err(err) = self.check() {
+            return Either::A(future::err(err));
+        }
+        if self.running.get() == 0 {
+            return Either::A(future::ok(()));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.shutdowns.borrow_mut().push(tx);
+        Either::B(rx.then(|result| match result {
+            Ok(result) => result,
+            Err(oneshot::Canceled) => Err(JobError::Dropped),
+        }))
+    }
+
+    fn exited(&self, result: Result<(), JobError>) {
+        self.running.set(self.running.get() - 1);
+        match result {
+            Err(err) => self.fail(err),
+            Ok(()) if !self.shut_down.get() => {
+                self.fail(JobError::Failed("rayon thread exited".to_string()))
+            }
+            Ok(()) => {}
+        }
+        let result = self.check();
+        if result.is_err() || self.running.get() == 0 {
+            for tx in self.shutdowns.borrow_mut().drain(..) {
+                let _ = tx.send(result.clone());
+            }
+        }
+    }
+
+    fn fail(&self, err: JobError) {
+        if self.failure.borrow().is_some() {
+            return;
+        }
+        *self.failure.borrow_mut() = Some(err.clone());
+        for tx in self.renders.borrow_mut().drain(..) {
+            let _ = tx.send(err.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::panic::WorkerPanic;
+
+    fn threads(running: usize) -> Threads {
+        let threads = Threads::default();
+        threads.running.set(running);
+        threads
+    }
+
+    fn tracing_panic() -> JobError {
+        JobError::Panicked(WorkerPanic {
+            message: "index out of bounds".to_string(),
+            location: Some("src/render.rs:1:1".to_string()),
+            backtrace: None,
+        })
+    }
+
+    #[test]
+    fn a_panic_while_tracing_fails_the_running_renders() {
+        let threads = threads(2);
+        let render = threads.broken();
+        threads.exited(Err(tracing_panic()));
+        assert_eq!(render.wait(), Err(tracing_panic()));
+    }
+
+    #[test]
+    fn a_panic_while_tracing_breaks_the_pool() {
+        let threads = threads(2);
+        threads.exited(Err(tracing_panic()));
+        assert_eq!(threads.check(), Err(tracing_panic()));
+        assert_eq!(threads.broken().wait(), Err(tracing_panic()));
+        assert_eq!(threads.shutdown().wait(), Err(tracing_panic()));
+    }
+
+    #[test]
+    fn a_panic_fails_a_shutdown_waiting_for_the_threads() {
+        let threads = threads(2);
+        let shutdown = threads.shutdown();
+        threads.exited(Err(tracing_panic()));
+        assert_eq!(shutdown.wait(), Err(tracing_panic()));
+    }
+
+    #[test]
+    fn finished_renders_stop_waiting_for_a_failure() {
+        let threads = threads(2);
+        for _ in 0..1000 {
+            let render = future::ok::<(), JobError>(()).select(threads.broken());
+            assert!(render.wait().is_ok());
+        }
+        assert!(threads.renders.borrow().len() <= 1);
+    }
+
+    #[test]
+    fn only_the_first_failure_is_kept() {
+        let threads = threads(2);
+        threads.exited(Err(tracing_panic()));
+        threads.exited(Err(JobError::Failed("worker failed to load".to_string())));
+        assert_eq!(threads.check(), Err(tracing_panic()));
+    }
+
+    #[test]
+    fn a_thread_exiting_before_shutdown_breaks_the_pool() {
+        let threads = threads(1);
+        threads.exited(Ok(()));
+        assert!(threads.check().is_err());
+    }
+
+    #[test]
+    fn shutdown_resolves_once_every_thread_exited() {
+        let threads = threads(2);
+        let shutdown = threads.shutdown();
+        threads.exited(Ok(()));
+        assert_eq!(threads.running.get(), 1);
+        threads.exited(Ok(()));
+        assert_eq!(shutdown.wait(), Ok(()));
+        assert_eq!(threads.check(), Ok(()));
+    }
+}
@@ -0,0 +1,525 @@
+//! Checks a scene description before it is rendered, reporting every problem
+//! rather than the first one serde trips on, including scenes that would
+//! parse but render black or divide by zero.
+
+use raytracer::scene::Scene;
+use serde_json::{Map, Value};
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+/// Something wrong with the value at `path` of a scene description, e.g.
+/// `elements[3].Sphere.radius`. The path is empty for the scene itself.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Problem {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.as_str() {
+            "" => write!(f, "scene {}", self.message),
+            path => write!(f, "{} {}", path, self.message),
+        }
+    }
+}
+
+/// Every problem found in a scene description.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvalidScene {
+    pub problems: Vec<Problem>,
+}
+
+impl InvalidScene {
+    fn new(path: &str, message: impl Into<String>) -> InvalidScene {
+        InvalidScene {
+            problems: vec![Problem {
+                path: path.to_string(),
+                message: message.into(),
+            }],
+        }
+    }
+}
+
+impl fmt::Display for InvalidScene {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid scene: ")?;
+        for (i, problem) in self.problems.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// A JS `Error` named `InvalidScene`, with the problems as a `problems`
+/// array of `{ path, message }`.
+impl From<InvalidScene> for JsValue {
+    fn from(err: InvalidScene) -> JsValue {
+        let error = js_sys::Error::new(&err.to_string());
+        error.set_name("InvalidScene");
+        let problems = js_sys::Array::new();
+        for problem in &err.problems {
+            let object = js_sys::Object::new();
+            set(&object, "path", &JsValue::from(problem.path.as_str()));
+            set(&object, "message", &JsValue::from(problem.message.as_str()));
+            problems.push(&object);
+        }
+        set(&error, "problems", &problems);
+        error.into()
+    }
+}
+
+fn set(target: &JsValue, key: &str, value: &JsValue) {
+    let _ = js_sys::Reflect::set(target, &JsValue::from(key), value);
+}
+
+/// Parses a scene description from JS, once it is found valid.
+pub fn scene(object: &JsValue) -> Result<Scene, InvalidScene> {
+    let value: Value = object
+        .into_serde()
+        .map_err(|e| InvalidScene::new("", e.to_string()))?;
+    let mut checker = Checker {
+        problems: Vec::new(),
+    };
+    checker.scene(&value);
+    if !checker.problems.is_empty() {
+        return Err(InvalidScene {
+            problems: checker.problems,
+        });
+    }
+    // Anything the checks missed, e.g. a field the raytracer added since.
+    serde_json::from_value(value).map_err(|e| InvalidScene::new("", e.to_string()))
+}
+
+/// The most pixels an image may have. A `Frame` takes 16 bytes per pixel,
+/// for the RGBA bytes and the sums of the samples, and may use at most half
+/// of the 4 GiB of wasm32 memory, e.g. 16384 by 8192 pixels.
+const MAX_PIXELS: u64 = (1 << 32) / 16 / 2;
+
+fn field_path(path: &str, key: &str) -> String {
+    match path {
+        "" => key.to_string(),
+        path => format!("{}.{}", path, key),
+    }
+}
+
+/// Walks a scene description, collecting problems. The `Option`s returned
+/// are `None` where a problem was reported, so that the values depending on
+/// it are not checked.
+struct Checker {
+    problems: Vec<Problem>,
+}
+
+impl Checker {
+    fn report(&mut self, path: &str, message: &str) {
+        self.problems.push(Problem {
+            path: path.to_string(),
+            message: message.to_string(),
+        });
+    }
+
+    fn object<'a>(&mut self, value: &'a Value, path: &str) -> Option<&'a Map<String, Value>> {
+        let object = value.as_object();
+        if object.is_none() {
+            self.report(path, "must be an object");
+        }
+        object
+    }
+
+    /// The `key` field of the object at `path`, and its path.
+    fn field<'a>(
+        &mut self,
+        object: &'a Map<String, Value>,
+        path: &str,
+        key: &str,
+    ) -> Option<(&'a Value, String)> {
+        let path = field_path(path, key);
+        match object.get(key) {
+            Some(value) => Some((value, path)),
+            None => {
+                self.report(&path, "is missing");
+                None
+            }
+        }
+    }
+
+    fn number(&mut self, object: &Map<String, Value>, path: &str, key: &str) -> Option<f64> {
+        let (value, path) = self.field(object, path, key)?;
+        let number = value.as_f64();
+        if number.is_none() {
+            self.report(&path, "must be a number");
+        }
+        number
+    }
+
+    /// A number that must be positive, e.g. one that is divided by.
+    fn positive(&mut self, object: &Map<String, Value>, path: &str, key: &str) -> Option<f64> {
+        let number = self.number(object, path, key)?;
+        if number <= 0.0 {
+            self.report(&field_path(path, key), "must be positive");
+            return None;
+        }
+        Some(number)
+    }
+
+    fn not_negative(&mut self, object: &Map<String, Value>, path: &str, key: &str) {
+        if let Some(number) = self.number(object, path, key) {
+            if number < 0.0 {
+                self.report(&field_path(path, key), "must not be negative");
+            }
+        }
+    }
+
+    fn fraction(&mut self, object: &Map<String, Value>, path: &str, key: &str) {
+        if let Some(number) = self.number(object, path, key) {
+            if !(0.0..=1.0).contains(&number) {
+                self.report(&field_path(path, key), "must be between 0 and 1");
+            }
+        }
+    }
+
+    /// A `u32`, as the image size and recursion depth are.
+    fn integer(&mut self, object: &Map<String, Value>, path: &str, key: &str) -> Option<u32> {
+        let (value, path) = self.field(object, path, key)?;
+        match value.as_u64() {
+            Some(integer) if integer <= u64::from(u32::max_value()) => Some(integer as u32),
+            _ => {
+                self.report(&path, "must be a whole number from 0 to 4294967295");
+                None
+            }
+        }
+    }
+
+    fn array<'a>(
+        &mut self,
+        object: &'a Map<String, Value>,
+        path: &str,
+        key: &str,
+    ) -> Option<(&'a Vec<Value>, String)> {
+        let (value, path) = self.field(object, path, key)?;
+        match value.as_array() {
+            Some(array) => Some((array, path)),
+            None => {
+                self.report(&path, "must be an array");
+                None
+            }
+        }
+    }
+
+    /// Which of `variants` of an enum the value at `path` is, the value of
+    /// the variant if it has one, and its path. Serde writes unit variants
+    /// as `"Variant"` and the others as `{ "Variant": value }`.
+    fn variant<'a>(
+        &mut self,
+        value: &'a Value,
+        path: &str,
+        variants: &[&str],
+    ) -> Option<(&'a str, Option<&'a Value>, String)> {
+        let variant = match value {
+            Value::String(name) => Some((name.as_str(), None, path.to_string())),
+            Value::Object(object) if object.len() == 1 => object
+                .iter()
+                .next()
+                .map(|(name, value)| (name.as_str(), Some(value), field_path(path, name))),
+            _ => None,
+        };
+        match variant {
+            Some(variant) if variants.contains(&variant.0) => Some(variant),
+            _ => {
+                let message = format!("must be one of {}", variants.join(", "));
+                self.report(path, &message);
+                None
+            }
+        }
+    }
+
+    fn vector(&mut self, object: &Map<String, Value>, path: &str, key: &str) -> Option<[f64; 3]> {
+        let (value, path) = self.field(object, path, key)?;
+        let vector = self.object(value, &path)?;
+        let x = self.number(vector, &path, "x");
+        let y = self.number(vector, &path, "y");
+        let z = self.number(vector, &path, "z");
+        Some([x?, y?, z?])
+    }
+
+    /// A direction, which gets normalized.
+    fn direction(&mut self, object: &Map<String, Value>, path: &str, key: &str) {
+        if self.vector(object, path, key) == Some([0.0; 3]) {
+            self.report(&field_path(path, key), "must not be zero");
+        }
+    }
+
+    fn color(&mut self, value: &Value, path: &str) {
+        if let Some(color) = self.object(value, path) {
+            self.not_negative(color, path, "red");
+            self.not_negative(color, path, "green");
+            self.not_negative(color, path, "blue");
+        }
+    }
+
+    fn scene(&mut self, value: &Value) -> Option<()> {
+        let scene = self.object(value, "")?;
+        let width = self.integer(scene, "", "width");
+        let height = self.integer(scene, "", "height");
+        if width == Some(0) {
+            self.report("width", "must be positive");
+        }
+        if height == Some(0) {
+            self.report("height", "must be positive");
+        }
+        // Ray::create_prime asserts it.
+        if let (Some(width), Some(height)) = (width, height) {
+            if height > width {
+                self.report("height", "must not be greater than the width");
+            }
+            if u64::from(width) * u64::from(height) > MAX_PIXELS {
+                let message = format!("times the height must be at most {} pixels", MAX_PIXELS);
+                self.report("width", &message);
+            }
+        }
+        if let Some(fov) = self.number(scene, "", "fov") {
+            if fov <= 0.0 || fov >= 180.0 {
+                self.report("fov", "must be between 0 and 180 degrees");
+            }
+        }
+        self.not_negative(scene, "", "shadow_bias");
+        self.integer(scene, "", "max_recursion_depth");
+
+        if let Some((elements, path)) = self.array(scene, "", "elements") {
+            if elements.is_empty() {
+                self.report(&path, "must not be empty, or only the background shows");
+            }
+            for (i, element) in elements.iter().enumerate() {
+                self.element(element, &format!("{}[{}]", path, i));
+            }
+        }
+        if let Some((lights, path)) = self.array(scene, "", "lights") {
+            if lights.is_empty() {
+                self.report(&path, "must not be empty, or everything renders black");
+            }
+            for (i, light) in lights.iter().enumerate() {
+                self.light(light, &format!("{}[{}]", path, i));
+            }
+        }
+        Some(())
+    }
+
+    fn element(&mut self, value: &Value, path: &str) -> Option<()> {
+        let (variant, value, path) = self.variant(value, path, &["Sphere", "Plane"])?;
+        let element = self.object(value.unwrap_or(&Value::Null), &path)?;
+        if variant == "Sphere" {
+            self.vector(element, &path, "center");
+            self.positive(element, &path, "radius");
+        } else {
+            self.vector(element, &path, "origin");
+            self.direction(element, &path, "normal");
+        }
+        let (material, path) = self.field(element, &path, "material")?;
+        self.material(material, &path)
+    }
+
+    fn material(&mut self, value: &Value, path: &str) -> Option<()> {
+        let material = self.object(value, path)?;
+        if let Some((coloration, path)) = self.field(material, path, "coloration") {
+            if let Some((_, color, path)) = self.variant(coloration, &path, &["Color"]) {
+                self.color(color.unwrap_or(&Value::Null), &path);
+            }
+        }
+        self.not_negative(material, path, "albedo");
+        let (surface, path) = self.field(material, path, "surface")?;
+        let surfaces = ["Diffuse", "Reflective", "Refractive"];
+        match self.variant(surface, &path, &surfaces)? {
+            ("Reflective", value, path) => {
+                let reflective = self.object(value.unwrap_or(&Value::Null), &path)?;
+                self.fraction(reflective, &path, "reflectivity");
+            }
+            ("Refractive", value, path) => {
+                let refractive = self.object(value.unwrap_or(&Value::Null), &path)?;
+                self.positive(refractive, &path, "index");
+                self.fraction(refractive, &path, "transparency");
+            }
+            (_, Some(value), path) if !value.is_null() => self.report(&path, "takes no value"),
+            _ => {}
+        }
+        Some(())
+    }
+
+    fn light(&mut self, value: &Value, path: &str) -> Option<()> {
+        let lights = ["Directional", "Spherical"];
+        let (variant, value, path) = self.variant(value, path, &lights)?;
+        let light = self.object(value.unwrap_or(&Value::Null), &path)?;
+        if variant == "Directional" {
+            self.direction(light, &path, "direction");
+        } else {
+            self.vector(light, &path, "position");
+        }
+        if let Some((color, path)) = self.field(light, &path, "color") {
+            self.color(color, &path);
+        }
+        self.positive(light, &path, "intensity");
+        Some(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn material() -> Value {
+        json!({
+            "coloration": { "Color": { "red": 1.0, "green": 1.0, "blue": 1.0 } },
+            "albedo": 0.18,
+            "surface": "Diffuse"
+        })
+    }
+
+    fn sphere(radius: f64) -> Value {
+        json!({
+            "Sphere": {
+                "center": { "x": 0.0, "y": 0.0, "z": -5.0 },
+                "radius": radius,
+                "material": material()
+            }
+        })
+    }
+
+    fn directional(direction: Value) -> Value {
+        json!({
+            "Directional": {
+                "direction": direction,
+                "color": { "red": 1.0, "green": 1.0, "blue": 1.0 },
+                "intensity": 1.0
+            }
+        })
+    }
+
+    fn scene() -> Value {
+        json!({
+            "width": 800,
+            "height": 600,
+            "fov": 90.0,
+            "shadow_bias": 1e-13,
+            "max_recursion_depth": 10,
+            "elements": [sphere(1.0)],
+            "lights": [directional(json!({ "x": 0.0, "y": 0.0, "z": -1.0 }))]
+        })
+    }
+
+    fn problems(value: &Value) -> Vec<String> {
+        let mut checker = Checker {
+            problems: Vec::new(),
+        };
+        checker.scene(value);
+        checker.problems.iter().map(Problem::to_string).collect()
+    }
+
+    #[test]
+    fn accepts_a_valid_scene() {
+        assert_eq!(problems(&scene()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn reports_the_path_of_a_problem_in_an_element() {
+        let mut scene = scene();
+        scene["elements"] = json!([sphere(1.0), sphere(2.0), sphere(3.0), sphere(0.0)]);
+        assert_eq!(
+            problems(&scene),
+            ["elements[3].Sphere.radius must be positive"]
+        );
+    }
+
+    #[test]
+    fn reports_the_path_of_a_problem_in_a_light() {
+        let mut scene = scene();
+        scene["lights"] = json!([directional(json!({ "x": 0.0, "y": 0.0, "z": 0.0 }))]);
+        assert_eq!(
+            problems(&scene),
+            ["lights[0].Directional.direction must not be zero"]
+        );
+    }
+
+    #[test]
+    fn reports_every_problem_together() {
+        let mut scene = scene();
+        scene["width"] = json!(0);
+        scene["fov"] = json!(180.0);
+        scene["elements"][0]["Sphere"]["material"]["albedo"] = json!(-1.0);
+        scene["lights"] = json!([]);
+        assert_eq!(
+            problems(&scene),
+            [
+                "width must be positive",
+                "height must not be greater than the width",
+                "fov must be between 0 and 180 degrees",
+                "elements[0].Sphere.material.albedo must not be negative",
+                "lights must not be empty, or everything renders black",
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_zero_width() {
+        let mut scene = scene();
+        scene["width"] = json!(0);
+        scene["height"] = json!(0);
+        assert_eq!(
+            problems(&scene),
+            ["width must be positive", "height must be positive"]
+        );
+    }
+
+    #[test]
+    fn rejects_a_height_greater_than_the_width() {
+        let mut scene = scene();
+        scene["height"] = json!(801);
+        assert_eq!(
+            problems(&scene),
+            ["height must not be greater than the width"]
+        );
+    }
+
+    #[test]
+    fn rejects_too_many_pixels() {
+        let mut scene = scene();
+        scene["width"] = json!(u32::max_value());
+        scene["height"] = json!(u32::max_value());
+        assert_eq!(
+            problems(&scene),
+            ["width times the height must be at most 134217728 pixels"]
+        );
+    }
+
+    #[test]
+    fn rejects_empty_lights() {
+        let mut scene = scene();
+        scene["lights"] = json!([]);
+        assert_eq!(
+            problems(&scene),
+            ["lights must not be empty, or everything renders black"]
+        );
+    }
+
+    #[test]
+    fn rejects_an_unknown_variant() {
+        let mut scene = scene();
+        scene["elements"] = json!([{ "Cube": { "size": 1.0 } }]);
+        assert_eq!(
+            problems(&scene),
+            ["elements[0] must be one of Sphere, Plane"]
+        );
+    }
+
+    #[test]
+    fn rejects_a_value_for_a_unit_variant() {
+        let mut scene = scene();
+        scene["elements"][0]["Sphere"]["material"]["surface"] = json!({ "Diffuse": 1.0 });
+        assert_eq!(
+            problems(&scene),
+            ["elements[0].Sphere.material.surface.Diffuse takes no value"]
+        );
+    }
+}
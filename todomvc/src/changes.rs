@@ -0,0 +1,136 @@
+//! Change events published by `Store::subscribe`, and views derived from
+//! them.
+
+use crate::store::{Item, ItemList, ItemListTrait};
+use std::collections::HashMap;
+
+/// A change to an item of the current list.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Change {
+  Inserted(Item),
+  Updated { before: Item, after: Item },
+  Removed(Item),
+  /// The current list was replaced as a whole, e.g. by switching lists.
+  /// Derived views start over from the items it carries.
+  Reset(Vec<Item>),
+}
+
+/// The changes from `before` to `after`, matching items by id: removals
+/// first, then updates and insertions in the order of `after`.
+pub fn diff(before: &ItemList, after: &ItemList) -> Vec<Change> {
+  let old: HashMap<&str, &Item> = before.iter().map(|item| (item.id.as_str(), item)).collect();
+  let new: HashMap<&str, &Item> = after.iter().map(|item| (item.id.as_str(), item)).collect();
+  let mut changes: Vec<Change> = before
+    .iter()
+    .filter(|item| !new.contains_key(item.id.as_str()))
+    .map(|item| Change::Removed(item.clone()))
+    .collect();
+  for item in after.iter() {
+    match old.get(item.id.as_str()) {
+      Some(previous) if *previous != item => changes.push(Change::Updated {
+        before: (*previous).clone(),
+        after: item.clone(),
+      }),
+      Some(_) => {}
+      None => changes.push(Change::Inserted(item.clone())),
+    }
+  }
+  changes
+}
+
+/// `ItemList::count` kept up to date from change events instead of scanning
+/// the list every time.
+#[derive(Debug, Default)]
+pub struct ItemCounts {
+  /// Whether each item is completed.
+  items: HashMap<String, bool>,
+  /// Number of subtasks of every item that has any.
+  children: HashMap<String, usize>,
+  total: usize,
+  completed: usize,
+}
+
+impl ItemCounts {
+  /// Counts of no items, until the first `Change::Reset`.
+  pub fn new() -> ItemCounts {
+    ItemCounts::default()
+  }
+
+  /// Counts as `(total, active, completed)`, see `ItemList::count`.
+  pub fn get(&self) -> (usize, usize, usize) {
+    (self.total, self.total - self.completed, self.completed)
+  }
+
+  pub fn apply(&mut self, changes: &[Change]) {
+    for change in changes {
+      match change {
+        Change::Inserted(item) => self.replace(&item.id, None, Some(item)),
+        Change::Updated { before, after } => self.replace(&after.id, Some(before), Some(after)),
+        Change::Removed(item) => self.replace(&item.id, Some(item), None),
+        Change::Reset(items) => self.reset(items),
+      }
+    }
+  }
+
+  fn reset(&mut self, items: &[Item]) {
+    *self = ItemCounts::default();
+    for item in items {
+      self.replace(&item.id, None, Some(item));
+    }
+  }
+
+  /// Whether `id` counts as a todo, as `(counted, completed)`. Parents only
+  /// summarize their subtasks.
+  fn contribution(&self, id: &str) -> (usize, usize) {
+    match self.items.get(id) {
+      Some(completed) if !self.children.contains_key(id) => (1, *completed as usize),
+      _ => (0, 0),
+    }
+  }
+
+  /// Replaces `before` with `after`, re-counting the item and the parents
+  /// that may have gained or lost their last subtask.
+  fn replace(&mut self, id: &str, before: Option<&Item>, after: Option<&Item>) {
+    let mut affected = vec![id.to_string()];
+    for parent in before.iter().chain(after.iter()).filter_map(|item| item.parent.as_ref()) {
+      if !affected.contains(parent) {
+        affected.push(parent.clone());
+      }
+    }
+    for id in affected.iter() {
+      let (total, completed) = self.contribution(id);
+      self.total -= total;
+      self.completed -= completed;
+    }
+
+    if let Some(parent) = before.and_then(|item| item.parent.as_ref()) {
+      let now_leaf = match self.children.get_mut(parent) {
+        Some(count) => {
+          *count -= 1;
+          *count == 0
+        }
+        None => false,
+      };
+      if now_leaf {
+        self.children.remove(parent);
+      }
+    }
+    match after {
+      Some(item) => {
+        if let Some(ref parent) = item.parent {
+          *self.children.entry(parent.clone()).or_insert(0) += 1;
+        }
+        self.items.insert(id.to_string(), item.completed);
+      }
+      None => {
+        self.items.remove(id);
+      }
+    }
+
+    for id in affected.iter() {
+      let (total, completed) = self.contribution(id);
+      self.total += total;
+      self.completed += completed;
+    }
+  }
+}
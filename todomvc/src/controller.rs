@@ -1,17 +1,29 @@
-use crate::exit;
+use crate::changes::ItemCounts;
+use crate::lists::{self, Route};
+use crate::platform::Clock;
+use crate::preferences::Preferences;
+use crate::recurrence::{DateTime, Rule};
+use crate::reminders::Reminder;
 use crate::store::*;
 use crate::view::ViewMessage;
 use crate::{Message, Scheduler};
-use js_sys::Date;
 
-use std::cell::RefCell;
-use std::rc::Weak;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
 
 pub struct Controller {
   store: Store,
+  clock: Box<dyn Clock>,
+  ids: Ids,
   sched: RefCell<Option<Weak<Scheduler>>>,
   active_route: String,
   last_active_route: String,
+  /// Words the shown items are searched for, shown unranked when empty.
+  search: String,
+  /// What the view was last asked to remind of.
+  reminders: Option<Vec<Reminder>>,
+  /// Counts of the current list, kept up to date by the store.
+  counts: Rc<RefCell<ItemCounts>>,
 }
 
 pub enum ControllerMessage {
@@ -23,15 +35,40 @@ pub enum ControllerMessage {
   RemoveItem(String),
   ToggleAll(bool),
   ToggleItem(String, bool),
+  SetPreferences(Preferences),
+  CreateList(String),
+  RenameList(String, String),
+  DeleteList(String),
+  ArchiveList(String, bool),
+  MoveItem(String, String),
+  AddSubtask(String, String),
+  Indent(String),
+  Outdent(String),
+  SetSchedule(String, Option<DateTime>, Option<Rule>),
+  SetNotes(String, String),
+  Search(String),
+  /// Batch operations on the selected items, each written to storage once.
+  CompleteItems(Vec<String>, bool),
+  RemoveItems(Vec<String>),
+  MoveItems(Vec<String>, String),
+  TagItems(Vec<String>, String),
 }
 
 impl Controller {
-  pub fn new(store: Store, sched: Weak<Scheduler>) -> Controller {
+  pub fn new(mut store: Store, clock: Box<dyn Clock>, sched: Weak<Scheduler>) -> Controller {
+    let counts = Rc::new(RefCell::new(ItemCounts::new()));
+    let subscriber = counts.clone();
+    store.subscribe(Box::new(move |changes| subscriber.borrow_mut().apply(changes)));
     Controller {
       store,
+      clock,
+      ids: Ids::default(),
       sched: RefCell::new(Some(sched)),
       active_route: "".into(),
       last_active_route: "none".into(),
+      search: String::new(),
+      reminders: None,
+      counts,
     }
   }
 
@@ -46,6 +83,22 @@ impl Controller {
       RemoveItem(id) => self.remove_item(&id),
       ToggleAll(completed) => self.toggle_all(completed),
       ToggleItem(id, completed) => self.toggle_item(id, completed),
+      SetPreferences(preferences) => self.set_preferences(preferences),
+      CreateList(name) => self.create_list(name),
+      RenameList(from, to) => self.rename_list(from, to),
+      DeleteList(name) => self.delete_list(name),
+      ArchiveList(name, archived) => self.archive_list(name, archived),
+      MoveItem(id, list) => self.move_item(id, list),
+      AddSubtask(parent, title) => self.add_subtask(parent, title),
+      Indent(id) => self.indent(id),
+      Outdent(id) => self.outdent(id),
+      SetSchedule(id, due, recurrence) => self.set_schedule(id, due, recurrence),
+      SetNotes(id, notes) => self.set_notes(id, notes),
+      Search(search) => self.search(search),
+      CompleteItems(ids, completed) => self.complete_items(ids, completed),
+      RemoveItems(ids) => self.remove_items(ids),
+      MoveItems(ids, list) => self.move_items(ids, list),
+      TagItems(ids, tag) => self.tag_items(ids, tag),
     }
   }
 
@@ -60,20 +113,178 @@ impl Controller {
   }
 
   fn add_item(&mut self, title: String) {
-    self.store.insert(Item {
-      id: Date::now().to_string(),
-      title,
-      completed: false,
-    });
+    self.store.insert(Item::new(self.ids.next(&*self.clock), title));
     self.add_message(ViewMessage::ClearNewTodo());
     self._filter(true);
   }
 
+  fn add_subtask(&mut self, parent: String, title: String) {
+    let exists = self
+      .store
+      .find(ItemQuery::Id { id: parent.clone() })
+      .map_or(false, |data| data.length() > 0);
+    if exists {
+      self.store.insert(Item {
+        parent: Some(parent),
+        ..Item::new(self.ids.next(&*self.clock), title)
+      });
+      self._filter(true);
+    }
+  }
+
+  fn indent(&mut self, id: String) {
+    if self.store.indent(&id) {
+      self._filter(true);
+    }
+  }
+
+  fn outdent(&mut self, id: String) {
+    if self.store.outdent(&id) {
+      self._filter(true);
+    }
+  }
+
+  /// Repeating items start from their due date, which defaults to now.
+  fn set_schedule(&mut self, id: String, due: Option<DateTime>, recurrence: Option<Rule>) {
+    let exists = self
+      .store
+      .find(ItemQuery::Id { id: id.clone() })
+      .map_or(false, |data| data.length() > 0);
+    if exists {
+      let due = match (due, &recurrence) {
+        (None, Some(_)) => Some(local_now(&*self.clock)),
+        (due, _) => due,
+      };
+      self.store.update(ItemUpdate::Schedule {
+        id,
+        due,
+        recurrence,
+      });
+      self._filter(true);
+    }
+  }
+
+  /// Blank notes are removed.
+  fn set_notes(&mut self, id: String, notes: String) {
+    let exists = self
+      .store
+      .find(ItemQuery::Id { id: id.clone() })
+      .map_or(false, |data| data.length() > 0);
+    if exists {
+      let notes = Some(notes).filter(|notes| !notes.trim().is_empty());
+      self.store.update(ItemUpdate::Notes { id, notes });
+      self._filter(true);
+    }
+  }
+
+  /// Ids of the open items that repeat.
+  fn open_recurring(&mut self) -> Vec<String> {
+    let mut ids = Vec::new();
+    if let Some(data) = self.store.find(ItemQuery::EmptyItemQuery) {
+      for item in data.iter() {
+        if !item.completed && item.recurrence.is_some() {
+          ids.push(item.id.clone());
+        }
+      }
+    }
+    ids
+  }
+
+  fn search(&mut self, search: String) {
+    if search.trim() != self.search.trim() {
+      self.search = search;
+      self._filter(true);
+    }
+  }
+
   pub fn set_page(&mut self, raw: String) {
-    let route = raw.trim_start_matches("#/");
+    let Route { list, filter } = Route::parse(&raw);
+    // Unknown lists leave the current one open.
+    let switched = match list {
+      Some(list) => list != self.store.list() && self.store.switch_list(&list),
+      None => false,
+    };
+    let route = if raw.is_empty() {
+      // Opened without a hash
+      self.store.preferences().default_filter.clone()
+    } else {
+      filter
+    };
     self.active_route = route.to_string();
-    self._filter(false);
-    self.add_message(ViewMessage::UpdateFilterButtons(route.to_string()));
+    self._filter(switched);
+    self.show_lists();
+    self.add_message(ViewMessage::UpdateFilterButtons(route));
+  }
+
+  fn show_lists(&mut self) {
+    self.add_message(ViewMessage::ShowLists(
+      self.store.lists().to_vec(),
+      self.store.list().to_string(),
+    ));
+    // Reminders name their list.
+    self.schedule_reminders();
+  }
+
+  /// Shows `list` with the active filter and points the location at it.
+  fn open_list(&mut self, list: &str) {
+    self.store.switch_list(list);
+    self._filter(true);
+    self.show_lists();
+    self.add_message(ViewMessage::Navigate(lists::route(
+      list,
+      &self.active_route,
+    )));
+  }
+
+  fn create_list(&mut self, name: String) {
+    if let Some(name) = lists::list_name(&name) {
+      if self.store.create_list(&name) {
+        self.open_list(&name);
+      }
+    }
+  }
+
+  fn rename_list(&mut self, from: String, to: String) {
+    if let Some(to) = lists::list_name(&to) {
+      if self.store.rename_list(&from, &to) {
+        if self.store.list() == to {
+          self.open_list(&to);
+        } else {
+          self.show_lists();
+        }
+      }
+    }
+  }
+
+  fn delete_list(&mut self, name: String) {
+    let current = self.store.list() == name;
+    if self.store.delete_list(&name) {
+      if current {
+        let list = self.store.list().to_string();
+        self.open_list(&list);
+      } else {
+        self.show_lists();
+      }
+    }
+  }
+
+  fn archive_list(&mut self, name: String, archived: bool) {
+    if self.store.archive_list(&name, archived) {
+      self.show_lists();
+    }
+  }
+
+  fn move_item(&mut self, id: String, list: String) {
+    let nested = self.store.is_nested(&id);
+    if self.store.move_item(&id, &list) {
+      self._filter(nested);
+      self.add_message(ViewMessage::RemoveItem(id));
+    }
+  }
+
+  fn set_preferences(&mut self, preferences: Preferences) {
+    self.store.set_preferences(preferences.clone());
+    self.add_message(ViewMessage::ApplyPreferences(preferences));
   }
 
   fn edit_item_save(&mut self, id: String, title: String) {
@@ -83,6 +294,12 @@ impl Controller {
         title: title.clone(),
       });
       self.add_message(ViewMessage::EditItemDone(id.to_string(), title.to_string()));
+      if self.search.trim().is_empty() {
+        self.schedule_reminders();
+      } else {
+        // The new title may match differently.
+        self._filter(true);
+      }
     } else {
       self.remove_item(&id);
     }
@@ -104,8 +321,10 @@ impl Controller {
   }
 
   fn remove_item(&mut self, id: &String) {
+    // Removing a subtask may complete its parent.
+    let nested = self.store.is_nested(id);
     self.store.remove(ItemQuery::Id { id: id.clone() });
-    self._filter(false);
+    self._filter(nested);
 
     let ritem = id.to_string();
     self.add_message(ViewMessage::RemoveItem(ritem));
@@ -127,8 +346,12 @@ impl Controller {
   }
 
   fn toggle_item(&mut self, id: String, completed: bool) {
+    // Subtasks and parents change each other, so the whole list is redrawn.
+    let nested = self.store.is_nested(&id);
+    let recurring = self.open_recurring();
     self.toggle_completed(id, completed);
-    self._filter(completed);
+    spawn_next_occurrences(&mut self.store, &*self.clock, &self.ids, recurring);
+    self._filter(completed || nested);
   }
 
   fn toggle_all(&mut self, completed: bool) {
@@ -138,31 +361,78 @@ impl Controller {
         vals.push(item.id.clone());
       }
     });
+    self.complete_items(vals, completed);
+  }
+
+  /// Completes or reopens `ids`, written to storage once. The list is
+  /// redrawn for the progress of parents.
+  fn complete_items(&mut self, ids: Vec<String>, completed: bool) {
+    let recurring = self.open_recurring();
+    let clock = &*self.clock;
+    let new_ids = &self.ids;
+    self.store.batch(|store| {
+      for id in ids {
+        store.update(ItemUpdate::Completed { id, completed });
+      }
+      spawn_next_occurrences(store, clock, new_ids, recurring);
+    });
+    self._filter(true);
+  }
+
+  fn remove_items(&mut self, ids: Vec<String>) {
+    self.store.batch(|store| {
+      for id in ids {
+        store.remove(ItemQuery::Id { id });
+      }
+    });
+    self._filter(true);
+  }
 
-    for id in vals.iter() {
-      self.toggle_completed(id.to_string(), completed);
+  fn move_items(&mut self, ids: Vec<String>, list: String) {
+    if self.store.move_items(&ids, &list) {
+      self._filter(true);
     }
+  }
 
-    self._filter(false);
+  /// Tags are words starting with `#` at the end of the title, so they can
+  /// be searched for like any other word.
+  fn tag_items(&mut self, ids: Vec<String>, tag: String) {
+    let words: Vec<&str> = tag.trim().trim_start_matches('#').split_whitespace().collect();
+    let tag = words.join("-");
+    if tag.is_empty() {
+      return;
+    }
+    let mut titles = Vec::new();
+    if let Some(data) = self.store.find(ItemQuery::EmptyItemQuery) {
+      for item in data.iter().filter(|item| ids.contains(&item.id)) {
+        if let Some(title) = tag_title(&item.title, &tag) {
+          titles.push((item.id.clone(), title));
+        }
+      }
+    }
+    self.store.batch(|store| {
+      for (id, title) in titles {
+        store.update(ItemUpdate::Title { id, title });
+      }
+    });
+    self._filter(true);
   }
 
   fn _filter(&mut self, force: bool) {
     let route = &self.active_route;
 
     if force || self.last_active_route != "" || &self.last_active_route != route {
-      let query = match route.as_str() {
-        "completed" => ItemQuery::Completed { completed: true },
-        "active" => ItemQuery::Completed { completed: false },
-        _ => ItemQuery::EmptyItemQuery,
-      };
+      let query = ItemQuery::for_route(route);
 
       let mut v = None;
 
-      {
+      if self.search.trim().is_empty() {
         let store = &mut self.store;
         if let Some(res) = store.find(query) {
           v = Some(res.into());
         }
+      } else {
+        v = Some(self.store.search(&self.search, query));
       }
 
       if let Some(res) = v {
@@ -170,21 +440,108 @@ impl Controller {
       }
     }
 
-    if let Some((total, active, completed)) = self.store.count() {
-      self.add_message(ViewMessage::SetItemsLeft(active));
-      self.add_message(ViewMessage::SetClearCompletedButtonVisibility(
-        completed > 0,
-      ));
-      self.add_message(ViewMessage::SetCompleteAllCheckbox(completed == total));
-      self.add_message(ViewMessage::SetMainVisibility(total > 0));
-    }
+    let (total, active, completed) = self.counts.borrow().get();
+    self.add_message(ViewMessage::SetItemsLeft(active));
+    self.add_message(ViewMessage::SetClearCompletedButtonVisibility(
+      completed > 0,
+    ));
+    self.add_message(ViewMessage::SetCompleteAllCheckbox(completed == total));
+    self.add_message(ViewMessage::SetMainVisibility(total > 0));
 
     self.last_active_route = route.to_string();
+    self.schedule_reminders();
+  }
+
+  /// Sends the reminders of all lists to the view when they changed, the
+  /// first time when the page loads.
+  fn schedule_reminders(&mut self) {
+    let reminders: Vec<Reminder> = self
+      .store
+      .scheduled()
+      .into_iter()
+      .filter_map(|(list, item)| {
+        Some(Reminder {
+          due: item.due?,
+          id: item.id,
+          title: item.title,
+          list,
+        })
+      })
+      .collect();
+    if self.reminders.as_ref() != Some(&reminders) {
+      self.reminders = Some(reminders.clone());
+      self.add_message(ViewMessage::ScheduleReminders(reminders));
+    }
+  }
+}
+
+fn local_now(clock: &dyn Clock) -> DateTime {
+  DateTime::from_millis(clock.now() - clock.timezone_offset() * 60_000.0)
+}
+
+/// Adds the next occurrence of each item of `ids` that is completed now.
+/// The completed one stops repeating, so reopening and completing it again
+/// doesn't add another.
+fn spawn_next_occurrences(store: &mut Store, clock: &dyn Clock, new_ids: &Ids, ids: Vec<String>) {
+  for id in ids {
+    let item = store
+      .find(ItemQuery::Id { id: id.clone() })
+      .and_then(|data| data.get(0).map(|item| (*item).clone()));
+    let item = match item {
+      Some(item) => item,
+      None => continue,
+    };
+    let (due, rule) = match (item.due, item.recurrence) {
+      (Some(due), Some(ref rule)) if item.completed => (due, rule.clone()),
+      _ => continue,
+    };
+    if let Some((next, rule)) = rule.next_occurrence(due, local_now(clock)) {
+      store.insert(Item {
+        parent: item.parent.clone(),
+        due: Some(next),
+        recurrence: Some(rule),
+        notes: item.notes.clone(),
+        ..Item::new(new_ids.next(clock), item.title.clone())
+      });
+    }
+    store.update(ItemUpdate::Schedule {
+      id,
+      due: Some(due),
+      recurrence: None,
+    });
+  }
+}
+
+/// Hands out item ids: the time in milliseconds, moved past the last id
+/// handed out, so that items created within the same millisecond, like the
+/// next occurrences of a batch, still get different ids.
+#[derive(Default)]
+struct Ids {
+  last: Cell<f64>,
+}
+
+impl Ids {
+  fn next(&self, clock: &dyn Clock) -> String {
+    let id = clock.now().max(self.last.get() + 1.0);
+    self.last.set(id);
+    id.to_string()
+  }
+}
+
+/// `title` tagged `#tag`, unless it already is.
+fn tag_title(title: &str, tag: &str) -> Option<String> {
+  let tagged = format!("#{}", tag);
+  if title.split_whitespace().any(|word| word == tagged) {
+    None
+  } else {
+    Some(format!("{} {}", title, tagged))
   }
 }
 
+// The controller lives as long as the page, natively it may be dropped.
+#[cfg(target_arch = "wasm32")]
 impl Drop for Controller {
   fn drop(&mut self) {
-    exit("calling drop on Controller");
+    crate::exit("calling drop on Controller");
   }
 }
@@ -1,322 +1,353 @@
+use std::convert::TryFrom;
+use std::fmt;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::EventTarget;
 
+/// Errors raised while talking to the DOM.
+#[derive(Debug)]
+pub enum DomError {
+    /// Nothing matched the selector, or window/document/body is unavailable.
+    Missing(String),
+    /// The element exists but is not of the type the operation needs.
+    WrongType(&'static str),
+    /// A DOM call threw a JS exception.
+    Js(JsValue),
+}
+
+impl fmt::Display for DomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomError::Missing(what) => write!(f, "element missing: {}", what),
+            DomError::WrongType(expected) => write!(f, "wrong element type, expected {}", expected),
+            DomError::Js(value) => match value.as_string() {
+                Some(message) => write!(f, "js exception: {}", message),
+                None => write!(f, "js exception: {:?}", value),
+            },
+        }
+    }
+}
+
+impl From<JsValue> for DomError {
+    fn from(value: JsValue) -> DomError {
+        DomError::Js(value)
+    }
+}
+
+impl From<DomError> for JsValue {
+    fn from(err: DomError) -> JsValue {
+        match err {
+            DomError::Js(value) => value,
+            err => JsValue::from_str(&err.to_string()),
+        }
+    }
+}
+
+fn document() -> Result<web_sys::Document, DomError> {
+    web_sys::window()
+        .ok_or_else(|| DomError::Missing("window".to_string()))?
+        .document()
+        .ok_or_else(|| DomError::Missing("document".to_string()))
+}
+
+#[derive(Clone)]
 pub struct Element {
-    el: Option<web_sys::Element>,
+    el: web_sys::Element,
 }
 
 impl From<web_sys::Element> for Element {
     fn from(el: web_sys::Element) -> Element {
-        Element { el: Some(el) }
+        Element { el }
     }
 }
 
-impl From<web_sys::EventTarget> for Element {
-    fn from(el: web_sys::EventTarget) -> Element {
-        let el = wasm_bindgen::JsCast::dyn_into::<web_sys::Element>(el);
-        Element { el: el.ok() }
+impl TryFrom<web_sys::EventTarget> for Element {
+    type Error = DomError;
+
+    fn try_from(el: web_sys::EventTarget) -> Result<Element, DomError> {
+        let el = el
+            .dyn_into::<web_sys::Element>()
+            .map_err(|_| DomError::WrongType("Element"))?;
+        Ok(Element { el })
     }
 }
 
-impl From<Element> for Option<web_sys::Node> {
-    fn from(obj: Element) -> Option<web_sys::Node> {
-        if let Some(el) = obj.el {
-            Some(el.into())
-        } else {
-            None
-        }
+impl From<Element> for web_sys::Node {
+    fn from(obj: Element) -> web_sys::Node {
+        obj.el.into()
     }
 }
 
-impl From<Element> for Option<EventTarget> {
-    fn from(obj: Element) -> Option<EventTarget> {
-        if let Some(el) = obj.el {
-            Some(el.into())
-        } else {
-            None
-        }
+impl From<Element> for EventTarget {
+    fn from(obj: Element) -> EventTarget {
+        obj.el.into()
     }
 }
 
 impl Element {
-    pub fn create_element(tag: &str) -> Option<Element> {
-        if let Some(el) = web_sys::window()?.document()?.create_element(tag).ok() {
-            Some(el.into())
-        } else {
-            None
-        }
+    pub fn create_element(tag: &str) -> Result<Element, DomError> {
+        Ok(document()?.create_element(tag)?.into())
+    }
+
+    /// Finds the first element in the body matching `selector`.
+    pub fn qs(selector: &str) -> Result<Element, DomError> {
+        Element::try_qs(selector)?.ok_or_else(|| DomError::Missing(selector.to_string()))
     }
 
-    pub fn qs(selector: &str) -> Option<Element> {
-        let body: web_sys::Element = web_sys::window()?.document()?.body()?.into();
-        let el = body.query_selector(selector).ok()?;
-        Some(Element { el })
+    /// Like `qs`, but a selector that matches nothing is not an error.
+    pub fn try_qs(selector: &str) -> Result<Option<Element>, DomError> {
+        Element::body()?.try_qs_from(selector)
     }
 
-    pub fn add_event_listener<T>(&mut self, event_name: &str, handler: T)
+    pub fn body() -> Result<Element, DomError> {
+        let body: web_sys::Element = document()?
+            .body()
+            .ok_or_else(|| DomError::Missing("body".to_string()))?
+            .into();
+        Ok(body.into())
+    }
+
+    /// The root `<html>` element.
+    pub fn document_element() -> Result<Element, DomError> {
+        document()?
+            .document_element()
+            .map(Element::from)
+            .ok_or_else(|| DomError::Missing("document element".to_string()))
+    }
+
+    /// The element an event was dispatched to.
+    pub fn event_target(event: &web_sys::Event) -> Result<Element, DomError> {
+        let target = event
+            .target()
+            .ok_or_else(|| DomError::Missing("event target".to_string()))?;
+        Element::try_from(target)
+    }
+
+    fn cast<T: JsCast>(&self, expected: &'static str) -> Result<&T, DomError> {
+        self.el.dyn_ref::<T>().ok_or(DomError::WrongType(expected))
+    }
+
+    pub fn add_event_listener<T>(&self, event_name: &str, handler: T) -> Result<(), DomError>
     where
         T: 'static + FnMut(web_sys::Event),
     {
         let cb = Closure::wrap(Box::new(handler) as Box<dyn FnMut(_)>);
-        if let Some(el) = self.el.take() {
-            let el_et: EventTarget = el.into();
-            el_et
-                .add_event_listener_with_callback(event_name, cb.as_ref().unchecked_ref())
-                .unwrap();
-            cb.forget();
-            if let Ok(el) = el_et.dyn_into::<web_sys::Element>() {
-                self.el = Some(el);
-            }
-        }
+        self.el
+            .add_event_listener_with_callback(event_name, cb.as_ref().unchecked_ref())?;
+        cb.forget();
+        Ok(())
     }
 
     pub fn delegate<T>(
-        &mut self,
+        &self,
         selector: &'static str,
         event: &str,
         mut handler: T,
         use_capture: bool,
-    ) where
+    ) -> Result<(), DomError>
+    where
         T: 'static + FnMut(web_sys::Event) -> (),
     {
-        let el = match self.el.take() {
-            Some(e) => e,
-            None => return,
-        };
-        if let Some(dyn_el) = &el.dyn_ref::<EventTarget>() {
-            if let Some(window) = web_sys::window() {
-                if let Some(document) = window.document() {
-                    // TODO document selector to the target element
-                    let tg_el = document;
-
-                    let cb = Closure::wrap(Box::new(move |event: web_sys::Event| {
-                        if let Some(target_element) = event.target() {
-                            let dyn_target_el: Option<&web_sys::Node> =
-                                wasm_bindgen::JsCast::dyn_ref(&target_element);
-                            if let Some(target_element) = dyn_target_el {
-                                if let Ok(potential_elements) = tg_el.query_selector_all(selector) {
-                                    let mut has_match = false;
-                                    for i in 0..potential_elements.length() {
-                                        if let Some(el) = potential_elements.get(i) {
-                                            if target_element.is_equal_node(Some(&el)) {
-                                                has_match = true;
-                                            }
-                                            break;
-                                        }
-                                    }
-
-                                    if has_match {
-                                        handler(event);
-                                    }
+        // TODO document selector to the target element
+        let tg_el = document()?;
+
+        let cb = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            if let Some(target_element) = event.target() {
+                let dyn_target_el: Option<&web_sys::Node> =
+                    wasm_bindgen::JsCast::dyn_ref(&target_element);
+                if let Some(target_element) = dyn_target_el {
+                    if let Ok(potential_elements) = tg_el.query_selector_all(selector) {
+                        let mut has_match = false;
+                        for i in 0..potential_elements.length() {
+                            if let Some(el) = potential_elements.get(i) {
+                                if target_element.is_equal_node(Some(&el)) {
+                                    has_match = true;
+                                    break;
                                 }
                             }
                         }
-                    }) as Box<dyn FnMut(_)>);
-
-                    dyn_el
-                        .add_event_listener_with_callback_and_bool(
-                            event,
-                            cb.as_ref().unchecked_ref(),
-                            use_capture,
-                        )
-                        .unwrap();
-                    cb.forget(); // TODO cycle collect
+
+                        if has_match {
+                            handler(event);
+                        }
+                    }
                 }
             }
-        }
-        self.el = Some(el);
+        }) as Box<dyn FnMut(_)>);
+
+        self.el.add_event_listener_with_callback_and_bool(
+            event,
+            cb.as_ref().unchecked_ref(),
+            use_capture,
+        )?;
+        cb.forget(); // TODO cycle collect
+        Ok(())
     }
 
-    pub fn qs_from(&mut self, selector: &str) -> Option<Element> {
-        let mut found_el = None;
-        if let Some(el) = self.el.as_ref() {
-            found_el = Some(Element {
-                el: el.query_selector(selector).ok()?,
-            });
-        }
-        found_el
+    pub fn qs_from(&self, selector: &str) -> Result<Element, DomError> {
+        self.try_qs_from(selector)?
+            .ok_or_else(|| DomError::Missing(selector.to_string()))
     }
 
-    pub fn set_inner_html(&mut self, value: String) {
-        if let Some(el) = self.el.take() {
-            el.set_inner_html(&value);
-            self.el = Some(el);
-        }
+    pub fn try_qs_from(&self, selector: &str) -> Result<Option<Element>, DomError> {
+        Ok(self.el.query_selector(selector)?.map(Element::from))
     }
 
-    pub fn set_text_content(&mut self, value: &str) {
-        if let Some(el) = self.el.as_ref() {
-            if let Some(node) = &el.dyn_ref::<web_sys::Node>() {
-                node.set_text_content(Some(&value));
+    pub fn qs_all(&self, selector: &str) -> Result<Vec<Element>, DomError> {
+        let nodes = self.el.query_selector_all(selector)?;
+        let mut elements = Vec::new();
+        for i in 0..nodes.length() {
+            if let Some(node) = nodes.get(i) {
+                let el = node
+                    .dyn_into::<web_sys::Element>()
+                    .map_err(|_| DomError::WrongType("Element"))?;
+                elements.push(el.into());
             }
         }
+        Ok(elements)
     }
 
-    pub fn text_content(&mut self) -> Option<String> {
-        let mut text = None;
-        if let Some(el) = self.el.as_ref() {
-            if let Some(node) = &el.dyn_ref::<web_sys::Node>() {
-                text = node.text_content();
-            }
-        }
-        text
+    /// Whether focus is on this element or one of its descendants.
+    pub fn has_focus_within(&self) -> Result<bool, DomError> {
+        let active = document()?.active_element();
+        Ok(self.el.contains(active.as_ref().map(|el| el.as_ref())))
     }
 
-    pub fn parent_element(&mut self) -> Option<Element> {
-        let mut parent = None;
-        if let Some(el) = self.el.as_ref() {
-            if let Some(node) = &el.dyn_ref::<web_sys::Node>() {
-                if let Some(parent_node) = node.parent_element() {
-                    parent = Some(parent_node.into());
-                }
-            }
-        }
-        parent
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        self.el.get_attribute(name)
     }
 
-    pub fn append_child(&mut self, child: &mut Element) {
-        if let Some(el) = self.el.as_ref() {
-            if let Some(node) = &el.dyn_ref::<web_sys::Node>() {
-                if let Some(ref child_el) = child.el {
-                    if let Some(child_node) = child_el.dyn_ref::<web_sys::Node>() {
-                        node.append_child(child_node).unwrap();
-                    }
-                }
-            }
-        }
+    pub fn set_attribute(&self, name: &str, value: &str) -> Result<(), DomError> {
+        self.el.set_attribute(name, value)?;
+        Ok(())
     }
 
-    pub fn class_list_remove(&mut self, value: &str) {
-        if let Some(el) = self.el.take() {
-            el.class_list().remove_1(&value).unwrap();
-            self.el = Some(el);
-        }
+    pub fn set_inner_html(&self, value: String) {
+        self.el.set_inner_html(&value);
     }
 
-    pub fn class_list_add(&mut self, value: &str) {
-        if let Some(el) = self.el.take() {
-            el.class_list().add_1(&value).unwrap();
-            self.el = Some(el);
-        }
+    pub fn set_text_content(&self, value: &str) {
+        self.el.set_text_content(Some(&value));
     }
 
-    pub fn remove_child(&mut self, mut child: Element) {
-        if let Some(child_el) = child.el.take() {
-            if let Some(el) = self.el.take() {
-                if let Some(el_node) = el.dyn_ref::<web_sys::Node>() {
-                    let child_node: web_sys::Node = child_el.into();
-                    el_node.remove_child(&child_node).unwrap();
-                }
-                self.el = Some(el);
-            }
-        }
+    pub fn text_content(&self) -> Option<String> {
+        self.el.text_content()
     }
 
-    pub fn set_class_name(&mut self, class_name: &str) {
-        if let Some(el) = self.el.take() {
-            el.set_class_name(&class_name);
-            self.el = Some(el);
-        }
+    /// The closest ancestor matching `selector`, starting with this element.
+    pub fn closest(&self, selector: &str) -> Result<Element, DomError> {
+        self.el
+            .closest(selector)?
+            .map(Element::from)
+            .ok_or_else(|| DomError::Missing(selector.to_string()))
     }
 
-    pub fn set_visibility(&mut self, visible: bool) {
-        if let Some(el) = self.el.take() {
-            {
-                let dyn_el: Option<&web_sys::HtmlElement> = wasm_bindgen::JsCast::dyn_ref(&el);
-                if let Some(el) = dyn_el {
-                    el.set_hidden(!visible);
-                }
-            }
-            self.el = Some(el);
-        }
+    pub fn parent_element(&self) -> Result<Element, DomError> {
+        self.el
+            .parent_element()
+            .map(Element::from)
+            .ok_or_else(|| DomError::Missing("parent element".to_string()))
     }
 
-    pub fn blur(&mut self) {
-        if let Some(el) = self.el.take() {
-            {
-                let dyn_el: Option<&web_sys::HtmlElement> = wasm_bindgen::JsCast::dyn_ref(&el);
-                if let Some(el) = dyn_el {
-                    el.blur().unwrap();
-                }
-            }
-            self.el = Some(el);
-        }
+    pub fn append_child(&self, child: &Element) -> Result<(), DomError> {
+        self.el.append_child(&child.el)?;
+        Ok(())
     }
 
-    pub fn focus(&mut self) {
-        if let Some(el) = self.el.take() {
-            {
-                let dyn_el: Option<&web_sys::HtmlElement> = wasm_bindgen::JsCast::dyn_ref(&el);
-                if let Some(el) = dyn_el {
-                    el.focus().unwrap();
-                }
-            }
-            self.el = Some(el);
-        }
+    /// Inserts `child` before `reference`, or at the end without one.
+    pub fn insert_before(&self, child: &Element, reference: Option<&Element>) -> Result<(), DomError> {
+        let reference: Option<&web_sys::Node> = reference.map(|reference| reference.el.as_ref());
+        self.el.insert_before(&child.el, reference)?;
+        Ok(())
     }
 
-    pub fn dataset_set(&mut self, key: &str, value: &str) {
-        if let Some(el) = self.el.take() {
-            {
-                if let Some(el) = wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlElement>(&el) {
-                    el.dataset().set(key, value).unwrap();
-                }
-            }
-            self.el = Some(el);
-        }
+    pub fn class_list_remove(&self, value: &str) -> Result<(), DomError> {
+        self.el.class_list().remove_1(&value)?;
+        Ok(())
     }
 
-    pub fn dataset_get(&mut self, key: &str) -> String {
-        let mut text = String::new();
-        if let Some(el) = self.el.take() {
-            {
-                if let Some(el) = wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlElement>(&el) {
-                    text = el.dataset().get(key);
-                }
-            }
-            self.el = Some(el);
-        }
-        text
+    pub fn class_list_add(&self, value: &str) -> Result<(), DomError> {
+        self.el.class_list().add_1(&value)?;
+        Ok(())
     }
 
-    pub fn set_value(&mut self, value: &str) {
-        if let Some(el) = self.el.take() {
-            if let Some(el) = wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlInputElement>(&el) {
-                el.set_value(&value);
-            }
-            self.el = Some(el);
-        }
+    pub fn has_class(&self, value: &str) -> bool {
+        self.el.class_list().contains(value)
     }
 
-    pub fn value(&mut self) -> String {
-        let mut v = String::new();
-        if let Some(el) = self.el.take() {
-            if let Some(el) = wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlInputElement>(&el) {
-                v = el.value();
-            }
-            self.el = Some(el);
-        }
-        v
+    pub fn remove_child(&self, child: Element) -> Result<(), DomError> {
+        self.el.remove_child(&child.el)?;
+        Ok(())
     }
 
-    pub fn set_checked(&mut self, checked: bool) {
-        if let Some(el) = self.el.take() {
-            if let Some(el) = wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlInputElement>(&el) {
-                el.set_checked(checked);
-            }
-            self.el = Some(el);
+    pub fn set_class_name(&self, class_name: &str) {
+        self.el.set_class_name(&class_name);
+    }
+
+    pub fn set_visibility(&self, visible: bool) -> Result<(), DomError> {
+        self.cast::<web_sys::HtmlElement>("HtmlElement")?
+            .set_hidden(!visible);
+        Ok(())
+    }
+
+    /// Sets an inline style property, including `--custom` properties.
+    pub fn set_style_property(&self, name: &str, value: &str) -> Result<(), DomError> {
+        self.cast::<web_sys::HtmlElement>("HtmlElement")?
+            .style()
+            .set_property(name, value)?;
+        Ok(())
+    }
+
+    pub fn blur(&self) -> Result<(), DomError> {
+        self.cast::<web_sys::HtmlElement>("HtmlElement")?.blur()?;
+        Ok(())
+    }
+
+    pub fn focus(&self) -> Result<(), DomError> {
+        self.cast::<web_sys::HtmlElement>("HtmlElement")?.focus()?;
+        Ok(())
+    }
+
+    pub fn dataset_set(&self, key: &str, value: &str) -> Result<(), DomError> {
+        self.cast::<web_sys::HtmlElement>("HtmlElement")?
+            .dataset()
+            .set(key, value)?;
+        Ok(())
+    }
+
+    pub fn dataset_get(&self, key: &str) -> Result<String, DomError> {
+        let el = self.cast::<web_sys::HtmlElement>("HtmlElement")?;
+        Ok(el.dataset().get(key))
+    }
+
+    pub fn set_value(&self, value: &str) -> Result<(), DomError> {
+        if let Some(select) = self.el.dyn_ref::<web_sys::HtmlSelectElement>() {
+            select.set_value(&value);
+            return Ok(());
         }
+        self.cast::<web_sys::HtmlInputElement>("HtmlInputElement")?
+            .set_value(&value);
+        Ok(())
     }
 
-    pub fn checked(&mut self) -> bool {
-        let mut checked = false;
-        if let Some(el) = self.el.take() {
-            if let Some(el) = wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlInputElement>(&el) {
-                checked = el.checked();
-            }
-            self.el = Some(el);
+    pub fn value(&self) -> Result<String, DomError> {
+        if let Some(select) = self.el.dyn_ref::<web_sys::HtmlSelectElement>() {
+            return Ok(select.value());
         }
-        checked
+        Ok(self
+            .cast::<web_sys::HtmlInputElement>("HtmlInputElement")?
+            .value())
+    }
+
+    pub fn set_checked(&self, checked: bool) -> Result<(), DomError> {
+        self.cast::<web_sys::HtmlInputElement>("HtmlInputElement")?
+            .set_checked(checked);
+        Ok(())
+    }
+
+    pub fn checked(&self) -> Result<bool, DomError> {
+        Ok(self
+            .cast::<web_sys::HtmlInputElement>("HtmlInputElement")?
+            .checked())
     }
 }
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+
+/// Locales with a message catalog, the first one is the fallback.
+const CATALOGS: &[(&str, &str)] = &[
+  ("en", include_str!("../locales/en.txt")),
+  ("de", include_str!("../locales/de.txt")),
+  ("fr", include_str!("../locales/fr.txt")),
+  ("ko", include_str!("../locales/ko.txt")),
+  ("ru", include_str!("../locales/ru.txt")),
+];
+
+/// CLDR plural categories.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PluralCategory {
+  Zero,
+  One,
+  Two,
+  Few,
+  Many,
+  Other,
+}
+
+impl PluralCategory {
+  /// Cardinal plural category of the integer `n` in `lang`, following the
+  /// CLDR rules for the languages we ship and their close relatives.
+  pub fn of(lang: &str, n: usize) -> PluralCategory {
+    use self::PluralCategory::*;
+    let n10 = n % 10;
+    let n100 = n % 100;
+    match primary_subtag(lang) {
+      "ja" | "ko" | "zh" | "th" | "vi" | "id" => Other,
+      "fr" | "pt" => {
+        if n <= 1 {
+          One
+        } else {
+          Other
+        }
+      }
+      "ru" | "uk" | "be" => {
+        if n10 == 1 && n100 != 11 {
+          One
+        } else if (2..=4).contains(&n10) && !(12..=14).contains(&n100) {
+          Few
+        } else {
+          Many
+        }
+      }
+      "pl" => {
+        if n == 1 {
+          One
+        } else if (2..=4).contains(&n10) && !(12..=14).contains(&n100) {
+          Few
+        } else {
+          Many
+        }
+      }
+      "ar" => match n {
+        0 => Zero,
+        1 => One,
+        2 => Two,
+        _ if (3..=10).contains(&n100) => Few,
+        _ if (11..=99).contains(&n100) => Many,
+        _ => Other,
+      },
+      _ => {
+        if n == 1 {
+          One
+        } else {
+          Other
+        }
+      }
+    }
+  }
+
+  fn key(self) -> &'static str {
+    match self {
+      PluralCategory::Zero => "zero",
+      PluralCategory::One => "one",
+      PluralCategory::Two => "two",
+      PluralCategory::Few => "few",
+      PluralCategory::Many => "many",
+      PluralCategory::Other => "other",
+    }
+  }
+}
+
+fn primary_subtag(tag: &str) -> &str {
+  tag.split(|c| c == '-' || c == '_').next().unwrap_or("")
+}
+
+fn parse_catalog(source: &str) -> HashMap<String, String> {
+  let mut messages = HashMap::new();
+  for line in source.lines() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    if let Some(eq) = line.find('=') {
+      let key = line[..eq].trim();
+      let value = line[eq + 1..].trim();
+      messages.insert(key.to_string(), value.to_string());
+    }
+  }
+  messages
+}
+
+fn substitute(message: &str, args: &[(&str, &str)]) -> String {
+  let mut output = message.to_string();
+  for (name, value) in args {
+    output = output.replace(&format!("{{{}}}", name), value);
+  }
+  output
+}
+
+/// Translated messages for one locale, falling back to English.
+pub struct L10n {
+  locale: String,
+  messages: HashMap<String, String>,
+  fallback: HashMap<String, String>,
+}
+
+impl L10n {
+  /// Picks the best supported locale out of `requested`, in order of
+  /// preference. An exact match wins over a primary subtag match.
+  pub fn negotiate(requested: &[String]) -> L10n {
+    let supported = |tag: &str| {
+      CATALOGS
+        .iter()
+        .find(|(locale, _)| locale.eq_ignore_ascii_case(tag))
+        .map(|(locale, _)| *locale)
+    };
+    let locale = requested
+      .iter()
+      .filter_map(|tag| supported(tag).or_else(|| supported(primary_subtag(tag))))
+      .next()
+      .unwrap_or(CATALOGS[0].0);
+    L10n::new(locale)
+  }
+
+  /// Locale negotiated from `navigator.languages`.
+  pub fn from_navigator() -> L10n {
+    let mut requested = Vec::new();
+    if let Some(window) = web_sys::window() {
+      let navigator = window.navigator();
+      let languages = navigator.languages();
+      for i in 0..languages.length() {
+        if let Some(tag) = languages.get(i).as_string() {
+          requested.push(tag);
+        }
+      }
+      if let Some(tag) = navigator.language() {
+        requested.push(tag);
+      }
+    }
+    L10n::negotiate(&requested)
+  }
+
+  pub fn new(locale: &str) -> L10n {
+    let catalog = |locale: &str| {
+      CATALOGS
+        .iter()
+        .find(|(tag, _)| *tag == locale)
+        .map(|(_, source)| parse_catalog(source))
+        .unwrap_or_default()
+    };
+    L10n {
+      locale: locale.to_string(),
+      messages: catalog(locale),
+      fallback: catalog(CATALOGS[0].0),
+    }
+  }
+
+  pub fn locale(&self) -> &str {
+    &self.locale
+  }
+
+  fn lookup(&self, key: &str) -> Option<&String> {
+    self.messages.get(key).or_else(|| self.fallback.get(key))
+  }
+
+  /// The message for `key`, or the key itself when no catalog has it.
+  pub fn get(&self, key: &str) -> String {
+    self.format(key, &[])
+  }
+
+  pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+    match self.lookup(key) {
+      Some(message) => substitute(message, args),
+      None => key.to_string(),
+    }
+  }
+
+  /// The plural form of `key` for `count`, which is also available to the
+  /// message as `{count}`.
+  pub fn plural(&self, key: &str, count: usize, args: &[(&str, &str)]) -> String {
+    let category = PluralCategory::of(&self.locale, count);
+    let exact = format!("{}[{}]", key, category.key());
+    let other = format!("{}[other]", key);
+    let message = self
+      .messages
+      .get(&exact)
+      .or_else(|| self.messages.get(&other))
+      .or_else(|| {
+        self.fallback.get(&format!(
+          "{}[{}]",
+          key,
+          PluralCategory::of("en", count).key()
+        ))
+      })
+      .or_else(|| self.fallback.get(&other));
+
+    let count = count.to_string();
+    let mut all_args = vec![("count", count.as_str())];
+    all_args.extend_from_slice(args);
+    match message {
+      Some(message) => substitute(message, &all_args),
+      None => key.to_string(),
+    }
+  }
+}
@@ -1,14 +1,28 @@
 use std::rc::Rc;
 use wasm_bindgen::prelude::*;
 
+pub mod changes;
 pub mod controller;
 pub mod element;
+pub mod l10n;
+pub mod lists;
+pub mod platform;
+pub mod preferences;
+pub mod recurrence;
+pub mod reminders;
 pub mod scheduler;
+pub mod search;
+pub mod selection;
+pub mod ssr;
 pub mod store;
 pub mod template;
+pub mod updates;
 pub mod view;
 
 use crate::controller::{Controller, ControllerMessage};
+use crate::element::Element;
+use crate::l10n::L10n;
+use crate::platform::DateClock;
 use crate::scheduler::Scheduler;
 use crate::store::Store;
 use crate::view::{View, ViewMessage};
@@ -24,29 +38,37 @@ pub fn exit(message: &str) {
   std::process::abort();
 }
 
-fn app(name: &str) {
+/// Starts the app on the current page. Its lists are persisted under keys
+/// starting with `name`, see `Store`.
+pub fn app(name: &str) -> Result<(), JsValue> {
+  // The module can be loaded on a page without the app, e.g. by the tests.
+  if Element::try_qs(".todoapp")?.is_none() {
+    return Ok(());
+  }
   let sched = Rc::new(Scheduler::new());
   let store = match Store::new(name) {
     Some(s) => s,
-    None => return,
+    None => return Ok(()),
   };
 
-  let controller = Controller::new(store, Rc::downgrade(&sched));
-  if let Some(mut view) = View::new(sched.clone()) {
-    let sch: &Rc<Scheduler> = &sched;
-    view.init();
-    sch.set_view(view);
-    sch.set_controller(controller);
-    sched.add_message(Message::Controller(ControllerMessage::SetPage(
-      "".to_string(),
-    )))
-  }
+  let preferences = store.preferences().clone();
+  let controller = Controller::new(store, Box::new(DateClock), Rc::downgrade(&sched));
+  let mut view = View::new(sched.clone(), L10n::from_navigator())?;
+  let sch: &Rc<Scheduler> = &sched;
+  view.init()?;
+  sch.set_view(view);
+  sch.set_controller(controller);
+  sched.add_message(Message::View(ViewMessage::ApplyPreferences(preferences)));
+  let hash = web_sys::window()
+    .and_then(|window| window.location().hash().ok())
+    .unwrap_or_default();
+  sched.add_message(Message::Controller(ControllerMessage::SetPage(hash)));
+  updates::register()?;
+  Ok(())
 }
 
 #[wasm_bindgen(start)]
 pub fn run() -> Result<(), JsValue> {
   console_error_panic_hook::set_once();
-  app("todos-wasmbindgen");
-
-  Ok(())
+  app("todos-wasmbindgen")
 }
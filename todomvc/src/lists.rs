@@ -0,0 +1,91 @@
+/// The list items live in until the user creates others. Its items keep the
+/// storage key used before the app had several lists.
+pub const DEFAULT_LIST: &str = "todos";
+
+/// A named todo list as shown by the list switcher.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ListInfo {
+  pub name: String,
+  pub archived: bool,
+}
+
+/// A parsed `location.hash`: either a bare filter such as `#/active`, which
+/// stays on the current list, or `#/list/:name/:filter`.
+#[derive(Debug, PartialEq)]
+pub struct Route {
+  pub list: Option<String>,
+  /// `""`, `"active"` or `"completed"`.
+  pub filter: String,
+}
+
+impl Route {
+  pub fn parse(hash: &str) -> Route {
+    let path = hash.trim_start_matches("#/");
+    if !path.starts_with("list/") {
+      return Route {
+        list: None,
+        filter: path.to_string(),
+      };
+    }
+    let mut parts = path["list/".len()..].splitn(2, '/');
+    let list = decode(parts.next().unwrap_or(""));
+    Route {
+      list: if list.is_empty() { None } else { Some(list) },
+      filter: parts.next().unwrap_or("").trim_end_matches('/').to_string(),
+    }
+  }
+}
+
+/// The hash showing `filter` on `list`.
+pub fn route(list: &str, filter: &str) -> String {
+  format!("#/list/{}/{}", encode(list), filter)
+}
+
+/// A list name as entered by the user, `None` when it is blank.
+pub fn list_name(name: &str) -> Option<String> {
+  let name = name.trim();
+  if name.is_empty() {
+    None
+  } else {
+    Some(name.to_string())
+  }
+}
+
+/// Percent-encodes everything but unreserved URL characters.
+fn encode(segment: &str) -> String {
+  let mut encoded = String::new();
+  for byte in segment.bytes() {
+    match byte {
+      b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+        encoded.push(byte as char)
+      }
+      _ => encoded.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  encoded
+}
+
+/// Browsers differ in whether `location.hash` is percent-encoded, so both
+/// forms are accepted.
+fn decode(segment: &str) -> String {
+  let bytes = segment.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let escaped = bytes.get(i + 1..i + 3).filter(|hex| {
+      bytes[i] == b'%' && hex.iter().all(|digit| digit.is_ascii_hexdigit())
+    });
+    match escaped {
+      Some(hex) => {
+        let hex = std::str::from_utf8(hex).unwrap_or_default();
+        decoded.push(u8::from_str_radix(hex, 16).unwrap_or_default());
+        i += 3;
+      }
+      None => {
+        decoded.push(bytes[i]);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,55 @@
+/// String key/value persistence, replaceable natively e.g. in tests.
+pub trait Storage {
+  fn get_item(&self, key: &str) -> Option<String>;
+  fn set_item(&self, key: &str, value: &str);
+  fn remove_item(&self, key: &str);
+}
+
+/// Source of the current time in milliseconds since the epoch.
+pub trait Clock {
+  fn now(&self) -> f64;
+
+  /// Minutes from local time to UTC, like `Date.prototype.getTimezoneOffset`.
+  fn timezone_offset(&self) -> f64 {
+    0.0
+  }
+}
+
+/// `window.localStorage`
+pub struct LocalStorage {
+  storage: web_sys::Storage,
+}
+
+impl LocalStorage {
+  pub fn new() -> Option<LocalStorage> {
+    let storage = web_sys::window()?.local_storage().ok()??;
+    Some(LocalStorage { storage })
+  }
+}
+
+impl Storage for LocalStorage {
+  fn get_item(&self, key: &str) -> Option<String> {
+    self.storage.get_item(key).ok()?
+  }
+
+  fn set_item(&self, key: &str, value: &str) {
+    self.storage.set_item(key, value).unwrap();
+  }
+
+  fn remove_item(&self, key: &str) {
+    self.storage.remove_item(key).unwrap();
+  }
+}
+
+/// `Date.now()`
+pub struct DateClock;
+
+impl Clock for DateClock {
+  fn now(&self) -> f64 {
+    js_sys::Date::now()
+  }
+
+  fn timezone_offset(&self) -> f64 {
+    js_sys::Date::new_0().get_timezone_offset()
+  }
+}
@@ -0,0 +1,115 @@
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Theme {
+  /// Follows `prefers-color-scheme`.
+  System,
+  Light,
+  Dark,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Density {
+  Comfortable,
+  Compact,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preferences {
+  pub theme: Theme,
+  pub density: Density,
+  /// Route shown when the page is opened without a hash, e.g. `"active"`.
+  pub default_filter: String,
+  pub confirm_delete: bool,
+}
+
+impl Default for Preferences {
+  fn default() -> Preferences {
+    Preferences {
+      theme: Theme::System,
+      density: Density::Comfortable,
+      default_filter: "".into(),
+      confirm_delete: false,
+    }
+  }
+}
+
+impl Theme {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Theme::System => "system",
+      Theme::Light => "light",
+      Theme::Dark => "dark",
+    }
+  }
+
+  pub fn parse(value: &str) -> Theme {
+    match value {
+      "light" => Theme::Light,
+      "dark" => Theme::Dark,
+      _ => Theme::System,
+    }
+  }
+
+  pub fn is_dark(self, system_dark: bool) -> bool {
+    match self {
+      Theme::System => system_dark,
+      Theme::Light => false,
+      Theme::Dark => true,
+    }
+  }
+
+  /// CSS custom properties for the light or dark palette, see `index.css`.
+  pub fn palette(dark: bool) -> &'static [(&'static str, &'static str)] {
+    if dark {
+      &[
+        ("--page-background", "#1e1e1e"),
+        ("--surface", "#2b2b2b"),
+        ("--text", "#e0e0e0"),
+        ("--muted", "#a0a0a0"),
+        ("--border", "#3c3c3c"),
+        ("--completed", "#6a6a6a"),
+        ("--heading", "rgba(230, 120, 120, 0.35)"),
+        ("--stack", "#262626"),
+        ("--info", "#7a7a7a"),
+        ("--highlight", "rgba(255, 214, 0, 0.3)"),
+        ("--selected", "rgba(120, 170, 230, 0.2)"),
+      ]
+    } else {
+      &[
+        ("--page-background", "#f5f5f5"),
+        ("--surface", "#fff"),
+        ("--text", "#4d4d4d"),
+        ("--muted", "#777"),
+        ("--border", "#e6e6e6"),
+        ("--completed", "#d9d9d9"),
+        ("--heading", "rgba(175, 47, 47, 0.15)"),
+        ("--stack", "#f6f6f6"),
+        ("--info", "#bfbfbf"),
+        ("--highlight", "rgba(255, 214, 0, 0.45)"),
+        ("--selected", "rgba(47, 120, 175, 0.1)"),
+      ]
+    }
+  }
+}
+
+impl Density {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Density::Comfortable => "comfortable",
+      Density::Compact => "compact",
+    }
+  }
+
+  pub fn parse(value: &str) -> Density {
+    match value {
+      "compact" => Density::Compact,
+      _ => Density::Comfortable,
+    }
+  }
+
+  pub fn properties(self) -> &'static [(&'static str, &'static str)] {
+    match self {
+      Density::Comfortable => &[("--row-padding", "15px"), ("--input-padding", "16px")],
+      Density::Compact => &[("--row-padding", "6px"), ("--input-padding", "8px")],
+    }
+  }
+}
@@ -0,0 +1,550 @@
+//! Due dates and recurrence rules for repeating todos.
+//!
+//! Rules are a subset of the iCalendar RRULE (RFC 5545): `FREQ` (`DAILY`,
+//! `WEEKLY`, `MONTHLY` or `YEARLY`), `INTERVAL`, `BYDAY`, `BYMONTHDAY`,
+//! `COUNT` and `UNTIL`. Weeks start on Monday. Dates are wall-clock times
+//! without a time zone, the way they were entered; nothing in here depends on
+//! the browser.
+
+use std::fmt;
+
+/// Bounds the search for the next occurrence of rules that rarely match,
+/// such as the 31st of every other month.
+const MAX_PERIODS: i64 = 1000;
+
+/// Due dates entered without a time remind in the morning.
+const DEFAULT_HOUR: u32 = 9;
+
+fn floor_div(a: i64, b: i64) -> i64 {
+  let q = a / b;
+  if (a % b != 0) && ((a < 0) != (b < 0)) {
+    q - 1
+  } else {
+    q
+  }
+}
+
+fn is_leap(year: i32) -> bool {
+  (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+  match month {
+    2 if is_leap(year) => 29,
+    2 => 28,
+    4 | 6 | 9 | 11 => 30,
+    _ => 31,
+  }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+  let year = i64::from(year) - if month <= 2 { 1 } else { 0 };
+  let era = floor_div(year, 400);
+  let year_of_era = year - era * 400;
+  let month = i64::from(month);
+  let month_of_year = if month > 2 { month - 3 } else { month + 9 };
+  let day_of_year = (153 * month_of_year + 2) / 5 + i64::from(day) - 1;
+  let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+  era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+  let days = days + 719_468;
+  let era = floor_div(days, 146_097);
+  let day_of_era = days - era * 146_097;
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let mp = (5 * day_of_year + 2) / 153;
+  let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+  (year as i32, month, day)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Weekday {
+  Monday,
+  Tuesday,
+  Wednesday,
+  Thursday,
+  Friday,
+  Saturday,
+  Sunday,
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+  Weekday::Monday,
+  Weekday::Tuesday,
+  Weekday::Wednesday,
+  Weekday::Thursday,
+  Weekday::Friday,
+  Weekday::Saturday,
+  Weekday::Sunday,
+];
+
+impl Weekday {
+  /// Days since Monday.
+  fn index(self) -> i64 {
+    WEEKDAYS.iter().position(|day| *day == self).unwrap_or(0) as i64
+  }
+
+  /// The two letter code used by `BYDAY`, e.g. `MO`.
+  pub fn code(self) -> &'static str {
+    match self {
+      Weekday::Monday => "MO",
+      Weekday::Tuesday => "TU",
+      Weekday::Wednesday => "WE",
+      Weekday::Thursday => "TH",
+      Weekday::Friday => "FR",
+      Weekday::Saturday => "SA",
+      Weekday::Sunday => "SU",
+    }
+  }
+
+  fn parse(code: &str) -> Option<Weekday> {
+    WEEKDAYS
+      .iter()
+      .cloned()
+      .find(|day| day.code().eq_ignore_ascii_case(code))
+  }
+
+  fn of_days(days: i64) -> Weekday {
+    // 1970-01-01 was a Thursday.
+    WEEKDAYS[(((days % 7) + 7 + 3) % 7) as usize]
+  }
+}
+
+/// A wall-clock date and time, ordered chronologically.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+  pub year: i32,
+  pub month: u32,
+  pub day: u32,
+  pub hour: u32,
+  pub minute: u32,
+}
+
+impl DateTime {
+  pub fn new(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> Option<DateTime> {
+    let valid_date = (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+    if !valid_date || hour > 23 || minute > 59 {
+      return None;
+    }
+    Some(DateTime {
+      year,
+      month,
+      day,
+      hour,
+      minute,
+    })
+  }
+
+  /// Parses `2026-10-19`, `2026-10-19 08:30` or `2026-10-19T08:30`.
+  pub fn parse(value: &str) -> Option<DateTime> {
+    let value = value.trim();
+    let (date, time) = match value.find(&['T', ' '][..]) {
+      Some(split) => (&value[..split], Some(value[split + 1..].trim())),
+      None => (value, None),
+    };
+    let mut date_parts = date.splitn(3, '-');
+    let year = date_parts.next()?.parse().ok()?;
+    let month = date_parts.next()?.parse().ok()?;
+    let day = date_parts.next()?.parse().ok()?;
+    let (hour, minute) = match time {
+      Some(time) => {
+        let mut time_parts = time.splitn(2, ':');
+        (
+          time_parts.next()?.parse().ok()?,
+          time_parts.next()?.parse().ok()?,
+        )
+      }
+      None => (DEFAULT_HOUR, 0),
+    };
+    DateTime::new(year, month, day, hour, minute)
+  }
+
+  /// The date and time of `millis` milliseconds after 1970-01-01 00:00 on the
+  /// same wall clock.
+  pub fn from_millis(millis: f64) -> DateTime {
+    let minutes = (millis / 60_000.0).floor() as i64;
+    let days = floor_div(minutes, 24 * 60);
+    let (year, month, day) = civil_from_days(days);
+    let minute_of_day = (minutes - days * 24 * 60) as u32;
+    DateTime {
+      year,
+      month,
+      day,
+      hour: minute_of_day / 60,
+      minute: minute_of_day % 60,
+    }
+  }
+
+  fn days(&self) -> i64 {
+    days_from_civil(self.year, self.month, self.day)
+  }
+
+  pub fn weekday(&self) -> Weekday {
+    Weekday::of_days(self.days())
+  }
+
+  /// The same time of day on another date, `None` if there is no such date.
+  fn on(&self, year: i32, month: u32, day: u32) -> Option<DateTime> {
+    DateTime::new(year, month, day, self.hour, self.minute)
+  }
+
+  fn on_days(&self, days: i64) -> DateTime {
+    let (year, month, day) = civil_from_days(days);
+    DateTime {
+      year,
+      month,
+      day,
+      ..*self
+    }
+  }
+
+  /// `2026-10-19 08:30`, for display.
+  pub fn human(&self) -> String {
+    format!(
+      "{:04}-{:02}-{:02} {:02}:{:02}",
+      self.year, self.month, self.day, self.hour, self.minute
+    )
+  }
+}
+
+/// `2026-10-19T08:30`, as stored.
+impl fmt::Display for DateTime {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(
+      f,
+      "{:04}-{:02}-{:02}T{:02}:{:02}",
+      self.year, self.month, self.day, self.hour, self.minute
+    )
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Frequency {
+  Daily,
+  Weekly,
+  Monthly,
+  Yearly,
+}
+
+impl Frequency {
+  fn as_str(self) -> &'static str {
+    match self {
+      Frequency::Daily => "DAILY",
+      Frequency::Weekly => "WEEKLY",
+      Frequency::Monthly => "MONTHLY",
+      Frequency::Yearly => "YEARLY",
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+  pub frequency: Frequency,
+  pub interval: u32,
+  /// Weekdays, in monthly rules optionally with an ordinal such as `-1` for
+  /// the last one of the month.
+  pub by_day: Vec<(Option<i32>, Weekday)>,
+  /// Days of the month, negative ones count from the end.
+  pub by_month_day: Vec<i32>,
+  /// Occurrences left, including the current one.
+  pub count: Option<u32>,
+  pub until: Option<DateTime>,
+}
+
+impl Rule {
+  fn every(frequency: Frequency) -> Rule {
+    Rule {
+      frequency,
+      interval: 1,
+      by_day: Vec::new(),
+      by_month_day: Vec::new(),
+      count: None,
+      until: None,
+    }
+  }
+
+  /// A rule from what the user typed: one of the presets `daily`,
+  /// `weekdays`, `weekly`, `monthly` and `yearly`, or an RRULE.
+  pub fn from_input(input: &str) -> Option<Rule> {
+    let input = input.trim();
+    let rule = match input.to_lowercase().as_str() {
+      "daily" => Rule::every(Frequency::Daily),
+      "weekdays" => Rule {
+        by_day: WEEKDAYS[..5].iter().map(|day| (None, *day)).collect(),
+        ..Rule::every(Frequency::Weekly)
+      },
+      "weekly" => Rule::every(Frequency::Weekly),
+      "monthly" => Rule::every(Frequency::Monthly),
+      "yearly" => Rule::every(Frequency::Yearly),
+      _ => return Rule::parse(input),
+    };
+    Some(rule)
+  }
+
+  /// Parses an RRULE such as `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR`, with or
+  /// without the `RRULE:` prefix. Parts outside the supported subset make the
+  /// whole rule invalid rather than being ignored.
+  pub fn parse(value: &str) -> Option<Rule> {
+    let value = value.trim();
+    let value = match value.get(..6) {
+      Some(prefix) if prefix.eq_ignore_ascii_case("RRULE:") => &value[6..],
+      _ => value,
+    };
+    let mut frequency = None;
+    let mut rule = Rule::every(Frequency::Daily);
+    for part in value.split(';').filter(|part| !part.is_empty()) {
+      let mut key_value = part.splitn(2, '=');
+      let key = key_value.next()?.trim().to_uppercase();
+      let value = key_value.next()?.trim();
+      match key.as_str() {
+        "FREQ" => {
+          frequency = Some(match value.to_uppercase().as_str() {
+            "DAILY" => Frequency::Daily,
+            "WEEKLY" => Frequency::Weekly,
+            "MONTHLY" => Frequency::Monthly,
+            "YEARLY" => Frequency::Yearly,
+            _ => return None,
+          })
+        }
+        "INTERVAL" => rule.interval = value.parse().ok().filter(|interval| *interval > 0)?,
+        "COUNT" => rule.count = Some(value.parse().ok().filter(|count| *count > 0)?),
+        "UNTIL" => rule.until = Some(parse_until(value)?),
+        "BYDAY" => {
+          for day in value.split(',') {
+            rule.by_day.push(parse_by_day(day.trim())?);
+          }
+        }
+        "BYMONTHDAY" => {
+          for day in value.split(',') {
+            let day: i32 = day.trim().parse().ok()?;
+            if day == 0 || day.abs() > 31 {
+              return None;
+            }
+            rule.by_month_day.push(day);
+          }
+        }
+        _ => return None,
+      }
+    }
+    rule.frequency = frequency?;
+
+    let ordinals = rule.by_day.iter().any(|(ordinal, _)| ordinal.is_some());
+    let monthly = rule.frequency == Frequency::Monthly;
+    if (ordinals || !rule.by_month_day.is_empty()) && !monthly {
+      return None;
+    }
+    if rule.frequency == Frequency::Yearly && !rule.by_day.is_empty() {
+      return None;
+    }
+    Some(rule)
+  }
+
+  /// The next occurrence after `after` of the series `current` is part of,
+  /// and the rule for the occurrence after that. `None` once the series ends.
+  ///
+  /// Occurrences that `after` already passed are skipped, so a daily task
+  /// completed three days late comes back tomorrow rather than three times.
+  pub fn next_occurrence(&self, current: DateTime, after: DateTime) -> Option<(DateTime, Rule)> {
+    if self.count.map_or(false, |count| count <= 1) {
+      return None;
+    }
+    let after = if after > current { after } else { current };
+    let interval = i64::from(self.interval.max(1));
+    let start = self.period(&current);
+    let mut period = start + (self.period(&after) - start) / interval * interval;
+
+    for _ in 0..MAX_PERIODS {
+      let next = self
+        .occurrences(&current, period)
+        .into_iter()
+        .filter(|occurrence| *occurrence > after)
+        .min();
+      if let Some(next) = next {
+        if self.until.map_or(false, |until| next > until) {
+          return None;
+        }
+        let rule = Rule {
+          count: self.count.map(|count| count - 1),
+          ..self.clone()
+        };
+        return Some((next, rule));
+      }
+      period += interval;
+    }
+    None
+  }
+
+  /// Number of the day, week, month or year `date` falls in.
+  fn period(&self, date: &DateTime) -> i64 {
+    match self.frequency {
+      Frequency::Daily => date.days(),
+      // 1970-01-05 was the first Monday.
+      Frequency::Weekly => floor_div(date.days() - date.weekday().index() - 4, 7),
+      Frequency::Monthly => i64::from(date.year) * 12 + i64::from(date.month) - 1,
+      Frequency::Yearly => i64::from(date.year),
+    }
+  }
+
+  /// Occurrences within `period`, at the time of day of `current`.
+  fn occurrences(&self, current: &DateTime, period: i64) -> Vec<DateTime> {
+    match self.frequency {
+      Frequency::Daily => {
+        let date = current.on_days(period);
+        let weekday = date.weekday();
+        if self.by_day.is_empty() || self.by_day.iter().any(|(_, day)| *day == weekday) {
+          vec![date]
+        } else {
+          Vec::new()
+        }
+      }
+      Frequency::Weekly => {
+        let monday = period * 7 + 4;
+        if self.by_day.is_empty() {
+          vec![current.on_days(monday + current.weekday().index())]
+        } else {
+          self
+            .by_day
+            .iter()
+            .map(|(_, day)| current.on_days(monday + day.index()))
+            .collect()
+        }
+      }
+      Frequency::Monthly => {
+        let year = floor_div(period, 12) as i32;
+        let month = (period - i64::from(year) * 12) as u32 + 1;
+        self
+          .month_days(current, year, month)
+          .into_iter()
+          .filter_map(|day| current.on(year, month, day))
+          .collect()
+      }
+      Frequency::Yearly => current
+        .on(period as i32, current.month, current.day)
+        .into_iter()
+        .collect(),
+    }
+  }
+
+  /// Days of `month` matched by `BYMONTHDAY` and `BYDAY`, both have to match
+  /// when the rule has both. Without either it is the day of `current`.
+  fn month_days(&self, current: &DateTime, year: i32, month: u32) -> Vec<u32> {
+    let length = days_in_month(year, month);
+    let by_month_day: Vec<u32> = self
+      .by_month_day
+      .iter()
+      .filter_map(|day| {
+        let day = if *day > 0 { *day } else { length as i32 + day + 1 };
+        if day >= 1 && day <= length as i32 {
+          Some(day as u32)
+        } else {
+          None
+        }
+      })
+      .collect();
+
+    let first = days_from_civil(year, month, 1);
+    let mut by_day = Vec::new();
+    for (ordinal, weekday) in self.by_day.iter() {
+      let matching: Vec<u32> = (1..=length)
+        .filter(|day| Weekday::of_days(first + i64::from(*day) - 1) == *weekday)
+        .collect();
+      match *ordinal {
+        Some(n) if n > 0 => by_day.extend(matching.get(n as usize - 1)),
+        Some(n) if n < 0 => {
+          let from_end = (-n) as usize;
+          if from_end <= matching.len() {
+            by_day.push(matching[matching.len() - from_end]);
+          }
+        }
+        _ => by_day.extend(matching),
+      }
+    }
+
+    match (self.by_month_day.is_empty(), self.by_day.is_empty()) {
+      (true, true) => vec![current.day],
+      (false, true) => by_month_day,
+      (true, false) => by_day,
+      (false, false) => by_month_day
+        .into_iter()
+        .filter(|day| by_day.contains(day))
+        .collect(),
+    }
+  }
+}
+
+fn parse_by_day(value: &str) -> Option<(Option<i32>, Weekday)> {
+  let split = value.len().checked_sub(2)?;
+  let (ordinal, code) = (value.get(..split)?, value.get(split..)?);
+  let weekday = Weekday::parse(code)?;
+  if ordinal.is_empty() {
+    return Some((None, weekday));
+  }
+  let ordinal: i32 = ordinal.trim_start_matches('+').parse().ok()?;
+  if ordinal == 0 || ordinal.abs() > 5 {
+    return None;
+  }
+  Some((Some(ordinal), weekday))
+}
+
+/// `UNTIL` is `YYYYMMDD` or `YYYYMMDDTHHMMSS`, optionally with a `Z` that is
+/// ignored like all time zones here. A date alone includes the whole day.
+fn parse_until(value: &str) -> Option<DateTime> {
+  let value = value.trim_end_matches(&['Z', 'z'][..]);
+  if !value.is_char_boundary(8) || !value[..8].bytes().all(|byte| byte.is_ascii_digit()) {
+    return None;
+  }
+  let year = value[..4].parse().ok()?;
+  let month = value[4..6].parse().ok()?;
+  let day = value[6..8].parse().ok()?;
+  let (hour, minute) = match &value[8..] {
+    "" => (23, 59),
+    time if time.starts_with(&['T', 't'][..]) => {
+      (time.get(1..3)?.parse().ok()?, time.get(3..5)?.parse().ok()?)
+    }
+    _ => return None,
+  };
+  DateTime::new(year, month, day, hour, minute)
+}
+
+/// The rule as an RRULE, e.g. `FREQ=MONTHLY;BYDAY=-1FR;COUNT=3`.
+impl fmt::Display for Rule {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "FREQ={}", self.frequency.as_str())?;
+    if self.interval != 1 {
+      write!(f, ";INTERVAL={}", self.interval)?;
+    }
+    if !self.by_day.is_empty() {
+      let days: Vec<String> = self
+        .by_day
+        .iter()
+        .map(|(ordinal, day)| match ordinal {
+          Some(ordinal) => format!("{}{}", ordinal, day.code()),
+          None => day.code().to_string(),
+        })
+        .collect();
+      write!(f, ";BYDAY={}", days.join(","))?;
+    }
+    if !self.by_month_day.is_empty() {
+      let days: Vec<String> = self.by_month_day.iter().map(|day| day.to_string()).collect();
+      write!(f, ";BYMONTHDAY={}", days.join(","))?;
+    }
+    if let Some(count) = self.count {
+      write!(f, ";COUNT={}", count)?;
+    }
+    if let Some(until) = self.until {
+      write!(
+        f,
+        ";UNTIL={:04}{:02}{:02}T{:02}{:02}00",
+        until.year, until.month, until.day, until.hour, until.minute
+      )?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,214 @@
+use crate::element::DomError;
+use crate::l10n::L10n;
+use crate::lists;
+use crate::recurrence::DateTime;
+use crate::view::report;
+
+use js_sys::Reflect;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::rc::{Rc, Weak};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{Notification, NotificationOptions, NotificationPermission, ServiceWorkerRegistration};
+
+/// `setTimeout` takes a signed 32 bit delay, longer waits take several.
+const MAX_DELAY: f64 = 2_147_483_647.0;
+
+/// An open item with a due date.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reminder {
+  pub id: String,
+  pub title: String,
+  /// The list the item is on.
+  pub list: String,
+  pub due: DateTime,
+}
+
+/// Shows a notification when an item falls due. There is one timer at a
+/// time, for the earliest pending reminder, and `schedule` replaces them all:
+/// the controller sends every reminder when the page loads and again
+/// whenever they change. Reminders that fell due while the page was closed
+/// are dropped.
+///
+/// Notifications are shown by the service worker once it is ready, as some
+/// browsers, e.g. Chrome on Android, only let workers show them.
+pub struct Reminders {
+  state: Rc<RefCell<State>>,
+}
+
+struct State {
+  /// Reminders with their time in milliseconds since the epoch, earliest
+  /// first.
+  pending: Vec<(f64, Reminder)>,
+  timer: Option<i32>,
+  /// Calls `fire`, for every timer: one closure for the life of the page
+  /// rather than one per timer, which a cleared timer would leak.
+  tick: Option<Closure<dyn FnMut()>>,
+  registration: Option<ServiceWorkerRegistration>,
+  l10n: Rc<L10n>,
+}
+
+impl Reminders {
+  pub fn new(l10n: Rc<L10n>) -> Reminders {
+    let state = Rc::new(RefCell::new(State {
+      pending: Vec::new(),
+      timer: None,
+      tick: None,
+      registration: None,
+      l10n,
+    }));
+    let weak = Rc::downgrade(&state);
+    state.borrow_mut().tick = Some(Closure::wrap(Box::new(move || {
+      if let Some(state) = weak.upgrade() {
+        report(fire(&state));
+      }
+    }) as Box<dyn FnMut()>));
+    report(wait_for_registration(Rc::downgrade(&state)));
+    Reminders { state }
+  }
+
+  pub fn schedule(&self, reminders: Vec<Reminder>) -> Result<(), DomError> {
+    let now = js_sys::Date::now();
+    let mut pending: Vec<(f64, Reminder)> = reminders
+      .into_iter()
+      .map(|reminder| (local_millis(&reminder.due), reminder))
+      .filter(|(at, _)| *at > now)
+      .collect();
+    pending.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    self.state.borrow_mut().pending = pending;
+    arm(&self.state)
+  }
+
+  /// Asks whether notifications may be shown, unless the user already
+  /// decided. Browsers only ask in response to user input.
+  pub fn request_permission() -> Result<(), DomError> {
+    if Notification::permission() == NotificationPermission::Default {
+      // The answer is checked again before each notification.
+      let _ = Notification::request_permission()?;
+    }
+    Ok(())
+  }
+}
+
+impl Drop for Reminders {
+  fn drop(&mut self) {
+    // Its callback goes with the state.
+    if let (Some(window), Some(timer)) = (web_sys::window(), self.state.borrow().timer) {
+      window.clear_timeout_with_handle(timer);
+    }
+  }
+}
+
+/// Replaces the timer with one for the earliest pending reminder.
+fn arm(state: &Rc<RefCell<State>>) -> Result<(), DomError> {
+  let window = web_sys::window().ok_or_else(|| DomError::Missing("window".to_string()))?;
+  let mut current = state.borrow_mut();
+  if let Some(timer) = current.timer.take() {
+    window.clear_timeout_with_handle(timer);
+  }
+  let at = match current.pending.first() {
+    Some((at, _)) => *at,
+    None => return Ok(()),
+  };
+  let now = js_sys::Date::now();
+  let delay = if at > now { (at - now).min(MAX_DELAY) } else { 0.0 };
+  let timer = match current.tick {
+    Some(ref tick) => window.set_timeout_with_callback_and_timeout_and_arguments_0(
+      tick.as_ref().unchecked_ref(),
+      delay as i32,
+    )?,
+    None => return Ok(()),
+  };
+  current.timer = Some(timer);
+  Ok(())
+}
+
+/// Keeps the registration of the service worker once it is active, see
+/// `updates::register`. Never happens where there is none, e.g. in jsdom.
+fn wait_for_registration(state: Weak<RefCell<State>>) -> Result<(), DomError> {
+  let window = web_sys::window().ok_or_else(|| DomError::Missing("window".to_string()))?;
+  let navigator = window.navigator();
+  if !Reflect::has(&navigator, &JsValue::from_str("serviceWorker"))? {
+    return Ok(());
+  }
+  let ready = Closure::wrap(Box::new(move |registration: JsValue| {
+    if let Some(state) = state.upgrade() {
+      state.borrow_mut().registration = Some(registration.unchecked_into());
+    }
+  }) as Box<dyn FnMut(JsValue)>);
+  // `ready` never rejects, and resolves once.
+  let _ = navigator.service_worker().ready()?.then(&ready);
+  ready.forget();
+  Ok(())
+}
+
+/// Notifies of every reminder that is due and waits for the next one. A
+/// notification failing, e.g. because the browser only lets service workers
+/// show them, is reported without holding up the others.
+fn fire(state: &Rc<RefCell<State>>) -> Result<(), DomError> {
+  let now = js_sys::Date::now();
+  let (due, l10n, registration) = {
+    let mut current = state.borrow_mut();
+    current.timer = None;
+    let split = current
+      .pending
+      .iter()
+      .position(|(at, _)| *at > now)
+      .unwrap_or_else(|| current.pending.len());
+    let due: Vec<Reminder> = current.pending.drain(..split).map(|(_, reminder)| reminder).collect();
+    (due, current.l10n.clone(), current.registration.clone())
+  };
+  for reminder in due.iter() {
+    report(notify(&l10n, registration.as_ref(), reminder));
+  }
+  arm(state)
+}
+
+/// Shows `reminder` through `registration` if there is one, clicking it
+/// opens its list.
+fn notify(
+  l10n: &L10n,
+  registration: Option<&ServiceWorkerRegistration>,
+  reminder: &Reminder,
+) -> Result<(), DomError> {
+  if Notification::permission() != NotificationPermission::Granted {
+    return Ok(());
+  }
+  let mut options = NotificationOptions::new();
+  options.body(&l10n.format(
+    "reminder-body",
+    &[("due", &reminder.due.human()), ("list", &reminder.list)],
+  ));
+  options.tag(&reminder.id);
+  let hash = lists::route(&reminder.list, "");
+  if let Some(registration) = registration {
+    // The worker opens the list when it is clicked, see `open_list` in sw/.
+    options.data(&JsValue::from_str(&hash));
+    let _ = registration.show_notification_with_options(&reminder.title, &options)?;
+    return Ok(());
+  }
+  let notification = Notification::new_with_options(&reminder.title, &options)?;
+
+  let onclick = Closure::once_into_js(move || {
+    if let Some(window) = web_sys::window() {
+      let _ = window.focus();
+      let _ = window.location().set_hash(&hash);
+    }
+  });
+  notification.set_onclick(Some(onclick.unchecked_ref()));
+  Ok(())
+}
+
+/// Milliseconds since the epoch of a wall-clock time in the browser's time
+/// zone.
+fn local_millis(due: &DateTime) -> f64 {
+  js_sys::Date::new_with_year_month_day_hr_min(
+    due.year as u32,
+    due.month as i32 - 1,
+    due.day as i32,
+    due.hour as i32,
+    due.minute as i32,
+  )
+  .get_time()
+}
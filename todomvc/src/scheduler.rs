@@ -1,13 +1,13 @@
 use crate::controller::Controller;
 use crate::exit;
-use crate::view::View;
+use crate::view::Render;
 use crate::Message;
 use std::cell::RefCell;
 use std::rc::Rc;
 
 pub struct Scheduler {
     controller: Rc<RefCell<Option<Controller>>>,
-    view: Rc<RefCell<Option<View>>>,
+    view: Rc<RefCell<Option<Box<dyn Render>>>>,
     events: RefCell<Vec<Message>>,
     running: RefCell<bool>,
 }
@@ -30,9 +30,9 @@ impl Scheduler {
         }
     }
 
-    pub fn set_view(&self, view: View) {
+    pub fn set_view<V: Render + 'static>(&self, view: V) {
         if let Ok(mut view_data) = self.view.try_borrow_mut() {
-            *view_data = Some(view);
+            *view_data = Some(Box::new(view));
         } else {
             exit("This might be a deadlock");
         }
@@ -125,6 +125,8 @@ impl Scheduler {
     }
 }
 
+// The scheduler lives as long as the page, natively it may be dropped.
+#[cfg(target_arch = "wasm32")]
 impl Drop for Scheduler {
     fn drop(&mut self) {
         exit("calling drop on Scheduler");
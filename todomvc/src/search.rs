@@ -0,0 +1,211 @@
+//! Full-text search over item titles and notes.
+//!
+//! Text is normalized the same way for indexing and querying: compatibility
+//! decomposed (NFKD), stripped of combining marks, lowercased and recomposed,
+//! so `Café`, `CAFE` and `ｃａｆｅ` all match each other. Words are runs of letters and
+//! digits, and every word of a query has to match the start of a word.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// How much a word typed in full counts over a word typed in part.
+const EXACT_WEIGHT: f64 = 2.0;
+
+pub fn normalize(text: &str) -> String {
+  text
+    .nfkd()
+    .filter(|c| !is_combining_mark(*c))
+    .flat_map(char::to_lowercase)
+    // Recomposed, NFKD splits Hangul syllables into their letters.
+    .nfc()
+    .collect()
+}
+
+/// A word of a text, normalized, and where it is in the original text.
+#[derive(Debug, PartialEq)]
+pub struct Token {
+  pub term: String,
+  pub range: Range<usize>,
+}
+
+pub fn tokenize(text: &str) -> Vec<Token> {
+  let mut tokens = Vec::new();
+  let mut start = None;
+  let end = std::iter::once((text.len(), ' '));
+  for (i, c) in text.char_indices().chain(end) {
+    if c.is_alphanumeric() || (start.is_some() && is_combining_mark(c)) {
+      if start.is_none() {
+        start = Some(i);
+      }
+    } else if let Some(start) = start.take() {
+      let term = normalize(&text[start..i]);
+      if !term.is_empty() {
+        tokens.push(Token {
+          term,
+          range: start..i,
+        });
+      }
+    }
+  }
+  tokens
+}
+
+/// Distinct terms of a query, in order.
+fn query_terms(query: &str) -> Vec<String> {
+  let mut terms: Vec<String> = Vec::new();
+  for token in tokenize(query) {
+    if !terms.contains(&token.term) {
+      terms.push(token.term);
+    }
+  }
+  terms
+}
+
+/// Inverted index from terms to the documents containing them, updated one
+/// document at a time.
+#[derive(Default)]
+pub struct SearchIndex {
+  /// Documents by term, with the number of times the term occurs. Sorted so
+  /// prefixes find their terms as a range.
+  postings: BTreeMap<String, HashMap<String, usize>>,
+  /// Distinct terms of each document, to take it out again.
+  documents: HashMap<String, Vec<String>>,
+  /// Number of words of each document.
+  lengths: HashMap<String, usize>,
+}
+
+impl SearchIndex {
+  pub fn new() -> SearchIndex {
+    SearchIndex::default()
+  }
+
+  /// Indexes `text` as document `id`, replacing what it was before.
+  pub fn insert(&mut self, id: &str, text: &str) {
+    self.remove(id);
+    let tokens = tokenize(text);
+    let mut terms = Vec::new();
+    for token in tokens.iter() {
+      *self
+        .postings
+        .entry(token.term.clone())
+        .or_default()
+        .entry(id.to_string())
+        .or_insert(0) += 1;
+      if !terms.contains(&token.term) {
+        terms.push(token.term.clone());
+      }
+    }
+    self.documents.insert(id.to_string(), terms);
+    self.lengths.insert(id.to_string(), tokens.len());
+  }
+
+  pub fn remove(&mut self, id: &str) {
+    for term in self.documents.remove(id).unwrap_or_default() {
+      let now_unused = match self.postings.get_mut(&term) {
+        Some(documents) => {
+          documents.remove(id);
+          documents.is_empty()
+        }
+        None => false,
+      };
+      if now_unused {
+        self.postings.remove(&term);
+      }
+    }
+    self.lengths.remove(id);
+  }
+
+  pub fn clear(&mut self) {
+    *self = SearchIndex::new();
+  }
+
+  /// Number of documents.
+  pub fn len(&self) -> usize {
+    self.documents.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.documents.is_empty()
+  }
+
+  /// Documents containing every word of `query`, as `(id, score)` with the
+  /// best match first.
+  ///
+  /// A query word matches the words it is a prefix of. Each counts by how
+  /// much of the word it covers, twice for the whole word, and by how rare
+  /// the query word is (inverse document frequency). Words that occur more
+  /// than once count a little more, long titles a little less.
+  pub fn search(&self, query: &str) -> Vec<(String, f64)> {
+    let terms = query_terms(query);
+    if terms.is_empty() {
+      return Vec::new();
+    }
+    let total = self.documents.len() as f64;
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+
+    for (i, query_term) in terms.iter().enumerate() {
+      let typed = query_term.chars().count() as f64;
+      // Best weight of this query word in each document.
+      let mut matches: HashMap<&str, f64> = HashMap::new();
+      let prefixed = self
+        .postings
+        .range(query_term.clone()..)
+        .take_while(|(term, _)| term.starts_with(query_term.as_str()));
+      for (term, documents) in prefixed {
+        let weight = if term == query_term {
+          EXACT_WEIGHT
+        } else {
+          typed / term.chars().count() as f64
+        };
+        for (id, count) in documents.iter() {
+          let weight = weight * (1.0 + (*count as f64).ln());
+          let best = matches.entry(id.as_str()).or_insert(0.0);
+          if weight > *best {
+            *best = weight;
+          }
+        }
+      }
+
+      let idf = (1.0 + total / matches.len().max(1) as f64).ln();
+      if i == 0 {
+        for (id, weight) in matches {
+          scores.insert(id, weight * idf);
+        }
+      } else {
+        scores = scores
+          .into_iter()
+          .filter_map(|(id, score)| matches.get(id).map(|weight| (id, score + weight * idf)))
+          .collect();
+      }
+    }
+
+    let mut results: Vec<(String, f64)> = scores
+      .into_iter()
+      .map(|(id, score)| {
+        let length = *self.lengths.get(id).unwrap_or(&1) as f64;
+        (id.to_string(), score / (1.0 + length.ln()))
+      })
+      .collect();
+    results.sort_by(|a, b| {
+      b.1
+        .partial_cmp(&a.1)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| a.0.cmp(&b.0))
+    });
+    results
+  }
+}
+
+/// Byte ranges of the words of `text` that `query` matches, for
+/// highlighting.
+pub fn highlights(text: &str, query: &str) -> Vec<Range<usize>> {
+  let terms = query_terms(query);
+  tokenize(text)
+    .into_iter()
+    .filter(|token| terms.iter().any(|term| token.term.starts_with(term.as_str())))
+    .map(|token| token.range)
+    .collect()
+}
@@ -0,0 +1,83 @@
+/// The rows picked for a batch operation, in the order they were picked.
+///
+/// Ctrl-click toggles a single row and makes it the anchor, shift-click
+/// selects every row between the anchor and the clicked one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Selection {
+  ids: Vec<String>,
+  anchor: Option<String>,
+}
+
+impl Selection {
+  pub fn new() -> Selection {
+    Selection::default()
+  }
+
+  pub fn ids(&self) -> &[String] {
+    &self.ids
+  }
+
+  pub fn len(&self) -> usize {
+    self.ids.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.ids.is_empty()
+  }
+
+  pub fn contains(&self, id: &str) -> bool {
+    self.ids.iter().any(|selected| selected == id)
+  }
+
+  /// Selects `id` or takes it out of the selection again.
+  pub fn toggle(&mut self, id: &str) {
+    match self.ids.iter().position(|selected| selected == id) {
+      Some(index) => {
+        self.ids.remove(index);
+      }
+      None => self.ids.push(id.to_string()),
+    }
+    self.anchor = Some(id.to_string());
+  }
+
+  /// Adds the rows of `order` from the anchor to `id`, both included. Without
+  /// an anchor, or one that isn't shown any more, only `id` is added.
+  pub fn extend(&mut self, id: &str, order: &[String]) {
+    let end = order.iter().position(|shown| shown == id);
+    let start = self
+      .anchor
+      .as_ref()
+      .and_then(|anchor| order.iter().position(|shown| shown == anchor));
+    let range = match (start, end) {
+      (Some(start), Some(end)) if start <= end => &order[start..=end],
+      (Some(start), Some(end)) => &order[end..=start],
+      _ => {
+        if !self.contains(id) {
+          self.ids.push(id.to_string());
+        }
+        self.anchor = Some(id.to_string());
+        return;
+      }
+    };
+    for shown in range {
+      if !self.contains(shown) {
+        self.ids.push(shown.clone());
+      }
+    }
+  }
+
+  /// Keeps only the rows that are still shown.
+  pub fn retain(&mut self, shown: &[String]) {
+    self.ids.retain(|id| shown.contains(id));
+    if let Some(anchor) = self.anchor.take() {
+      if shown.contains(&anchor) {
+        self.anchor = Some(anchor);
+      }
+    }
+  }
+
+  pub fn clear(&mut self) {
+    self.ids.clear();
+    self.anchor = None;
+  }
+}
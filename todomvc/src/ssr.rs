@@ -0,0 +1,15 @@
+use crate::l10n::L10n;
+use crate::store::ItemList;
+use crate::template::Template;
+
+/// Renders the full initial HTML for `items` natively, so the page can be
+/// served with its content before the wasm module boots.
+///
+/// `route` is the active filter (`""`, `"active"` or `"completed"`) and
+/// `languages` the client's preferred locales, e.g. from `Accept-Language`.
+/// The page is marked with `data-ssr` so that `View` hydrates it instead of
+/// rendering it again.
+pub fn render_page(items: &ItemList, route: &str, languages: &[String]) -> String {
+  let l10n = L10n::negotiate(languages);
+  Template::page(&l10n, items, route)
+}
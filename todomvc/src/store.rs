@@ -1,126 +1,773 @@
-use js_sys::JSON;
-use wasm_bindgen::prelude::*;
+use crate::changes::{self, Change};
+use crate::lists::{ListInfo, DEFAULT_LIST};
+use crate::platform::{LocalStorage, Storage};
+use crate::preferences::{Density, Preferences, Theme};
+use crate::recurrence::{DateTime, Rule};
+use crate::search::SearchIndex;
 
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::Infallible;
+
+/// Persists the todo lists of one app. Items are only ever read and written
+/// for the current list, see `switch_list`.
 pub struct Store {
-  local_storage: web_sys::Storage,
+  local_storage: Box<dyn Storage>,
   data: ItemList,
+  preferences: Preferences,
   name: String,
+  lists: Vec<ListInfo>,
+  list: String,
+  /// Titles of the current list, kept up to date by every change to `data`.
+  index: SearchIndex,
+  /// Open while `transaction` runs.
+  transaction: Option<Transaction>,
+  subscribers: Vec<(usize, Subscriber)>,
+  next_subscriber: usize,
+  /// The items as subscribers last saw them.
+  published: ItemList,
+  /// The open scheduled items of each other list, see `scheduled`. Read on
+  /// demand and dropped whenever another list or the lists change.
+  elsewhere: RefCell<Option<Vec<(String, Vec<Item>)>>>,
+}
+
+/// Receives the changes of every write of the current list, see
+/// `Store::subscribe`.
+pub type Subscriber = Box<dyn FnMut(&[Change])>;
+
+/// What a transaction changed, written when it commits.
+struct Transaction {
+  /// The current list as it was before, restored when rolling back.
+  data: ItemList,
+  /// Whether the current list changed.
+  dirty: bool,
+  /// New contents of other lists, by storage key.
+  lists: Vec<(String, ItemList)>,
 }
 
 impl Store {
   pub fn new(name: &str) -> Option<Store> {
-    let window = web_sys::window()?;
-    if let Ok(Some(local_storage)) = window.local_storage() {
-      let mut store = Store {
-        local_storage,
-        data: ItemList::new(),
-        name: String::from(name),
-      };
-      store.fetch_local_storage();
-      Some(store)
+    let local_storage = LocalStorage::new()?;
+    Some(Store::with_storage(Box::new(local_storage), name))
+  }
+
+  /// A store persisting to `storage` under keys starting with `name`.
+  pub fn with_storage(local_storage: Box<dyn Storage>, name: &str) -> Store {
+    let mut store = Store {
+      local_storage,
+      data: ItemList::new(),
+      preferences: Preferences::default(),
+      name: String::from(name),
+      lists: Vec::new(),
+      list: String::new(),
+      index: SearchIndex::new(),
+      transaction: None,
+      subscribers: Vec::new(),
+      next_subscriber: 0,
+      published: ItemList::new(),
+      elsewhere: RefCell::new(None),
+    };
+    store.fetch_lists();
+    store.fetch_local_storage();
+    store.fetch_preferences();
+    store
+  }
+
+  fn lists_key(&self) -> String {
+    format!("{}-lists", self.name)
+  }
+
+  /// The default list keeps the plain `name` key, other lists are stored
+  /// under `<name>-list-<list>`.
+  fn list_key(&self, list: &str) -> String {
+    if list == DEFAULT_LIST {
+      self.name.clone()
     } else {
-      None
+      format!("{}-list-{}", self.name, list)
     }
   }
 
-  fn fetch_local_storage(&mut self) -> Option<()> {
+  /// Lists are stored under `<name>-lists` as an array of `[name, archived]`
+  /// pairs, in switcher order. Without it there is only the default list.
+  fn fetch_lists(&mut self) {
+    let stored: Option<Vec<(String, bool)>> = self
+      .local_storage
+      .get_item(&self.lists_key())
+      .and_then(|value| serde_json::from_str(&value).ok());
+    self.lists = stored
+      .unwrap_or_default()
+      .into_iter()
+      .map(|(name, archived)| ListInfo { name, archived })
+      .collect();
+    if self.lists.is_empty() {
+      self.lists.push(ListInfo {
+        name: DEFAULT_LIST.to_string(),
+        archived: false,
+      });
+    }
+    self.list = self.first_list();
+  }
+
+  fn sync_lists(&mut self) {
+    self.elsewhere.replace(None);
+    let array: Vec<(&str, bool)> = self
+      .lists
+      .iter()
+      .map(|list| (list.name.as_str(), list.archived))
+      .collect();
+    if let Ok(storage_string) = serde_json::to_string(&array) {
+      self
+        .local_storage
+        .set_item(&self.lists_key(), storage_string.as_str());
+    }
+  }
+
+  /// The list opened when no other is asked for: the first one that is not
+  /// archived.
+  fn first_list(&self) -> String {
+    self
+      .lists
+      .iter()
+      .find(|list| !list.archived)
+      .unwrap_or(&self.lists[0])
+      .name
+      .clone()
+  }
+
+  fn fetch_local_storage(&mut self) {
+    // The previous list is now one of the others.
+    self.elsewhere.replace(None);
+    self.data = self.read_items(&self.list_key(&self.list));
+    self.rebuild_index();
+    if !self.subscribers.is_empty() {
+      self.published = self.data.clone();
+      let items = self.data.iter().cloned().collect();
+      self.publish(&[Change::Reset(items)]);
+    }
+  }
+
+  fn rebuild_index(&mut self) {
+    self.index.clear();
+    for item in self.data.iter() {
+      self.index.insert(&item.id, &item.searchable_text());
+    }
+  }
+
+  /// Items are stored as a JSON array of `[title, completed, id]` arrays.
+  /// Optional fields follow, `null` where unset: the id of the parent of
+  /// subtasks, the due date, the recurrence rule and the notes.
+  fn read_items(&self, key: &str) -> ItemList {
     let mut item_list = ItemList::new();
 
-    if let Ok(Some(value)) = self.local_storage.get_item(&self.name) {
-      let data = JSON::parse(&value).ok()?;
-      let iter = js_sys::try_iter(&data).ok()??;
-      for item in iter {
-        let item = item.ok()?;
-        let item_array: &js_sys::Array = wasm_bindgen::JsCast::dyn_ref(&item)?;
-        let title = item_array.shift().as_string()?;
-        let completed = item_array.shift().as_bool()?;
-        let id = item_array.shift().as_string()?;
+    if let Some(value) = self.local_storage.get_item(key) {
+      let data: Vec<Vec<serde_json::Value>> = serde_json::from_str(&value).unwrap_or_default();
+      for entry in data {
+        let field = |i: usize| entry.get(i).and_then(|value| value.as_str()).map(String::from);
         let temp_item = Item {
-          title,
-          completed,
-          id,
+          title: field(0).unwrap_or_default(),
+          completed: entry.get(1).and_then(|value| value.as_bool()).unwrap_or(false),
+          id: field(2).unwrap_or_default(),
+          parent: field(3),
+          due: field(4).and_then(|due| DateTime::parse(&due)),
+          recurrence: field(5).and_then(|rule| Rule::parse(&rule)),
+          notes: field(6),
         };
         item_list.push(temp_item);
       }
     }
 
-    self.data = item_list;
+    item_list
+  }
+
+  fn write_items(&self, key: &str, items: &ItemList) {
+    let array: Vec<serde_json::Value> = items
+      .iter()
+      .map(|item| {
+        let mut entry = vec![
+          serde_json::json!(item.title),
+          serde_json::json!(item.completed),
+          serde_json::json!(item.id),
+          serde_json::json!(item.parent),
+          serde_json::json!(item.due.map(|due| due.to_string())),
+          serde_json::json!(item.recurrence.as_ref().map(|rule| rule.to_string())),
+          serde_json::json!(item.notes),
+        ];
+        // Trailing unset fields are left out.
+        while entry.len() > 3 && entry[entry.len() - 1].is_null() {
+          entry.pop();
+        }
+        serde_json::Value::Array(entry)
+      })
+      .collect();
+    if let Ok(storage_string) = serde_json::to_string(&array) {
+      self.local_storage.set_item(key, storage_string.as_str());
+    }
+    if key != self.list_key(&self.list) {
+      self.elsewhere.replace(None);
+    }
+  }
+
+  fn preferences_key(&self) -> String {
+    format!("{}-preferences", self.name)
+  }
+
+  /// Preferences live next to the items under `<name>-preferences`, as a
+  /// `[theme, density, default_filter, confirm_delete]` array.
+  fn fetch_preferences(&mut self) -> Option<()> {
+    let value = self.local_storage.get_item(&self.preferences_key())?;
+    let (theme, density, default_filter, confirm_delete): (String, String, String, bool) =
+      serde_json::from_str(&value).ok()?;
+    self.preferences = Preferences {
+      theme: Theme::parse(&theme),
+      density: Density::parse(&density),
+      default_filter,
+      confirm_delete,
+    };
     Some(())
   }
 
-  fn sync_local_storage(&mut self) {
-    let array = js_sys::Array::new();
-    for item in self.data.iter() {
-      let child = js_sys::Array::new();
-      let s = item.title.clone();
-      child.push(&JsValue::from(&s));
-      child.push(&JsValue::from(item.completed));
-      child.push(&JsValue::from(item.id.to_string()));
+  pub fn preferences(&self) -> &Preferences {
+    &self.preferences
+  }
 
-      array.push(&JsValue::from(child));
-    }
-    if let Ok(storage_string) = JSON::stringify(&JsValue::from(array)) {
-      let storage_string: String = storage_string.to_string().into();
+  pub fn set_preferences(&mut self, preferences: Preferences) {
+    let array = (
+      preferences.theme.as_str(),
+      preferences.density.as_str(),
+      preferences.default_filter.as_str(),
+      preferences.confirm_delete,
+    );
+    if let Ok(storage_string) = serde_json::to_string(&array) {
       self
         .local_storage
-        .set_item(&self.name, storage_string.as_str())
-        .unwrap();
+        .set_item(&self.preferences_key(), storage_string.as_str());
+    }
+    self.preferences = preferences;
+  }
+
+  fn sync_local_storage(&mut self) {
+    match self.transaction {
+      Some(ref mut transaction) => transaction.dirty = true,
+      None => {
+        self.write_items(&self.list_key(&self.list), &self.data);
+        if !self.subscribers.is_empty() {
+          let changes = changes::diff(&self.published, &self.data);
+          self.published = self.data.clone();
+          self.publish(&changes);
+        }
+      }
+    }
+  }
+
+  fn publish(&mut self, changes: &[Change]) {
+    if changes.is_empty() {
+      return;
+    }
+    for (_, subscriber) in self.subscribers.iter_mut() {
+      subscriber(changes);
+    }
+  }
+
+  /// Calls `subscriber` with the changes to the items of the current list
+  /// each time they are written, once per transaction. It starts with a
+  /// `Change::Reset` to the items as they are now. Returns an id for
+  /// `unsubscribe`.
+  pub fn subscribe(&mut self, mut subscriber: Subscriber) -> usize {
+    if self.subscribers.is_empty() {
+      self.published = self.data.clone();
+    }
+    subscriber(&[Change::Reset(self.published.iter().cloned().collect())]);
+    let id = self.next_subscriber;
+    self.next_subscriber += 1;
+    self.subscribers.push((id, subscriber));
+    id
+  }
+
+  pub fn unsubscribe(&mut self, id: usize) {
+    self.subscribers.retain(|(subscriber, _)| *subscriber != id);
+    if self.subscribers.is_empty() {
+      self.published = ItemList::new();
+    }
+  }
+
+  /// The items of another list, including the changes of an open
+  /// transaction.
+  fn read_list(&self, key: &str) -> ItemList {
+    let pending = self
+      .transaction
+      .as_ref()
+      .and_then(|transaction| transaction.lists.iter().find(|(list, _)| list == key));
+    match pending {
+      Some((_, items)) => items.clone(),
+      None => self.read_items(key),
+    }
+  }
+
+  /// Writes the items of another list, or keeps them until an open
+  /// transaction commits.
+  fn write_list(&mut self, key: &str, items: ItemList) {
+    match self.transaction {
+      Some(ref mut transaction) => {
+        transaction.lists.retain(|(list, _)| list != key);
+        transaction.lists.push((key.to_string(), items));
+      }
+      None => self.write_items(key, &items),
+    }
+    self.elsewhere.replace(None);
+  }
+
+  /// Runs the item changes of `f` as a whole. They are persisted when `f`
+  /// returns `Ok`, with a single write for each list that changed, and
+  /// undone when it returns `Err`.
+  ///
+  /// Transactions don't nest, one opened inside another joins it. Changes to
+  /// the lists themselves, such as `rename_list`, are written right away,
+  /// and `f` must not switch to another list.
+  pub fn transaction<F, T, E>(&mut self, f: F) -> Result<T, E>
+  where
+    F: FnOnce(&mut Store) -> Result<T, E>,
+  {
+    if self.transaction.is_some() {
+      return f(self);
+    }
+    self.transaction = Some(Transaction {
+      data: self.data.clone(),
+      dirty: false,
+      lists: Vec::new(),
+    });
+    let result = f(self);
+    let transaction = match self.transaction.take() {
+      Some(transaction) => transaction,
+      None => return result,
+    };
+    if result.is_ok() {
+      for (key, items) in transaction.lists.iter() {
+        self.write_items(key, items);
+      }
+      if transaction.dirty {
+        self.sync_local_storage();
+      }
+    } else {
+      self.data = transaction.data;
+      self.rebuild_index();
+      // It may have seen the other lists as changed by the transaction.
+      self.elsewhere.replace(None);
+    }
+    result
+  }
+
+  /// A `transaction` that can't fail.
+  pub fn batch<F, T>(&mut self, f: F) -> T
+  where
+    F: FnOnce(&mut Store) -> T,
+  {
+    match self.transaction(|store| Ok::<T, Infallible>(f(store))) {
+      Ok(result) => result,
+      Err(never) => match never {},
+    }
+  }
+
+  pub fn lists(&self) -> &[ListInfo] {
+    &self.lists
+  }
+
+  /// Name of the current list.
+  pub fn list(&self) -> &str {
+    &self.list
+  }
+
+  fn list_position(&self, name: &str) -> Option<usize> {
+    self.lists.iter().position(|list| list.name == name)
+  }
+
+  /// Makes `name` the current list, returns `false` if there is no such list.
+  pub fn switch_list(&mut self, name: &str) -> bool {
+    if self.list_position(name).is_none() {
+      return false;
+    }
+    self.list = name.to_string();
+    self.fetch_local_storage();
+    true
+  }
+
+  /// Adds an empty list, unless one called `name` already exists.
+  pub fn create_list(&mut self, name: &str) -> bool {
+    if self.list_position(name).is_some() {
+      return false;
+    }
+    self.lists.push(ListInfo {
+      name: name.to_string(),
+      archived: false,
+    });
+    self.sync_lists();
+    true
+  }
+
+  /// Renames `from` to `to`, moving its items to the new key. Inside a
+  /// transaction the items are moved as committed, and its changes to them
+  /// are written under the new key when it commits.
+  pub fn rename_list(&mut self, from: &str, to: &str) -> bool {
+    let position = match self.list_position(from) {
+      Some(position) if self.list_position(to).is_none() => position,
+      _ => return false,
+    };
+    let (from_key, to_key) = (self.list_key(from), self.list_key(to));
+    let items = self.read_items(&from_key);
+    self.write_items(&to_key, &items);
+    // The current list is written under the key of `self.list`, renamed
+    // below, the others under the key they were changed under.
+    if let Some(ref mut transaction) = self.transaction {
+      for (key, _) in transaction.lists.iter_mut() {
+        if *key == from_key {
+          *key = to_key.clone();
+        }
+      }
+    }
+    self.lists[position].name = to.to_string();
+    self.sync_lists();
+    self.local_storage.remove_item(&from_key);
+    if self.list == from {
+      self.list = to.to_string();
+    }
+    true
+  }
+
+  /// Deletes `name` and its items. The last list can't be deleted, deleting
+  /// the current one switches to `first_list`.
+  pub fn delete_list(&mut self, name: &str) -> bool {
+    let position = match self.list_position(name) {
+      Some(position) if self.lists.len() > 1 => position,
+      _ => return false,
+    };
+    self.lists.remove(position);
+    self.sync_lists();
+    let key = self.list_key(name);
+    // Or an open transaction would bring it back when it commits.
+    if let Some(ref mut transaction) = self.transaction {
+      transaction.lists.retain(|(list, _)| *list != key);
+    }
+    self.local_storage.remove_item(&key);
+    if self.list == name {
+      let list = self.first_list();
+      self.switch_list(&list);
+    }
+    true
+  }
+
+  pub fn archive_list(&mut self, name: &str, archived: bool) -> bool {
+    let position = match self.list_position(name) {
+      Some(position) => position,
+      None => return false,
+    };
+    self.lists[position].archived = archived;
+    self.sync_lists();
+    true
+  }
+
+  /// Moves the item `id` with its subtasks from the current list to the end
+  /// of `list`, where it becomes a top-level item.
+  pub fn move_item(&mut self, id: &str, list: &str) -> bool {
+    self.move_items(&[id.to_string()], list)
+  }
+
+  /// Moves each of `ids` like `move_item`, writing both lists once. Returns
+  /// whether any item was moved.
+  pub fn move_items(&mut self, ids: &[String], list: &str) -> bool {
+    if list == self.list || self.list_position(list).is_none() {
+      return false;
+    }
+    let key = self.list_key(list);
+    let mut items = self.read_list(&key);
+    let mut moved = false;
+    for id in ids {
+      let parent = match self.data.iter().find(|item| &item.id == id) {
+        Some(item) => item.parent.clone(),
+        // Already gone with a selected parent.
+        None => continue,
+      };
+      let subtree = self.subtree(id);
+      while let Some(index) = self.data.iter().position(|item| subtree.contains(&item.id)) {
+        let mut item = self.data.remove(index);
+        self.index.remove(&item.id);
+        if &item.id == id {
+          item.parent = None;
+        }
+        items.push(item);
+      }
+      self.roll_up(parent);
+      moved = true;
     }
+    if moved {
+      self.write_list(&key, items);
+      self.sync_local_storage();
+    }
+    moved
   }
 
   pub fn find(&mut self, query: ItemQuery) -> Option<ItemListSlice<'_>> {
-    Some(
-      self
+    Some(query.select(&self.data))
+  }
+
+  /// Ids of `id` and of its subtasks at any depth.
+  pub fn subtree(&self, id: &str) -> Vec<String> {
+    let mut ids = vec![id.to_string()];
+    let mut i = 0;
+    while i < ids.len() {
+      for item in self.data.iter() {
+        // Ids already seen guard against parent cycles in stored data.
+        if item.parent.as_ref() == Some(&ids[i]) && !ids.contains(&item.id) {
+          ids.push(item.id.clone());
+        }
+      }
+      i += 1;
+    }
+    ids
+  }
+
+  /// Whether `id` is a subtask or has subtasks.
+  pub fn is_nested(&self, id: &str) -> bool {
+    self.data.iter().any(|item| match item.parent {
+      Some(ref parent) => item.id == id || parent == id,
+      None => false,
+    })
+  }
+
+  fn parent_of(&self, id: &str) -> Option<String> {
+    self
+      .data
+      .iter()
+      .find(|item| item.id == id)
+      .and_then(|item| item.parent.clone())
+  }
+
+  /// Recomputes the completion of `parent` and its ancestors, a parent is
+  /// completed exactly when all of its subtasks are.
+  fn roll_up(&mut self, parent: Option<String>) {
+    let mut parent = parent;
+    let mut seen = Vec::new();
+    while let Some(id) = parent {
+      if seen.contains(&id) {
+        break;
+      }
+      let mut children = self
         .data
         .iter()
-        .filter(|todo| query.matches(*todo))
-        .collect(),
-    )
+        .filter(|item| item.parent.as_ref() == Some(&id))
+        .peekable();
+      if children.peek().is_some() {
+        let completed = children.all(|item| item.completed);
+        if let Some(item) = self.data.iter_mut().find(|item| item.id == id) {
+          item.completed = completed;
+        }
+      }
+      parent = self.parent_of(&id);
+      seen.push(id);
+    }
   }
 
+  /// Completing or reopening an item does the same to its subtasks, editing
+  /// the title or the notes only touches the item itself.
   pub fn update(&mut self, update: ItemUpdate) {
     let id = update.id();
+    let ids = match update {
+      ItemUpdate::Completed { .. } => self.subtree(&id),
+      ItemUpdate::Title { .. } | ItemUpdate::Notes { .. } | ItemUpdate::Schedule { .. } => {
+        vec![id.clone()]
+      }
+    };
     self.data.iter_mut().for_each(|todo| {
-      if id == todo.id {
+      if ids.contains(&todo.id) {
         todo.update(&update);
       }
     });
+    if let ItemUpdate::Title { .. } | ItemUpdate::Notes { .. } = update {
+      if let Some(item) = self.data.iter().find(|todo| todo.id == id) {
+        self.index.insert(&id, &item.searchable_text());
+      }
+    }
+    let parent = self.parent_of(&id);
+    self.roll_up(parent);
 
     self.sync_local_storage();
   }
 
   pub fn insert(&mut self, item: Item) {
+    let parent = item.parent.clone();
+    self.index.insert(&item.id, &item.searchable_text());
     self.data.push(item);
+    self.roll_up(parent);
     self.sync_local_storage();
   }
 
+  /// Removes the matching items together with their subtasks.
   pub fn remove(&mut self, query: ItemQuery) {
-    self.data.retain(|todo| !query.matches(todo));
+    let matched: Vec<(String, Option<String>)> = self
+      .data
+      .iter()
+      .filter(|todo| query.matches(todo))
+      .map(|todo| (todo.id.clone(), todo.parent.clone()))
+      .collect();
+    let mut ids = Vec::new();
+    for (id, _) in matched.iter() {
+      ids.extend(self.subtree(id));
+    }
+    self.data.retain(|todo| !ids.contains(&todo.id));
+    for id in ids.iter() {
+      self.index.remove(id);
+    }
+    for (_, parent) in matched {
+      self.roll_up(parent);
+    }
     self.sync_local_storage();
   }
 
-  pub fn count(&mut self) -> Option<(usize, usize, usize)> {
-    self.find(ItemQuery::EmptyItemQuery).map(|data| {
-      let total = data.length();
+  /// Makes `id` the last subtask of the sibling shown above it.
+  pub fn indent(&mut self, id: &str) -> bool {
+    let index = match self.data.iter().position(|item| item.id == id) {
+      Some(index) => index,
+      None => return false,
+    };
+    let parent = self.data.get(index).and_then(|item| item.parent.clone());
+    let sibling = self.data.iter().take(index).rev().find(|item| item.parent == parent);
+    let sibling = match sibling {
+      Some(sibling) => sibling.id.clone(),
+      None => return false,
+    };
+    // Subtasks are shown in storage order, the end makes it the last one.
+    let mut item = self.data.remove(index);
+    item.parent = Some(sibling.clone());
+    self.data.push(item);
+    self.roll_up(Some(sibling));
+    self.roll_up(parent);
+    self.sync_local_storage();
+    true
+  }
 
-      let mut completed = 0;
-      for item in data.iter() {
-        if item.completed {
-          completed += 1;
-        }
+  /// Turns the subtask `id` into a sibling shown right below its parent.
+  pub fn outdent(&mut self, id: &str) -> bool {
+    let parent = match self.parent_of(id) {
+      Some(parent) => parent,
+      None => return false,
+    };
+    let index = match self.data.iter().position(|item| item.id == id) {
+      Some(index) => index,
+      None => return false,
+    };
+    let mut item = self.data.remove(index);
+    item.parent = self.parent_of(&parent);
+    let grandparent = item.parent.clone();
+    let position = self
+      .data
+      .iter()
+      .position(|item| item.id == parent)
+      .map_or(0, |position| position + 1);
+    self.data.insert(position, item);
+    self.roll_up(Some(parent));
+    self.roll_up(grandparent);
+    self.sync_local_storage();
+    true
+  }
+
+  /// Items matching `query` whose title has every word of `search`, the
+  /// best matches first.
+  pub fn search(&self, search: &str, query: ItemQuery) -> ItemList {
+    let scores: HashMap<String, f64> = self.index.search(search).into_iter().collect();
+    let shown = query.select(&self.data);
+    let mut found: Vec<(&Item, f64)> = shown
+      .iter()
+      .filter_map(|item| scores.get(&item.id).map(|score| (*item, *score)))
+      .collect();
+    // Stable, so equally good matches keep the list order.
+    found.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+
+    let mut items = ItemList::new();
+    for (item, _) in found {
+      items.push(item.clone());
+    }
+    items
+  }
+
+  /// Open items with a due date on the lists that aren't archived, each
+  /// with the name of its list. The other lists are only read from storage
+  /// the first time after they change.
+  pub fn scheduled(&self) -> Vec<(String, Item)> {
+    let is_due = |item: &Item| !item.completed && item.due.is_some();
+    if self.elsewhere.borrow().is_none() {
+      let elsewhere = self
+        .lists
+        .iter()
+        .filter(|list| !list.archived && list.name != self.list)
+        .map(|list| {
+          let items = self.read_list(&self.list_key(&list.name));
+          let due = items.iter().filter(|item| is_due(item)).cloned().collect();
+          (list.name.clone(), due)
+        })
+        .collect();
+      self.elsewhere.replace(Some(elsewhere));
+    }
+    let elsewhere = self.elsewhere.borrow();
+    let elsewhere = elsewhere.as_ref().map_or(&[][..], Vec::as_slice);
+
+    let mut scheduled = Vec::new();
+    for list in self.lists.iter().filter(|list| !list.archived) {
+      let items: Vec<&Item> = if list.name == self.list {
+        self.data.iter().filter(|item| is_due(item)).collect()
+      } else {
+        elsewhere
+          .iter()
+          .filter(|(name, _)| *name == list.name)
+          .flat_map(|(_, items)| items.iter())
+          .collect()
+      };
+      for item in items {
+        scheduled.push((list.name.clone(), item.clone()));
       }
-      (total, total - completed, completed)
-    })
+    }
+    scheduled
+  }
+
+  /// Item counts as `(total, active, completed)`, see `ItemList::count`.
+  pub fn count(&mut self) -> Option<(usize, usize, usize)> {
+    Some(self.data.count())
   }
 }
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct Item {
   pub id: String,
   pub title: String,
   pub completed: bool,
+  /// Id of the item this is a subtask of.
+  pub parent: Option<String>,
+  /// When a reminder is shown, and where a repeating item starts from.
+  pub due: Option<DateTime>,
+  pub recurrence: Option<Rule>,
+  pub notes: Option<String>,
 }
 
 impl Item {
+  /// An open top-level item, without a schedule or notes.
+  pub fn new(id: impl Into<String>, title: impl Into<String>) -> Item {
+    Item {
+      id: id.into(),
+      title: title.into(),
+      completed: false,
+      parent: None,
+      due: None,
+      recurrence: None,
+      notes: None,
+    }
+  }
+
+  /// What search looks through: the title, then the notes.
+  pub fn searchable_text(&self) -> String {
+    match &self.notes {
+      Some(notes) => format!("{}\n{}", self.title, notes),
+      None => self.title.clone(),
+    }
+  }
+
   pub fn update(&mut self, update: &ItemUpdate) {
     match update {
       ItemUpdate::Title { title, .. } => {
@@ -129,6 +776,15 @@ impl Item {
       ItemUpdate::Completed { completed, .. } => {
         self.completed = *completed;
       }
+      ItemUpdate::Notes { notes, .. } => {
+        self.notes = notes.clone();
+      }
+      ItemUpdate::Schedule {
+        due, recurrence, ..
+      } => {
+        self.due = *due;
+        self.recurrence = recurrence.clone();
+      }
     }
   }
 }
@@ -141,6 +797,7 @@ pub trait ItemListTrait<T> {
   fn iter(&self) -> std::slice::Iter<'_, T>;
 }
 
+#[derive(Clone)]
 pub struct ItemList {
   list: Vec<Item>,
 }
@@ -155,6 +812,46 @@ impl ItemList {
   fn iter_mut(&mut self) -> std::slice::IterMut<'_, Item> {
     self.list.iter_mut()
   }
+  fn remove(&mut self, index: usize) -> Item {
+    self.list.remove(index)
+  }
+  fn insert(&mut self, index: usize, item: Item) {
+    self.list.insert(index, item)
+  }
+
+  /// Counts of the items without subtasks as `(total, active, completed)`.
+  /// Parents only summarize the progress of their subtasks.
+  pub fn count(&self) -> (usize, usize, usize) {
+    let leaves: Vec<&Item> = self
+      .list
+      .iter()
+      .filter(|item| {
+        !self
+          .list
+          .iter()
+          .any(|child| child.parent.as_ref() == Some(&item.id))
+      })
+      .collect();
+    let total = leaves.len();
+    let completed = leaves.iter().filter(|item| item.completed).count();
+    (total, total - completed, completed)
+  }
+
+  /// The top-level item `item` is a subtask of, or `item` itself.
+  fn root<'a>(&'a self, item: &'a Item) -> &'a Item {
+    let mut root = item;
+    // Bounded by the list length in case the stored parents form a cycle.
+    for _ in 0..self.list.len() {
+      match root.parent {
+        Some(ref parent) => match self.list.iter().find(|item| &item.id == parent) {
+          Some(parent) => root = parent,
+          None => break,
+        },
+        None => break,
+      }
+    }
+    root
+  }
 }
 
 impl ItemListTrait<Item> for ItemList {
@@ -224,13 +921,7 @@ impl<'a> Into<ItemList> for ItemListSlice<'a> {
     let mut i = ItemList::new();
     let items = self.list.into_iter();
     for j in items {
-      // TODO neaten this cloning?
-      let item = Item {
-        id: j.id.clone(),
-        completed: j.completed,
-        title: j.title.clone(),
-      };
-      i.push(item);
+      i.push(j.clone());
     }
     i
   }
@@ -243,7 +934,29 @@ pub enum ItemQuery {
 }
 
 impl ItemQuery {
-  fn matches(&self, item: &Item) -> bool {
+  /// The query behind a filter route such as `"active"`.
+  pub fn for_route(route: &str) -> ItemQuery {
+    match route {
+      "completed" => ItemQuery::Completed { completed: true },
+      "active" => ItemQuery::Completed { completed: false },
+      _ => ItemQuery::EmptyItemQuery,
+    }
+  }
+
+  /// The items to show for this query. Completion filters apply to top-level
+  /// items and keep their subtasks with them, so shown parents always come
+  /// with all of their subtasks.
+  pub fn select<'a>(&self, items: &'a ItemList) -> ItemListSlice<'a> {
+    match *self {
+      ItemQuery::Completed { completed } => items
+        .iter()
+        .filter(|item| items.root(item).completed == completed)
+        .collect(),
+      _ => items.iter().filter(|item| self.matches(item)).collect(),
+    }
+  }
+
+  pub fn matches(&self, item: &Item) -> bool {
     match *self {
       ItemQuery::EmptyItemQuery => true,
       ItemQuery::Id { ref id } => &item.id == id,
@@ -255,6 +968,12 @@ impl ItemQuery {
 pub enum ItemUpdate {
   Title { id: String, title: String },
   Completed { id: String, completed: bool },
+  Notes { id: String, notes: Option<String> },
+  Schedule {
+    id: String,
+    due: Option<DateTime>,
+    recurrence: Option<Rule>,
+  },
 }
 
 impl ItemUpdate {
@@ -262,6 +981,8 @@ impl ItemUpdate {
     match self {
       ItemUpdate::Title { id, .. } => id.clone(),
       ItemUpdate::Completed { id, .. } => id.clone(),
+      ItemUpdate::Notes { id, .. } => id.clone(),
+      ItemUpdate::Schedule { id, .. } => id.clone(),
     }
   }
 }
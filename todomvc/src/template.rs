@@ -1,40 +1,183 @@
-use crate::store::{ItemList, ItemListTrait};
+use crate::l10n::L10n;
+use crate::search;
+use crate::store::{Item, ItemList, ItemListSlice, ItemListTrait, ItemQuery};
 use askama::Template as AskamaTemplate;
 
 #[derive(AskamaTemplate)]
 #[template(path = "row.html")]
 struct RowTemplate<'a> {
     id: &'a str,
-    title: &'a str,
+    title: &'a [TextPart<'a>],
+    notes: &'a [TextPart<'a>],
+    notes_text: &'a str,
     completed: bool,
+    toggle_label: &'a str,
+    delete_label: &'a str,
+    move_label: &'a str,
+    add_subtask_label: &'a str,
+    has_subtasks: bool,
+    progress: &'a str,
+    progress_label: &'a str,
+    collapse_label: &'a str,
+    schedule_label: &'a str,
+    notes_label: &'a str,
+    due: &'a str,
+    due_label: &'a str,
+    repeat: &'a str,
+    subtasks: &'a str,
+}
+
+/// A piece of a title or of the notes, `highlighted` where it matches the
+/// search.
+struct TextPart<'a> {
+    text: &'a str,
+    highlighted: bool,
+}
+
+/// `text` split up around the words `search` matches.
+fn text_parts<'a>(text: &'a str, search: &str) -> Vec<TextPart<'a>> {
+    let mut parts = Vec::new();
+    let mut end = 0;
+    for range in search::highlights(text, search) {
+        if range.start > end {
+            parts.push(TextPart {
+                text: &text[end..range.start],
+                highlighted: false,
+            });
+        }
+        end = range.end;
+        parts.push(TextPart {
+            text: &text[range],
+            highlighted: true,
+        });
+    }
+    if end < text.len() || parts.is_empty() {
+        parts.push(TextPart {
+            text: &text[end..],
+            highlighted: false,
+        });
+    }
+    parts
 }
 
 #[derive(AskamaTemplate)]
 #[template(path = "itemsLeft.html")]
-struct ItemsLeftTemplate {
-    active_todos: usize,
+struct ItemsLeftTemplate<'a> {
+    items_left: &'a str,
+}
+
+#[derive(AskamaTemplate)]
+#[template(path = "page.html")]
+struct PageTemplate<'a> {
+    l10n: &'a L10n,
+    route: &'a str,
+    rows: &'a str,
+    items_left: &'a str,
+    has_items: bool,
+    has_completed: bool,
+    all_completed: bool,
 }
 
 pub struct Template {}
 
 impl Template {
-    pub fn item_list(items: ItemList) -> String {
+    /// Rows for `items`, with subtasks nested in their parent's row.
+    /// Subtasks whose parent isn't in `items` are shown at the top level.
+    pub fn item_list(l10n: &L10n, items: ItemList) -> String {
+        Template::search_results(l10n, items, "")
+    }
+
+    /// Like `item_list`, with the words `search` matches highlighted.
+    pub fn search_results(l10n: &L10n, items: ItemList, search: &str) -> String {
         let mut output = String::from("");
         for item in items.iter() {
-            let row = RowTemplate {
-                id: &item.id,
-                completed: item.completed,
-                title: &item.title,
-            };
-            if let Ok(res) = row.render() {
-                output.push_str(&res);
+            let shown_parent = item
+                .parent
+                .as_ref()
+                .map_or(false, |parent| items.iter().any(|other| &other.id == parent));
+            if !shown_parent {
+                output.push_str(&Template::row(l10n, &items, item, search));
             }
         }
         output
     }
 
-    pub fn item_counter(active_todos: usize) -> String {
-        let items_left = ItemsLeftTemplate { active_todos };
+    fn row(l10n: &L10n, items: &ItemList, item: &Item, search: &str) -> String {
+        let subtasks: Vec<&Item> = items
+            .iter()
+            .filter(|other| other.parent.as_ref() == Some(&item.id))
+            .collect();
+        let subtask_rows: String = subtasks
+            .iter()
+            .map(|subtask| Template::row(l10n, items, subtask, search))
+            .collect();
+        let done = subtasks.iter().filter(|subtask| subtask.completed).count();
+        let done_string = done.to_string();
+
+        let due = item.due.map(|due| due.human()).unwrap_or_default();
+        let repeat = item
+            .recurrence
+            .as_ref()
+            .map(|rule| rule.to_string())
+            .unwrap_or_default();
+        let due_key = if repeat.is_empty() {
+            "due-label"
+        } else {
+            "due-repeating-label"
+        };
+
+        let notes = item.notes.as_deref().unwrap_or_default();
+        let args = [("title", item.title.as_str())];
+        let row = RowTemplate {
+            id: &item.id,
+            completed: item.completed,
+            title: &text_parts(&item.title, search),
+            notes: &text_parts(notes, search),
+            notes_text: notes,
+            toggle_label: &l10n.format("toggle-item-label", &args),
+            delete_label: &l10n.format("delete-item-label", &args),
+            move_label: &l10n.format("move-item-label", &args),
+            add_subtask_label: &l10n.format("add-subtask-label", &args),
+            has_subtasks: !subtasks.is_empty(),
+            progress: &format!("{}/{}", done, subtasks.len()),
+            progress_label: &l10n.plural(
+                "subtask-progress",
+                subtasks.len(),
+                &[("done", &done_string)],
+            ),
+            collapse_label: &l10n.format("collapse-label", &args),
+            schedule_label: &l10n.format("schedule-label", &args),
+            notes_label: &l10n.format("notes-label", &args),
+            due: &due,
+            due_label: &l10n.format(due_key, &[("due", &due)]),
+            repeat: &repeat,
+            subtasks: &subtask_rows,
+        };
+        row.render().unwrap_or_default()
+    }
+
+    /// The whole page as the client would show it for `items` on `route`,
+    /// using the same row and counter markup as the view.
+    pub fn page(l10n: &L10n, items: &ItemList, route: &str) -> String {
+        let shown: ItemListSlice<'_> = ItemQuery::for_route(route).select(items);
+        let (total, _, completed) = items.count();
+
+        let page = PageTemplate {
+            l10n,
+            route,
+            rows: &Template::item_list(l10n, shown.into()),
+            items_left: &Template::item_counter(l10n, total - completed),
+            has_items: total > 0,
+            has_completed: completed > 0,
+            all_completed: completed == total,
+        };
+        page.render().unwrap_or_default()
+    }
+
+    pub fn item_counter(l10n: &L10n, active_todos: usize) -> String {
+        let items_left = ItemsLeftTemplate {
+            items_left: &l10n.plural("items-left", active_todos, &[]),
+        };
         if let Ok(res) = items_left.render() {
             res
         } else {
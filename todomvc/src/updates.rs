@@ -0,0 +1,103 @@
+//! Offline support: registers the service worker built from `sw/` and offers
+//! to reload once it has installed a new version of the app.
+
+use crate::element::{DomError, Element};
+use crate::view::report;
+
+use js_sys::Reflect;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{EventTarget, ServiceWorker, ServiceWorkerRegistration, ServiceWorkerState};
+
+/// Relative to the page, so the worker controls the directory the app is
+/// served from.
+const SCRIPT: &str = "./sw.js";
+
+/// Asks a waiting worker to take over, see `SKIP_WAITING` in `sw/`.
+const SKIP_WAITING: &str = "skip-waiting";
+
+fn listen<T>(target: &EventTarget, event_name: &str, handler: T) -> Result<(), DomError>
+where
+  T: 'static + FnMut(web_sys::Event),
+{
+  let cb = Closure::wrap(Box::new(handler) as Box<dyn FnMut(_)>);
+  target.add_event_listener_with_callback(event_name, cb.as_ref().unchecked_ref())?;
+  cb.forget();
+  Ok(())
+}
+
+/// Registers the worker and binds the `.update` banner. A new version waits
+/// until every page of the old one is closed, unless the user reloads from
+/// the banner: the new worker then takes over and the page reloads once it
+/// controls it.
+pub fn register() -> Result<(), DomError> {
+  let window = web_sys::window().ok_or_else(|| DomError::Missing("window".to_string()))?;
+  let navigator = window.navigator();
+  // Unavailable on insecure origins and outside browsers, e.g. in jsdom.
+  if !Reflect::has(&navigator, &JsValue::from_str("serviceWorker"))? {
+    return Ok(());
+  }
+  let container = navigator.service_worker();
+  let banner = Element::qs(".update")?;
+  let waiting: Rc<RefCell<Option<ServiceWorker>>> = Rc::new(RefCell::new(None));
+  let requested = Rc::new(Cell::new(false));
+
+  let worker = waiting.clone();
+  let reload_requested = requested.clone();
+  Element::qs(".update-reload")?.add_event_listener("click", move |_| {
+    report((|| {
+      if let Some(worker) = worker.borrow().as_ref() {
+        reload_requested.set(true);
+        worker.post_message(&JsValue::from_str(SKIP_WAITING))?;
+      }
+      Ok(())
+    })())
+  })?;
+
+  let location = window.location();
+  listen(&container, "controllerchange", move |_| {
+    // The first install takes over without a reload, so do updates that
+    // another page asked for.
+    if requested.get() {
+      report(location.reload().map_err(DomError::from));
+    }
+  })?;
+
+  let offer = Rc::new(move |worker: ServiceWorker| {
+    *waiting.borrow_mut() = Some(worker);
+    report(banner.set_visibility(true));
+  });
+  let controlled = container.clone();
+  let registered = Closure::wrap(Box::new(move |registration: JsValue| {
+    let registration: ServiceWorkerRegistration = registration.unchecked_into();
+    // Without a controller this is the first install, not an update.
+    if controlled.controller().is_none() {
+      return;
+    }
+    if let Some(worker) = registration.waiting() {
+      offer(worker);
+    }
+    let offer = offer.clone();
+    let installing = registration.clone();
+    report(listen(&registration, "updatefound", move |_| {
+      if let Some(worker) = installing.installing() {
+        let offer = offer.clone();
+        let installed = worker.clone();
+        report(listen(&worker, "statechange", move |_| {
+          if installed.state() == ServiceWorkerState::Installed {
+            offer(installed.clone());
+          }
+        }));
+      }
+    }));
+  }) as Box<dyn FnMut(JsValue)>);
+  let failed = Closure::wrap(Box::new(|err: JsValue| {
+    report(Err(DomError::from(err)));
+  }) as Box<dyn FnMut(JsValue)>);
+  let _ = container.register(SCRIPT).then2(&registered, &failed);
+  registered.forget();
+  failed.forget();
+  Ok(())
+}
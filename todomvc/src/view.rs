@@ -1,9 +1,16 @@
 use crate::controller::ControllerMessage;
-use crate::element::Element;
+use crate::element::{DomError, Element};
 use crate::exit;
-use crate::store::ItemList;
+use crate::l10n::L10n;
+use crate::lists::{self, ListInfo};
+use crate::preferences::{Density, Preferences, Theme};
+use crate::recurrence::{DateTime, Rule};
+use crate::reminders::{Reminder, Reminders};
+use crate::selection::Selection;
+use crate::store::{ItemList, ItemListTrait};
 use crate::{Message, Scheduler};
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::rc::Rc;
 use wasm_bindgen::JsCast;
 
@@ -11,9 +18,16 @@ use crate::template::Template;
 
 const ENTER_KEY: u32 = 13;
 const ESCAPE_KEY: u32 = 27;
+const DARK_SCHEME_QUERY: &str = "(prefers-color-scheme: dark)";
 
 use wasm_bindgen::prelude::*;
 
+/// Receiver of the view messages sent by the controller. `View` renders them
+/// to the DOM, native code can substitute its own implementation.
+pub trait Render {
+  fn call(&mut self, message: ViewMessage);
+}
+
 pub enum ViewMessage {
   UpdateFilterButtons(String),
   ClearNewTodo(),
@@ -25,20 +39,256 @@ pub enum ViewMessage {
   RemoveItem(String),
   EditItemDone(String, String),
   SetItemComplete(String, bool),
+  ApplyPreferences(Preferences),
+  /// All lists and the name of the current one.
+  ShowLists(Vec<ListInfo>, String),
+  /// Sets `location.hash`.
+  Navigate(String),
+  /// Every open item with a due date, replacing earlier reminders.
+  ScheduleReminders(Vec<Reminder>),
 }
-fn item_id(mut element: Element) -> Option<String> {
-  element.parent_element().map(|mut parent| {
-    let mut res = None;
-    let parent_id = parent.dataset_get("id");
-    if parent_id != "" {
-      res = Some(parent_id);
-    } else {
-      if let Some(mut ep) = parent.parent_element() {
-        res = Some(ep.dataset_get("id"));
+
+fn item_id(element: &Element) -> Result<String, DomError> {
+  let parent = element.parent_element()?;
+  let parent_id = parent.dataset_get("id")?;
+  if parent_id != "" {
+    Ok(parent_id)
+  } else {
+    parent.parent_element()?.dataset_get("id")
+  }
+}
+
+/// Makes `row` the only row of `list` reachable with Tab (roving tabindex).
+fn set_roving_row(list: &Element, row: &Element) -> Result<(), DomError> {
+  for other in list.qs_all("li")? {
+    other.set_attribute("tabindex", "-1")?;
+  }
+  row.set_attribute("tabindex", "0")
+}
+
+fn window() -> Result<web_sys::Window, DomError> {
+  web_sys::window().ok_or_else(|| DomError::Missing("window".to_string()))
+}
+
+fn prefers_dark() -> bool {
+  match window().and_then(|w| Ok(w.match_media(DARK_SCHEME_QUERY)?)) {
+    Ok(Some(query)) => query.matches(),
+    _ => false,
+  }
+}
+
+/// Applies the theme palette and density as custom properties on `<html>`.
+fn apply_theme(preferences: &Preferences) -> Result<(), DomError> {
+  let root = Element::document_element()?;
+  let dark = preferences.theme.is_dark(prefers_dark());
+  let properties = Theme::palette(dark)
+    .iter()
+    .chain(preferences.density.properties());
+  for (name, value) in properties {
+    root.set_style_property(name, value)?;
+  }
+  root.set_attribute("data-theme", if dark { "dark" } else { "light" })
+}
+
+/// Asks the user to confirm a deletion when they opted in to it.
+fn confirm_delete(preferences: &RefCell<Preferences>, message: &str) -> Result<bool, DomError> {
+  if !preferences.borrow().confirm_delete {
+    return Ok(true);
+  }
+  Ok(window()?.confirm_with_message(message)?)
+}
+
+/// Asks which list to move an item to, `None` if the answer isn't one of the
+/// lists other than `current`.
+fn prompt_move_target(
+  l10n: &L10n,
+  lists: &[ListInfo],
+  current: &str,
+) -> Result<Option<String>, DomError> {
+  let others: Vec<&str> = lists
+    .iter()
+    .map(|list| list.name.as_str())
+    .filter(|name| *name != current)
+    .collect();
+  if others.is_empty() {
+    return Ok(None);
+  }
+  let message = l10n.format("move-item-prompt", &[("lists", &others.join(", "))]);
+  let answer = window()?.prompt_with_message_and_default(&message, others[0])?;
+  Ok(answer
+    .map(|answer| answer.trim().to_string())
+    .filter(|answer| others.contains(&answer.as_str())))
+}
+
+/// Rows in display order, leaving out the subtasks of collapsed rows.
+fn visible_rows(list: &Element) -> Result<Vec<Element>, DomError> {
+  let mut hidden = Vec::new();
+  for row in list.qs_all(".collapsed li")? {
+    hidden.push(row.dataset_get("id")?);
+  }
+  let mut rows = Vec::new();
+  for row in list.qs_all("li")? {
+    if !hidden.contains(&row.dataset_get("id")?) {
+      rows.push(row);
+    }
+  }
+  Ok(rows)
+}
+
+/// Shows or hides the subtasks of `row`, remembering the choice in
+/// `collapsed` across renders. Rows without subtasks are left alone.
+fn set_collapsed(
+  collapsed: &RefCell<HashSet<String>>,
+  row: &Element,
+  value: bool,
+) -> Result<(), DomError> {
+  if row.attribute("aria-expanded").is_none() {
+    return Ok(());
+  }
+  let id = row.dataset_get("id")?;
+  if value {
+    row.class_list_add("collapsed")?;
+    collapsed.borrow_mut().insert(id);
+  } else {
+    row.class_list_remove("collapsed")?;
+    collapsed.borrow_mut().remove(&id);
+  }
+  row.set_attribute("aria-expanded", if value { "false" } else { "true" })
+}
+
+/// Asks for the title of a new subtask of `row`, `None` when left blank.
+fn prompt_subtask(l10n: &L10n, row: &Element) -> Result<Option<String>, DomError> {
+  let title = row.qs_from("label")?.text_content().unwrap_or_default();
+  let message = l10n.format("add-subtask-prompt", &[("title", &title)]);
+  let answer = window()?.prompt_with_message(&message)?;
+  Ok(answer
+    .map(|answer| answer.trim().to_string())
+    .filter(|answer| !answer.is_empty()))
+}
+
+/// A due date and recurrence rule as entered by the user.
+type Schedule = (Option<DateTime>, Option<Rule>);
+
+/// Asks for the due date and the recurrence of `row`. `None` when cancelled
+/// or when an answer doesn't parse, which is pointed out to the user.
+fn prompt_schedule(
+  l10n: &L10n,
+  row: &Element,
+) -> Result<Option<Schedule>, DomError> {
+  let window = window()?;
+  let title = row.qs_from("label")?.text_content().unwrap_or_default();
+  let message = l10n.format("schedule-due-prompt", &[("title", &title)]);
+  let due = match window.prompt_with_message_and_default(&message, &row.dataset_get("due")?)? {
+    Some(due) => due.trim().to_string(),
+    None => return Ok(None),
+  };
+  let due = if due.is_empty() {
+    None
+  } else {
+    match DateTime::parse(&due) {
+      Some(due) => Some(due),
+      None => {
+        window.alert_with_message(&l10n.format("schedule-invalid", &[("value", &due)]))?;
+        return Ok(None);
       }
     }
-    res.unwrap()
-  })
+  };
+
+  let message = l10n.get("schedule-repeat-prompt");
+  let current = row.dataset_get("repeat")?;
+  let repeat = match window.prompt_with_message_and_default(&message, &current)? {
+    Some(repeat) => repeat.trim().to_string(),
+    None => return Ok(None),
+  };
+  let recurrence = if repeat.is_empty() || repeat.eq_ignore_ascii_case("none") {
+    None
+  } else {
+    match Rule::from_input(&repeat) {
+      Some(rule) => Some(rule),
+      None => {
+        window.alert_with_message(&l10n.format("schedule-invalid", &[("value", &repeat)]))?;
+        return Ok(None);
+      }
+    }
+  };
+  if due.is_some() || recurrence.is_some() {
+    Reminders::request_permission()?;
+  }
+  Ok(Some((due, recurrence)))
+}
+
+/// Asks for the notes of `row`, `None` when cancelled. Blank notes are
+/// removed by the controller.
+fn prompt_notes(l10n: &L10n, row: &Element) -> Result<Option<String>, DomError> {
+  let title = row.qs_from("label")?.text_content().unwrap_or_default();
+  let message = l10n.format("notes-prompt", &[("title", &title)]);
+  let current = row.dataset_get("notes")?;
+  Ok(window()?
+    .prompt_with_message_and_default(&message, &current)?
+    .map(|answer| answer.trim().to_string()))
+}
+
+/// Ids of the rows in display order, see `visible_rows`.
+fn visible_ids(list: &Element) -> Result<Vec<String>, DomError> {
+  visible_rows(list)?
+    .iter()
+    .map(|row| row.dataset_get("id"))
+    .collect()
+}
+
+/// Whether every selected row is completed, when batch completion reopens.
+fn selection_completed(list: &Element, selection: &Selection) -> Result<bool, DomError> {
+  for row in list.qs_all("li")? {
+    if selection.contains(&row.dataset_get("id")?) && !row.has_class("completed") {
+      return Ok(false);
+    }
+  }
+  Ok(!selection.is_empty())
+}
+
+/// Marks the selected rows and shows the batch toolbar while there are any.
+fn show_selection(l10n: &L10n, list: &Element, selection: &Selection) -> Result<(), DomError> {
+  for row in list.qs_all("li")? {
+    if selection.contains(&row.dataset_get("id")?) {
+      row.class_list_add("selected")?;
+    } else {
+      row.class_list_remove("selected")?;
+    }
+  }
+  Element::qs(".batch")?.set_visibility(!selection.is_empty())?;
+  Element::qs(".batch-count")?.set_text_content(&l10n.plural("batch-count", selection.len(), &[]));
+  let complete = if selection_completed(list, selection)? {
+    "batch-reopen"
+  } else {
+    "batch-complete"
+  };
+  Element::qs(".batch-complete")?.set_text_content(&l10n.get(complete));
+  Ok(())
+}
+
+/// Id of the row next to `rows[index]`, which keeps the focus when that row
+/// goes away.
+fn neighbour_id(rows: &[Element], index: usize) -> Result<Option<String>, DomError> {
+  let neighbour = rows.get(index + 1).or_else(|| {
+    if index > 0 {
+      rows.get(index - 1)
+    } else {
+      None
+    }
+  });
+  match neighbour {
+    Some(neighbour) => Ok(Some(neighbour.dataset_get("id")?)),
+    None => Ok(None),
+  }
+}
+
+/// Every DOM error raised by the app ends up here: those of the view, while
+/// handling a `ViewMessage` or inside an event listener, and those of the
+/// reminders and the service worker.
+pub(crate) fn report(result: Result<(), DomError>) {
+  if let Err(err) = result {
+    web_sys::console::error_1(&err.into());
+  }
 }
 
 #[wasm_bindgen]
@@ -50,18 +300,42 @@ pub struct View {
   main: Element,
   toggle_all: Element,
   new_todo: Element,
+  search: Element,
+  announcer: Element,
+  list_switcher: Element,
+  focused: Rc<RefCell<Option<String>>>,
+  /// Ids of the rows whose subtasks are hidden.
+  collapsed: Rc<RefCell<HashSet<String>>>,
+  /// Rows picked for the batch toolbar, kept across renders while shown.
+  selection: Rc<RefCell<Selection>>,
+  lists: Rc<RefCell<Vec<ListInfo>>>,
+  /// Name of the current list.
+  list: Rc<RefCell<String>>,
+  items_left: Option<usize>,
+  l10n: Rc<L10n>,
+  reminders: Reminders,
+  preferences: Rc<RefCell<Preferences>>,
+  /// Set while the page still shows server rendered markup, see `ssr`.
+  hydrating: bool,
   callbacks: Vec<(web_sys::EventTarget, String, Closure<dyn FnMut()>)>,
 }
 
 impl View {
-  pub fn new(sched: Rc<Scheduler>) -> Option<View> {
+  /// Binds the view to the page. A page rendered by `ssr::render_page` is
+  /// hydrated: its markup is kept as long as it matches the store.
+  pub fn new(sched: Rc<Scheduler>, l10n: L10n) -> Result<View, DomError> {
+    let hydrating = Element::qs(".todoapp")?.attribute("data-ssr").is_some();
     let todo_list = Element::qs(".todo-list")?;
     let todo_item_counter = Element::qs(".todo-count")?;
     let clear_completed = Element::qs(".clear-completed")?;
     let main = Element::qs(".main")?;
     let toggle_all = Element::qs(".toggle-all")?;
     let new_todo = Element::qs(".new-todo")?;
-    Some(View {
+    let search = Element::qs(".search")?;
+    let announcer = Element::qs(".announcer")?;
+    let list_switcher = Element::qs(".list-switcher")?;
+    let l10n = Rc::new(l10n);
+    Ok(View {
       sched: RefCell::new(sched),
       todo_list,
       todo_item_counter,
@@ -69,19 +343,31 @@ impl View {
       main,
       toggle_all,
       new_todo,
+      search,
+      announcer,
+      list_switcher,
+      focused: Rc::new(RefCell::new(None)),
+      collapsed: Rc::new(RefCell::new(HashSet::new())),
+      selection: Rc::new(RefCell::new(Selection::new())),
+      lists: Rc::new(RefCell::new(Vec::new())),
+      list: Rc::new(RefCell::new(String::new())),
+      items_left: None,
+      reminders: Reminders::new(l10n.clone()),
+      l10n,
+      preferences: Rc::new(RefCell::new(Preferences::default())),
+      hydrating,
       callbacks: Vec::new(),
     })
   }
 
-  pub fn init(&mut self) {
-    let window = match web_sys::window() {
-      Some(w) => w,
-      None => return,
-    };
-    let document = match window.document() {
-      Some(d) => d,
-      None => return,
-    };
+  pub fn init(&mut self) -> Result<(), DomError> {
+    let window = web_sys::window().ok_or_else(|| DomError::Missing("window".to_string()))?;
+    let document = window
+      .document()
+      .ok_or_else(|| DomError::Missing("document".to_string()))?;
+    if !self.hydrating {
+      self.localize()?;
+    }
     let sched = self.sched.clone();
     let set_page = Closure::wrap(Box::new(move || {
       if let Some(location) = document.location() {
@@ -94,69 +380,164 @@ impl View {
     }) as Box<dyn FnMut()>);
 
     let window_et: web_sys::EventTarget = window.into();
-    window_et
-      .add_event_listener_with_callback("hashchange", set_page.as_ref().unchecked_ref())
-      .unwrap();
+    window_et.add_event_listener_with_callback("hashchange", set_page.as_ref().unchecked_ref())?;
     set_page.forget();
-    self.bind_add_item();
-    self.bind_edit_item_save();
-    self.bind_edit_item_cancel();
-    self.bind_remove_item();
-    self.bind_toggle_item();
-    self.bind_edit_item();
-    self.bind_remove_completed();
-    self.bind_toggle_all();
+    self.bind_add_item()?;
+    self.bind_search()?;
+    self.bind_edit_item_save()?;
+    self.bind_edit_item_cancel()?;
+    self.bind_remove_item()?;
+    self.bind_move_item()?;
+    self.bind_subtasks()?;
+    self.bind_schedule()?;
+    self.bind_notes()?;
+    self.bind_toggle_item()?;
+    self.bind_selection()?;
+    self.bind_edit_item()?;
+    self.bind_remove_completed()?;
+    self.bind_toggle_all()?;
+    self.bind_keyboard_navigation()?;
+    self.bind_settings()?;
+    self.bind_color_scheme()?;
+    self.bind_lists()?;
+    Ok(())
+  }
+
+  /// Translates the static markup of `index.html`. Elements carry their
+  /// message key in a `data-l10n` attribute.
+  fn localize(&self) -> Result<(), DomError> {
+    Element::document_element()?.set_attribute("lang", self.l10n.locale())?;
+    for el in Element::body()?.qs_all("[data-l10n]")? {
+      if let Some(key) = el.attribute("data-l10n") {
+        el.set_text_content(&self.l10n.get(&key));
+      }
+    }
+    self
+      .new_todo
+      .set_attribute("placeholder", &self.l10n.get("new-todo-placeholder"))?;
+    self
+      .new_todo
+      .set_attribute("aria-label", &self.l10n.get("new-todo-label"))?;
+    self
+      .search
+      .set_attribute("placeholder", &self.l10n.get("search-placeholder"))?;
+    self
+      .search
+      .set_attribute("aria-label", &self.l10n.get("search-label"))?;
+    self
+      .todo_list
+      .set_attribute("aria-label", &self.l10n.get("todo-list-label"))?;
+    Element::qs(".batch")?.set_attribute("aria-label", &self.l10n.get("batch-label"))?;
+    Element::qs(".filters")?.set_attribute("aria-label", &self.l10n.get("filters-label"))
   }
 
-  fn bind_edit_item(&mut self) {
+  fn bind_edit_item(&mut self) -> Result<(), DomError> {
+    let l10n = self.l10n.clone();
     self.todo_list.delegate(
       "li label",
       "dblclick",
-      |e: web_sys::Event| {
-        if let Some(target) = e.target() {
-          View::edit_item(target.into());
-        }
+      move |e: web_sys::Event| {
+        report(Element::event_target(&e).and_then(|el| View::edit_item(&l10n, el)))
       },
       false,
-    );
+    )
   }
 
-  fn edit_item(mut el: Element) {
-    if let Some(mut parent_element) = el.parent_element() {
-      if let Some(mut list_item) = parent_element.parent_element() {
-        list_item.class_list_add("editing");
-        if let Some(mut input) = Element::create_element("input") {
-          input.set_class_name("edit");
-          if let Some(text) = el.text_content() {
-            input.set_value(&text);
-          }
-          list_item.append_child(&mut input);
-          input.focus();
-        }
+  fn edit_item(l10n: &L10n, el: Element) -> Result<(), DomError> {
+    let list_item = el.parent_element()?.parent_element()?;
+    list_item.class_list_add("editing")?;
+    let input = Element::create_element("input")?;
+    input.set_class_name("edit");
+    if let Some(text) = el.text_content() {
+      input.set_attribute("aria-label", &l10n.format("edit-item-label", &[("title", &text)]))?;
+      input.set_value(&text)?;
+    }
+    // Above the subtasks, the view it replaces is hidden while editing.
+    list_item.insert_before(&input, list_item.try_qs_from(".subtasks")?.as_ref())?;
+    input.focus()
+  }
+
+  fn show_items(&mut self, items: ItemList) -> Result<(), DomError> {
+    if self.hydrating {
+      self.hydrating = false;
+      if self.is_rendered(&items)? {
+        return self.restore_focus(false);
       }
     }
+    let had_focus = self.todo_list.has_focus_within()?;
+    let search = self.search.value()?;
+    if !search.trim().is_empty() {
+      let results = self.l10n.plural("search-results", items.length(), &[]);
+      self.announcer.set_text_content(&results);
+    }
+    self
+      .todo_list
+      .set_inner_html(Template::search_results(&self.l10n, items, &search));
+    self.apply_collapsed()?;
+    self.update_selection()?;
+    self.restore_focus(had_focus)
   }
 
-  pub fn call(&mut self, method_name: ViewMessage) {
-    use self::ViewMessage::*;
-    match method_name {
-      UpdateFilterButtons(route) => self.update_filter_buttons(&route),
-      ClearNewTodo() => self.clear_new_todo(),
-      ShowItems(item_list) => self.show_items(item_list),
-      SetItemsLeft(count) => self.set_items_left(count),
-      SetClearCompletedButtonVisibility(visible) => {
-        self.set_clear_completed_button_visibility(visible)
+  /// Whether the rows on the page already show exactly `items`.
+  fn is_rendered(&self, items: &ItemList) -> Result<bool, DomError> {
+    let rows = self.todo_list.qs_all("li")?;
+    if rows.len() != items.length() {
+      return Ok(false);
+    }
+    // Subtasks are nested, so rows aren't in the order of `items`.
+    for row in rows.iter() {
+      let id = row.dataset_get("id")?;
+      let item = match items.iter().find(|item| item.id == id) {
+        Some(item) => item,
+        None => return Ok(false),
+      };
+      if row.qs_from("label")?.text_content().as_ref() != Some(&item.title)
+        || row.qs_from(".toggle")?.checked()? != item.completed
+      {
+        return Ok(false);
+      }
+    }
+    Ok(true)
+  }
+
+  fn apply_collapsed(&self) -> Result<(), DomError> {
+    let ids: Vec<String> = self.collapsed.borrow().iter().cloned().collect();
+    for id in ids {
+      if let Some(row) = self.todo_list.try_qs_from(&View::get_selector_string(&id))? {
+        set_collapsed(&self.collapsed, &row, true)?;
       }
-      SetCompleteAllCheckbox(complete) => self.set_complete_all_checkbox(complete),
-      SetMainVisibility(complete) => self.set_main_visibility(complete),
-      RemoveItem(id) => self.remove_item(&id),
-      EditItemDone(id, title) => self.edit_item_done(&id, &title),
-      SetItemComplete(id, completed) => self.set_item_complete(&id, completed),
     }
+    Ok(())
   }
 
-  fn show_items(&mut self, items: ItemList) {
-    self.todo_list.set_inner_html(Template::item_list(items));
+  /// Drops the rows that went away from the selection and marks the others.
+  fn update_selection(&self) -> Result<(), DomError> {
+    let mut shown = Vec::new();
+    for row in self.todo_list.qs_all("li")? {
+      shown.push(row.dataset_get("id")?);
+    }
+    self.selection.borrow_mut().retain(&shown);
+    show_selection(&self.l10n, &self.todo_list, &self.selection.borrow())
+  }
+
+  /// Re-applies the roving tabindex after the list changed, preferring the
+  /// last focused row and falling back to the first one.
+  fn restore_focus(&self, had_focus: bool) -> Result<(), DomError> {
+    let focused = self.focused.borrow().clone();
+    let mut row = None;
+    if let Some(id) = focused {
+      row = self.todo_list.try_qs_from(&View::get_selector_string(&id))?;
+    }
+    if row.is_none() {
+      row = self.todo_list.try_qs_from("li")?;
+    }
+    if let Some(row) = row {
+      set_roving_row(&self.todo_list, &row)?;
+      if had_focus {
+        row.focus()?;
+      }
+    }
+    Ok(())
   }
 
   fn get_selector_string(id: &str) -> String {
@@ -166,189 +547,508 @@ impl View {
     selector
   }
 
-  fn remove_item(&mut self, id: &str) {
-    let elem = Element::qs(&View::get_selector_string(id));
-
-    if let Some(elem) = elem {
-      self.todo_list.remove_child(elem);
+  fn remove_item(&mut self, id: &str) -> Result<(), DomError> {
+    // The row is already gone when the controller re-rendered the list.
+    if let Some(elem) = Element::try_qs(&View::get_selector_string(id))? {
+      let had_focus = elem.has_focus_within()?;
+      self.todo_list.remove_child(elem)?;
+      self.update_selection()?;
+      self.restore_focus(had_focus)?;
     }
+    Ok(())
   }
 
-  fn set_items_left(&mut self, items_left: usize) {
-    self
-      .todo_item_counter
-      .set_inner_html(Template::item_counter(items_left));
+  fn set_items_left(&mut self, items_left: usize) -> Result<(), DomError> {
+    // Only announce real changes, `SetItemsLeft` is sent after every update.
+    if self.items_left != Some(items_left) {
+      // As text, unlike the counter, which is escaped HTML.
+      let announcement = self.l10n.plural("items-left", items_left, &[]);
+      self.announcer.set_text_content(&announcement);
+      self.items_left = Some(items_left);
+    }
+    let counter = Template::item_counter(&self.l10n, items_left);
+    self.todo_item_counter.set_inner_html(counter);
+    Ok(())
   }
 
-  fn set_clear_completed_button_visibility(&mut self, visible: bool) {
-    self.clear_completed.set_visibility(visible);
+  fn set_clear_completed_button_visibility(&mut self, visible: bool) -> Result<(), DomError> {
+    self.clear_completed.set_visibility(visible)
   }
 
-  fn set_main_visibility(&mut self, visible: bool) {
-    self.main.set_visibility(visible);
+  fn set_main_visibility(&mut self, visible: bool) -> Result<(), DomError> {
+    self.main.set_visibility(visible)
   }
 
-  fn set_complete_all_checkbox(&mut self, checked: bool) {
-    self.toggle_all.set_checked(checked);
+  fn set_complete_all_checkbox(&mut self, checked: bool) -> Result<(), DomError> {
+    self.toggle_all.set_checked(checked)
   }
 
-  fn update_filter_buttons(&self, route: &str) {
-    if let Some(mut el) = Element::qs(".filters .selected") {
+  fn update_filter_buttons(&self, route: &str) -> Result<(), DomError> {
+    if let Some(el) = Element::try_qs(".filters .selected")? {
       el.set_class_name("");
     }
 
-    let mut selector = String::from(".filters [href=\"");
+    let mut selector = String::from(".filters [data-filter=\"");
     selector.push_str(route);
     selector.push_str("\"]");
 
-    if let Some(mut el) = Element::qs(&selector) {
+    if let Some(el) = Element::try_qs(&selector)? {
       el.set_class_name("selected");
     }
+    Ok(())
   }
 
-  fn clear_new_todo(&mut self) {
-    self.new_todo.set_value("");
-  }
+  /// Fills the list switcher, with archived lists in a group of their own,
+  /// and points the filter links at `current`.
+  fn show_lists(&mut self, lists: Vec<ListInfo>, current: String) -> Result<(), DomError> {
+    self.list_switcher.set_inner_html(String::new());
+    let archived = Element::create_element("optgroup")?;
+    archived.set_attribute("label", &self.l10n.get("lists-archived"))?;
+    for list in lists.iter() {
+      let option = Element::create_element("option")?;
+      option.set_attribute("value", &list.name)?;
+      option.set_text_content(&list.name);
+      if list.archived {
+        archived.append_child(&option)?;
+      } else {
+        self.list_switcher.append_child(&option)?;
+      }
+    }
+    if lists.iter().any(|list| list.archived) {
+      self.list_switcher.append_child(&archived)?;
+    }
+    self.list_switcher.set_value(&current)?;
 
-  fn set_item_complete(&self, id: &str, completed: bool) {
-    if let Some(mut list_item) = Element::qs(&View::get_selector_string(id)) {
-      let class_name = if completed { "completed" } else { "" };
-      list_item.set_class_name(class_name);
+    let current_archived = lists
+      .iter()
+      .any(|list| list.name == current && list.archived);
+    let archive_label = if current_archived {
+      "list-unarchive"
+    } else {
+      "list-archive"
+    };
+    Element::qs(".archive-list")?.set_text_content(&self.l10n.get(archive_label));
+    Element::qs(".delete-list")?.set_visibility(lists.len() > 1)?;
+    // Items can only be moved when there is another list.
+    let app = Element::qs(".todoapp")?;
+    if lists.len() > 1 {
+      app.class_list_remove("single-list")?;
+    } else {
+      app.class_list_add("single-list")?;
+    }
 
-      if let Some(mut el) = list_item.qs_from("input") {
-        el.set_checked(completed);
-      }
+    for link in Element::qs(".filters")?.qs_all("[data-filter]")? {
+      let filter = link.attribute("data-filter").unwrap_or_default();
+      link.set_attribute("href", &lists::route(&current, &filter))?;
     }
+    *self.lists.borrow_mut() = lists;
+    *self.list.borrow_mut() = current;
+    Ok(())
+  }
+
+  fn navigate(&self, hash: &str) -> Result<(), DomError> {
+    Ok(window()?.location().set_hash(hash)?)
   }
 
-  fn edit_item_done(&self, id: &str, title: &str) {
-    if let Some(mut list_item) = Element::qs(&View::get_selector_string(id)) {
-      if let Some(input) = list_item.qs_from("input.edit") {
-        list_item.class_list_remove("editing");
+  fn apply_preferences(&mut self, preferences: Preferences) -> Result<(), DomError> {
+    apply_theme(&preferences)?;
+    Element::qs(".settings-theme")?.set_value(preferences.theme.as_str())?;
+    Element::qs(".settings-density")?.set_value(preferences.density.as_str())?;
+    Element::qs(".settings-default-filter")?.set_value(&preferences.default_filter)?;
+    Element::qs(".settings-confirm-delete")?.set_checked(preferences.confirm_delete)?;
+    *self.preferences.borrow_mut() = preferences;
+    Ok(())
+  }
 
-        if let Some(mut list_item_label) = list_item.qs_from("label") {
-          list_item_label.set_text_content(title);
-        }
+  fn clear_new_todo(&mut self) -> Result<(), DomError> {
+    self.new_todo.set_value("")
+  }
 
-        list_item.remove_child(input);
+  fn set_item_complete(&self, id: &str, completed: bool) -> Result<(), DomError> {
+    // Filtered views may not render the row at all.
+    if let Some(list_item) = Element::try_qs(&View::get_selector_string(id))? {
+      // Other classes such as `collapsed` stay.
+      if completed {
+        list_item.class_list_add("completed")?;
+      } else {
+        list_item.class_list_remove("completed")?;
       }
+      list_item.qs_from(".toggle")?.set_checked(completed)?;
+    }
+    show_selection(&self.l10n, &self.todo_list, &self.selection.borrow())
+  }
+
+  fn edit_item_done(&self, id: &str, title: &str) -> Result<(), DomError> {
+    let list_item = Element::qs(&View::get_selector_string(id))?;
+    if let Some(input) = list_item.try_qs_from("input.edit")? {
+      list_item.class_list_remove("editing")?;
+      list_item.qs_from("label")?.set_text_content(title);
+      list_item.remove_child(input)?;
     }
+    Ok(())
   }
 
-  fn bind_add_item(&mut self) {
+  fn bind_add_item(&mut self) -> Result<(), DomError> {
     let sched = self.sched.clone();
     let cb = move |event: web_sys::Event| {
-      if let Some(target) = event.target() {
-        if let Some(input_el) = wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlInputElement>(&target)
-        {
-          let v = input_el.value();
-          let title = v.trim();
-          if title != "" {
-            if let Ok(sched) = &(sched.try_borrow_mut()) {
-              sched.add_message(Message::Controller(ControllerMessage::AddItem(
-                String::from(title),
-              )));
-            }
+      report((|| {
+        let v = Element::event_target(&event)?.value()?;
+        let title = v.trim();
+        if title != "" {
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::AddItem(
+              String::from(title),
+            )));
           }
         }
-      }
+        Ok(())
+      })())
+    };
+    self.new_todo.add_event_listener("change", cb)
+  }
+
+  /// Searches as the user types.
+  fn bind_search(&mut self) -> Result<(), DomError> {
+    let sched = self.sched.clone();
+    let cb = move |event: web_sys::Event| {
+      report((|| {
+        let search = Element::event_target(&event)?.value()?;
+        if let Ok(sched) = &(sched.try_borrow_mut()) {
+          sched.add_message(Message::Controller(ControllerMessage::Search(search)));
+        }
+        Ok(())
+      })())
     };
-    self.new_todo.add_event_listener("change", cb);
+    self.search.add_event_listener("input", cb)
   }
 
-  fn bind_remove_completed(&mut self) {
+  fn bind_remove_completed(&mut self) -> Result<(), DomError> {
     let sched = self.sched.clone();
+    let preferences = self.preferences.clone();
+    let l10n = self.l10n.clone();
     let handler = move |_| {
-      if let Ok(sched) = &(sched.try_borrow_mut()) {
-        sched.add_message(Message::Controller(ControllerMessage::RemoveCompleted()));
-      }
+      report((|| {
+        if confirm_delete(&preferences, &l10n.get("clear-completed-confirm"))? {
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::RemoveCompleted()));
+          }
+        }
+        Ok(())
+      })())
     };
-    self.clear_completed.add_event_listener("click", handler);
+    self.clear_completed.add_event_listener("click", handler)
   }
 
-  fn bind_toggle_all(&mut self) {
+  fn bind_toggle_all(&mut self) -> Result<(), DomError> {
     let sched = self.sched.clone();
     self
       .toggle_all
       .add_event_listener("click", move |event: web_sys::Event| {
-        if let Some(target) = event.target() {
-          if let Some(input_el) =
-            wasm_bindgen::JsCast::dyn_ref::<web_sys::HtmlInputElement>(&target)
-          {
+        report((|| {
+          let checked = Element::event_target(&event)?.checked()?;
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::ToggleAll(checked)));
+          }
+          Ok(())
+        })())
+      })
+  }
+
+  fn bind_remove_item(&mut self) -> Result<(), DomError> {
+    let sched = self.sched.clone();
+    let preferences = self.preferences.clone();
+    let l10n = self.l10n.clone();
+    self.todo_list.delegate(
+      ".destroy",
+      "click",
+      move |e: web_sys::Event| {
+        report((|| {
+          let button = Element::event_target(&e)?;
+          let title = button
+            .parent_element()?
+            .qs_from("label")?
+            .text_content()
+            .unwrap_or_default();
+          let message = l10n.format("delete-confirm", &[("title", &title)]);
+          if !confirm_delete(&preferences, &message)? {
+            return Ok(());
+          }
+          let item_id = item_id(&button)?;
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::RemoveItem(item_id)));
+          }
+          Ok(())
+        })())
+      },
+      false,
+    )
+  }
+
+  fn bind_move_item(&mut self) -> Result<(), DomError> {
+    let sched = self.sched.clone();
+    let l10n = self.l10n.clone();
+    let lists = self.lists.clone();
+    let list = self.list.clone();
+    self.todo_list.delegate(
+      ".move",
+      "click",
+      move |e: web_sys::Event| {
+        report((|| {
+          let button = Element::event_target(&e)?;
+          let target = prompt_move_target(&l10n, &lists.borrow(), &list.borrow())?;
+          if let Some(target) = target {
+            let item_id = item_id(&button)?;
             if let Ok(sched) = &(sched.try_borrow_mut()) {
-              sched.add_message(Message::Controller(ControllerMessage::ToggleAll(
-                input_el.checked(),
+              sched.add_message(Message::Controller(ControllerMessage::MoveItem(
+                item_id, target,
               )));
             }
           }
-        }
-      });
+          Ok(())
+        })())
+      },
+      false,
+    )
   }
 
-  fn bind_remove_item(&mut self) {
+  fn bind_subtasks(&mut self) -> Result<(), DomError> {
     let sched = self.sched.clone();
+    let l10n = self.l10n.clone();
     self.todo_list.delegate(
-      ".destroy",
+      ".add-subtask",
       "click",
       move |e: web_sys::Event| {
-        if let Some(target) = e.target() {
-          let el: Element = target.into();
-          if let Some(item_id) = item_id(el) {
+        report((|| {
+          let button = Element::event_target(&e)?;
+          let row = button.parent_element()?.parent_element()?;
+          if let Some(title) = prompt_subtask(&l10n, &row)? {
+            let item_id = item_id(&button)?;
             if let Ok(sched) = &(sched.try_borrow_mut()) {
-              sched.add_message(Message::Controller(ControllerMessage::RemoveItem(item_id)));
+              sched.add_message(Message::Controller(ControllerMessage::AddSubtask(
+                item_id, title,
+              )));
             }
           }
-        }
+          Ok(())
+        })())
+      },
+      false,
+    )?;
+
+    let collapsed = self.collapsed.clone();
+    self.todo_list.delegate(
+      ".collapse",
+      "click",
+      move |e: web_sys::Event| {
+        report((|| {
+          let row = Element::event_target(&e)?.parent_element()?.parent_element()?;
+          let expanded = row.attribute("aria-expanded") == Some("true".to_string());
+          set_collapsed(&collapsed, &row, expanded)
+        })())
       },
       false,
-    );
+    )
   }
 
-  fn bind_toggle_item(&mut self) {
+  fn bind_schedule(&mut self) -> Result<(), DomError> {
     let sched = self.sched.clone();
+    let l10n = self.l10n.clone();
     self.todo_list.delegate(
-      ".toggle",
+      ".schedule",
       "click",
       move |e: web_sys::Event| {
-        if let Some(target) = e.target() {
-          let mut el: Element = target.into();
-          let checked = el.checked();
-          if let Some(item_id) = item_id(el) {
+        report((|| {
+          let button = Element::event_target(&e)?;
+          let row = button.parent_element()?.parent_element()?;
+          if let Some((due, recurrence)) = prompt_schedule(&l10n, &row)? {
+            let item_id = item_id(&button)?;
             if let Ok(sched) = &(sched.try_borrow_mut()) {
-              sched.add_message(Message::Controller(ControllerMessage::ToggleItem(
-                item_id, checked,
+              sched.add_message(Message::Controller(ControllerMessage::SetSchedule(
+                item_id, due, recurrence,
               )));
             }
           }
+          Ok(())
+        })())
+      },
+      false,
+    )
+  }
+
+  fn bind_notes(&mut self) -> Result<(), DomError> {
+    let sched = self.sched.clone();
+    let l10n = self.l10n.clone();
+    self.todo_list.delegate(
+      ".edit-notes",
+      "click",
+      move |e: web_sys::Event| {
+        report((|| {
+          let button = Element::event_target(&e)?;
+          let row = button.parent_element()?.parent_element()?;
+          if let Some(notes) = prompt_notes(&l10n, &row)? {
+            let item_id = item_id(&button)?;
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+              sched.add_message(Message::Controller(ControllerMessage::SetNotes(item_id, notes)));
+            }
+          }
+          Ok(())
+        })())
+      },
+      false,
+    )
+  }
+
+  fn bind_toggle_item(&mut self) -> Result<(), DomError> {
+    let sched = self.sched.clone();
+    self.todo_list.delegate(
+      ".toggle",
+      "click",
+      move |e: web_sys::Event| {
+        report((|| {
+          let el = Element::event_target(&e)?;
+          let checked = el.checked()?;
+          let item_id = item_id(&el)?;
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::ToggleItem(
+              item_id, checked,
+            )));
+          }
+          Ok(())
+        })())
+      },
+      false,
+    )
+  }
+
+  /// Ctrl- or Cmd-click picks single rows, Shift-click the rows from the
+  /// last picked one. The toolbar acts on the picked rows.
+  fn bind_selection(&mut self) -> Result<(), DomError> {
+    let l10n = self.l10n.clone();
+    let selection = self.selection.clone();
+    let todo_list = self.todo_list.clone();
+    self.todo_list.delegate(
+      "li label, li label mark",
+      "click",
+      move |e: web_sys::Event| {
+        let mouse_e = match wasm_bindgen::JsCast::dyn_ref::<web_sys::MouseEvent>(&e) {
+          Some(mouse_e) => mouse_e,
+          None => return,
+        };
+        if !(mouse_e.shift_key() || mouse_e.ctrl_key() || mouse_e.meta_key()) {
+          return;
         }
+        e.prevent_default();
+        report((|| {
+          let id = Element::event_target(&e)?.closest("li")?.dataset_get("id")?;
+          if mouse_e.shift_key() {
+            let shown = visible_ids(&todo_list)?;
+            selection.borrow_mut().extend(&id, &shown);
+          } else {
+            selection.borrow_mut().toggle(&id);
+          }
+          show_selection(&l10n, &todo_list, &selection.borrow())
+        })())
       },
       false,
-    );
+    )?;
+
+    let sched = self.sched.clone();
+    let selection = self.selection.clone();
+    let todo_list = self.todo_list.clone();
+    Element::qs(".batch-complete")?.add_event_listener("click", move |_| {
+      report((|| {
+        let completed = !selection_completed(&todo_list, &selection.borrow())?;
+        let ids = selection.borrow().ids().to_vec();
+        if let Ok(sched) = &(sched.try_borrow_mut()) {
+          sched.add_message(Message::Controller(ControllerMessage::CompleteItems(
+            ids, completed,
+          )));
+        }
+        Ok(())
+      })())
+    })?;
+
+    let sched = self.sched.clone();
+    let selection = self.selection.clone();
+    let l10n = self.l10n.clone();
+    let lists = self.lists.clone();
+    let list = self.list.clone();
+    Element::qs(".batch-move")?.add_event_listener("click", move |_| {
+      report((|| {
+        if let Some(target) = prompt_move_target(&l10n, &lists.borrow(), &list.borrow())? {
+          let ids = selection.borrow().ids().to_vec();
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::MoveItems(ids, target)));
+          }
+        }
+        Ok(())
+      })())
+    })?;
+
+    let sched = self.sched.clone();
+    let selection = self.selection.clone();
+    let l10n = self.l10n.clone();
+    Element::qs(".batch-tag")?.add_event_listener("click", move |_| {
+      report((|| {
+        if let Some(tag) = window()?.prompt_with_message(&l10n.get("batch-tag-prompt"))? {
+          let ids = selection.borrow().ids().to_vec();
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::TagItems(ids, tag)));
+          }
+        }
+        Ok(())
+      })())
+    })?;
+
+    let sched = self.sched.clone();
+    let selection = self.selection.clone();
+    let preferences = self.preferences.clone();
+    let l10n = self.l10n.clone();
+    Element::qs(".batch-delete")?.add_event_listener("click", move |_| {
+      report((|| {
+        let ids = selection.borrow().ids().to_vec();
+        let message = l10n.plural("batch-delete-confirm", ids.len(), &[]);
+        if confirm_delete(&preferences, &message)? {
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::RemoveItems(ids)));
+          }
+        }
+        Ok(())
+      })())
+    })?;
+
+    let selection = self.selection.clone();
+    let l10n = self.l10n.clone();
+    let todo_list = self.todo_list.clone();
+    Element::qs(".batch-clear")?.add_event_listener("click", move |_| {
+      selection.borrow_mut().clear();
+      report(show_selection(&l10n, &todo_list, &selection.borrow()));
+    })
   }
 
-  fn bind_edit_item_save(&mut self) {
+  fn bind_edit_item_save(&mut self) -> Result<(), DomError> {
     let sched = self.sched.clone();
 
     self.todo_list.delegate(
       "li .edit",
       "blur",
       move |e: web_sys::Event| {
-        if let Some(target) = e.target() {
-          let mut target_el: Element = target.into();
-          if target_el.dataset_get("iscancelled") != "true" {
-            let val = target_el.value();
-            if let Some(item) = item_id(target_el) {
-              if let Ok(sched) = &(sched.try_borrow_mut()) {
-                sched.add_message(Message::Controller(ControllerMessage::EditItemSave(
-                  item, val,
-                )));
-              }
+        report((|| {
+          let target_el = Element::event_target(&e)?;
+          if target_el.dataset_get("iscanceled")? != "true" {
+            let val = target_el.value()?;
+            let item = item_id(&target_el)?;
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+              sched.add_message(Message::Controller(ControllerMessage::EditItemSave(
+                item, val,
+              )));
             }
           }
-        }
+          Ok(())
+        })())
       },
       true,
-    );
+    )?;
 
     self.todo_list.delegate(
       "li .edit",
@@ -356,18 +1056,21 @@ impl View {
       |e: web_sys::Event| {
         if let Some(key_e) = wasm_bindgen::JsCast::dyn_ref::<web_sys::KeyboardEvent>(&e) {
           if key_e.key_code() == ENTER_KEY {
-            if let Some(target) = e.target() {
-              let mut el: Element = target.into();
-              el.blur();
-            }
+            report((|| {
+              let el = Element::event_target(&e)?;
+              // Saving on blur removes the input from the row.
+              let row = el.parent_element()?;
+              el.blur()?;
+              row.focus()
+            })())
           }
         }
       },
       false,
-    );
+    )
   }
 
-  fn bind_edit_item_cancel(&mut self) {
+  fn bind_edit_item_cancel(&mut self) -> Result<(), DomError> {
     let sched = self.sched.clone();
     self.todo_list.delegate(
       "li .edit",
@@ -375,23 +1078,315 @@ impl View {
       move |e: web_sys::Event| {
         if let Some(key_e) = wasm_bindgen::JsCast::dyn_ref::<web_sys::KeyboardEvent>(&e) {
           if key_e.key_code() == ESCAPE_KEY {
-            if let Some(target) = e.target() {
-              let mut el: Element = target.into();
-              el.dataset_set("iscanceled", "true");
-              el.blur();
-              if let Some(item_id) = item_id(el) {
-                if let Ok(sched) = &(sched.try_borrow_mut()) {
-                  sched.add_message(Message::Controller(ControllerMessage::EditItemCancel(
-                    item_id,
-                  )));
+            report((|| {
+              let el = Element::event_target(&e)?;
+              el.dataset_set("iscanceled", "true")?;
+              let item_id = item_id(&el)?;
+              let row = el.parent_element()?;
+              el.blur()?;
+              row.focus()?;
+              if let Ok(sched) = &(sched.try_borrow_mut()) {
+                sched.add_message(Message::Controller(ControllerMessage::EditItemCancel(
+                  item_id,
+                )));
+              }
+              Ok(())
+            })())
+          }
+        }
+      },
+      false,
+    )
+  }
+
+  fn bind_keyboard_navigation(&mut self) -> Result<(), DomError> {
+    let focused = self.focused.clone();
+    let todo_list = self.todo_list.clone();
+    self.todo_list.delegate(
+      "li",
+      "focus",
+      move |e: web_sys::Event| {
+        report((|| {
+          let row = Element::event_target(&e)?;
+          *focused.borrow_mut() = Some(row.dataset_get("id")?);
+          set_roving_row(&todo_list, &row)
+        })())
+      },
+      true,
+    )?;
+
+    let sched = self.sched.clone();
+    let focused = self.focused.clone();
+    let l10n = self.l10n.clone();
+    let preferences = self.preferences.clone();
+    let lists = self.lists.clone();
+    let current_list = self.list.clone();
+    let collapsed = self.collapsed.clone();
+    let selection = self.selection.clone();
+    let todo_list = self.todo_list.clone();
+    self.todo_list.delegate(
+      "li",
+      "keydown",
+      move |e: web_sys::Event| {
+        let key_e = match wasm_bindgen::JsCast::dyn_ref::<web_sys::KeyboardEvent>(&e) {
+          Some(key_e) => key_e,
+          None => return,
+        };
+        report((|| {
+          let row = Element::event_target(&e)?;
+          let list = &todo_list;
+          let rows = visible_rows(list)?;
+          let id = row.dataset_get("id")?;
+          let index = rows
+            .iter()
+            .position(|r| r.dataset_get("id").ok().as_ref() == Some(&id))
+            .unwrap_or(0);
+
+          let message = match key_e.key().as_str() {
+            "ArrowDown" | "Down" => {
+              View::focus_row(list, rows.get(index + 1))?;
+              None
+            }
+            "ArrowUp" | "Up" => {
+              View::focus_row(list, rows.get(index.saturating_sub(1)))?;
+              None
+            }
+            "Home" => {
+              View::focus_row(list, rows.first())?;
+              None
+            }
+            "End" => {
+              View::focus_row(list, rows.last())?;
+              None
+            }
+            "ArrowRight" | "Right" if key_e.alt_key() => Some(ControllerMessage::Indent(id)),
+            "ArrowLeft" | "Left" if key_e.alt_key() => Some(ControllerMessage::Outdent(id)),
+            "ArrowRight" | "Right" => {
+              set_collapsed(&collapsed, &row, false)?;
+              None
+            }
+            "ArrowLeft" | "Left" => {
+              set_collapsed(&collapsed, &row, true)?;
+              None
+            }
+            "+" => match prompt_subtask(&l10n, &row)? {
+              Some(title) => Some(ControllerMessage::AddSubtask(id, title)),
+              None => return Ok(()),
+            },
+            "Enter" | "F2" => {
+              View::edit_item(&l10n, row.qs_from("label")?)?;
+              None
+            }
+            " " | "Spacebar" => {
+              let checked = row.qs_from(".toggle")?.checked()?;
+              Some(ControllerMessage::ToggleItem(id, !checked))
+            }
+            "Delete" | "Del" => {
+              e.prevent_default();
+              let title = row.qs_from("label")?.text_content().unwrap_or_default();
+              let message = l10n.format("delete-confirm", &[("title", &title)]);
+              if !confirm_delete(&preferences, &message)? {
+                return Ok(());
+              }
+              // Keep focus in the list by moving it to a neighbouring row.
+              *focused.borrow_mut() = neighbour_id(&rows, index)?;
+              Some(ControllerMessage::RemoveItem(id))
+            }
+            "x" | "X" => {
+              selection.borrow_mut().toggle(&id);
+              show_selection(&l10n, list, &selection.borrow())?;
+              None
+            }
+            "Escape" | "Esc" if !selection.borrow().is_empty() => {
+              selection.borrow_mut().clear();
+              show_selection(&l10n, list, &selection.borrow())?;
+              None
+            }
+            "r" | "R" => match prompt_schedule(&l10n, &row)? {
+              Some((due, recurrence)) => Some(ControllerMessage::SetSchedule(id, due, recurrence)),
+              None => return Ok(()),
+            },
+            "n" | "N" => match prompt_notes(&l10n, &row)? {
+              Some(notes) => Some(ControllerMessage::SetNotes(id, notes)),
+              None => return Ok(()),
+            },
+            "m" | "M" => {
+              e.prevent_default();
+              match prompt_move_target(&l10n, &lists.borrow(), &current_list.borrow())? {
+                Some(target) => {
+                  *focused.borrow_mut() = neighbour_id(&rows, index)?;
+                  Some(ControllerMessage::MoveItem(id, target))
                 }
+                None => return Ok(()),
               }
             }
+            _ => return Ok(()),
+          };
+          e.prevent_default();
+
+          if let Some(message) = message {
+            if let Ok(sched) = &(sched.try_borrow_mut()) {
+              sched.add_message(Message::Controller(message));
+            }
           }
-        }
+          Ok(())
+        })())
       },
       false,
-    );
+    )
+  }
+
+  fn bind_settings(&mut self) -> Result<(), DomError> {
+    let sched = self.sched.clone();
+    Element::qs(".settings")?.add_event_listener("change", move |_| {
+      report((|| {
+        let preferences = Preferences {
+          theme: Theme::parse(&Element::qs(".settings-theme")?.value()?),
+          density: Density::parse(&Element::qs(".settings-density")?.value()?),
+          default_filter: Element::qs(".settings-default-filter")?.value()?,
+          confirm_delete: Element::qs(".settings-confirm-delete")?.checked()?,
+        };
+        if let Ok(sched) = &(sched.try_borrow_mut()) {
+          sched.add_message(Message::Controller(ControllerMessage::SetPreferences(
+            preferences,
+          )));
+        }
+        Ok(())
+      })())
+    })
+  }
+
+  /// The list switcher navigates to the chosen list, the buttons next to it
+  /// act on the current list.
+  fn bind_lists(&mut self) -> Result<(), DomError> {
+    self
+      .list_switcher
+      .add_event_listener("change", move |event: web_sys::Event| {
+        report((|| {
+          let list = Element::event_target(&event)?.value()?;
+          let filter = match Element::try_qs(".filters .selected")? {
+            Some(link) => link.attribute("data-filter").unwrap_or_default(),
+            None => String::new(),
+          };
+          Ok(window()?.location().set_hash(&lists::route(&list, &filter))?)
+        })())
+      })?;
+
+    let sched = self.sched.clone();
+    let l10n = self.l10n.clone();
+    Element::qs(".new-list")?.add_event_listener("click", move |_| {
+      report((|| {
+        if let Some(name) = window()?.prompt_with_message(&l10n.get("list-name-prompt"))? {
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::CreateList(name)));
+          }
+        }
+        Ok(())
+      })())
+    })?;
+
+    let sched = self.sched.clone();
+    let l10n = self.l10n.clone();
+    let list = self.list.clone();
+    Element::qs(".rename-list")?.add_event_listener("click", move |_| {
+      report((|| {
+        let current = list.borrow().clone();
+        let message = l10n.format("list-rename-prompt", &[("name", &current)]);
+        if let Some(name) = window()?.prompt_with_message_and_default(&message, &current)? {
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::RenameList(
+              current, name,
+            )));
+          }
+        }
+        Ok(())
+      })())
+    })?;
+
+    let sched = self.sched.clone();
+    let lists = self.lists.clone();
+    let list = self.list.clone();
+    Element::qs(".archive-list")?.add_event_listener("click", move |_| {
+      let current = list.borrow().clone();
+      let archived = lists
+        .borrow()
+        .iter()
+        .any(|list| list.name == current && list.archived);
+      if let Ok(sched) = &(sched.try_borrow_mut()) {
+        sched.add_message(Message::Controller(ControllerMessage::ArchiveList(
+          current, !archived,
+        )));
+      }
+    })?;
+
+    let sched = self.sched.clone();
+    let l10n = self.l10n.clone();
+    let list = self.list.clone();
+    Element::qs(".delete-list")?.add_event_listener("click", move |_| {
+      report((|| {
+        let current = list.borrow().clone();
+        // Always confirmed, it takes every item of the list with it.
+        let message = l10n.format("list-delete-confirm", &[("name", &current)]);
+        if window()?.confirm_with_message(&message)? {
+          if let Ok(sched) = &(sched.try_borrow_mut()) {
+            sched.add_message(Message::Controller(ControllerMessage::DeleteList(current)));
+          }
+        }
+        Ok(())
+      })())
+    })
+  }
+
+  /// Re-applies the theme when the system color scheme changes, which only
+  /// matters while the theme follows the system.
+  fn bind_color_scheme(&mut self) -> Result<(), DomError> {
+    let query = match window()?.match_media(DARK_SCHEME_QUERY)? {
+      Some(query) => query,
+      None => return Ok(()),
+    };
+    let preferences = self.preferences.clone();
+    let cb = Closure::wrap(Box::new(move |_: web_sys::Event| {
+      let preferences = preferences.borrow();
+      if preferences.theme == Theme::System {
+        report(apply_theme(&preferences));
+      }
+    }) as Box<dyn FnMut(_)>);
+    query.add_event_listener_with_callback("change", cb.as_ref().unchecked_ref())?;
+    cb.forget();
+    Ok(())
+  }
+
+  fn focus_row(list: &Element, row: Option<&Element>) -> Result<(), DomError> {
+    if let Some(row) = row {
+      set_roving_row(list, row)?;
+      row.focus()?;
+    }
+    Ok(())
+  }
+}
+
+impl Render for View {
+  fn call(&mut self, method_name: ViewMessage) {
+    use self::ViewMessage::*;
+    let result = match method_name {
+      UpdateFilterButtons(route) => self.update_filter_buttons(&route),
+      ClearNewTodo() => self.clear_new_todo(),
+      ShowItems(item_list) => self.show_items(item_list),
+      SetItemsLeft(count) => self.set_items_left(count),
+      SetClearCompletedButtonVisibility(visible) => {
+        self.set_clear_completed_button_visibility(visible)
+      }
+      SetCompleteAllCheckbox(complete) => self.set_complete_all_checkbox(complete),
+      SetMainVisibility(complete) => self.set_main_visibility(complete),
+      RemoveItem(id) => self.remove_item(&id),
+      EditItemDone(id, title) => self.edit_item_done(&id, &title),
+      SetItemComplete(id, completed) => self.set_item_complete(&id, completed),
+      ApplyPreferences(preferences) => self.apply_preferences(preferences),
+      ShowLists(lists, current) => self.show_lists(lists, current),
+      Navigate(hash) => self.navigate(&hash),
+      ScheduleReminders(reminders) => self.reminders.schedule(reminders),
+    };
+    report(result);
   }
 }
 
@@ -0,0 +1,41 @@
+// Service worker of the app, see sw/src/lib.rs. Browsers only dispatch to
+// handlers added while this script first runs, so they are added here and
+// wait for the wasm module.
+//
+// Browsers look for an update by comparing this file byte for byte: bump
+// CACHE_VERSION with every release so the new version gets installed and
+// the caches of the old one deleted.
+const CACHE_VERSION = '1';
+const WASM = './sw/pkg/todomvc_sw_bg.wasm';
+
+importScripts('./sw/pkg/todomvc_sw.js');
+
+// Offline the module has to come from this version's cache, named like
+// cache_name in sw/src/cache.rs.
+const ready = caches
+  .open('todomvc-' + CACHE_VERSION)
+  .then((cache) => cache.match(WASM))
+  .then((cached) => wasm_bindgen(cached || WASM));
+
+self.addEventListener('install', (event) => {
+  event.waitUntil(ready.then(() => wasm_bindgen.install(CACHE_VERSION)));
+});
+
+self.addEventListener('activate', (event) => {
+  event.waitUntil(ready.then(() => wasm_bindgen.activate(CACHE_VERSION)));
+});
+
+self.addEventListener('fetch', (event) => {
+  event.respondWith(ready.then(() => wasm_bindgen.respond(CACHE_VERSION, event.request)));
+});
+
+self.addEventListener('message', (event) => {
+  event.waitUntil(ready.then(() => wasm_bindgen.message(event.data)));
+});
+
+// Reminders shown by the app carry the route of their list, see
+// src/reminders.rs.
+self.addEventListener('notificationclick', (event) => {
+  event.notification.close();
+  event.waitUntil(ready.then(() => wasm_bindgen.open_list(event.notification.data)));
+});
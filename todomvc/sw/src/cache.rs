@@ -0,0 +1,48 @@
+//! Which caches and requests the worker deals with, apart from the browser
+//! APIs so it can be tested natively.
+
+/// Caches of the app are named `todomvc-<version>`, see `cache_name`.
+pub const CACHE_PREFIX: &str = "todomvc-";
+
+/// What the app needs to start offline, relative to the worker script. The
+/// worker's own wasm is cached too: once offline, the browser only keeps the
+/// scripts it imports.
+pub const PRECACHE: &[&str] = &[
+  "./",
+  "./index.html",
+  "./index.css",
+  "./manifest.webmanifest",
+  "./icon.svg",
+  "./pkg/todomvc.js",
+  "./pkg/todomvc_bg.wasm",
+  "./sw/pkg/todomvc_sw_bg.wasm",
+];
+
+/// Served for navigations that fail while offline.
+pub const OFFLINE_PAGE: &str = "./index.html";
+
+/// Message the page posts to a waiting worker to activate it right away.
+pub const SKIP_WAITING: &str = "skip-waiting";
+
+pub fn cache_name(version: &str) -> String {
+  format!("{}{}", CACHE_PREFIX, version)
+}
+
+/// The caches of other versions of the app among `names`. Caches of other
+/// apps on the same origin are left alone.
+pub fn stale_caches(names: &[String], version: &str) -> Vec<String> {
+  let current = cache_name(version);
+  names
+    .iter()
+    .filter(|name| name.starts_with(CACHE_PREFIX) && **name != current)
+    .cloned()
+    .collect()
+}
+
+/// Only reads from the app's own origin are cached, anything else goes to
+/// the network untouched.
+pub fn is_cacheable(method: &str, url: &str, origin: &str) -> bool {
+  method.eq_ignore_ascii_case("GET")
+    && url.starts_with(origin)
+    && url[origin.len()..].starts_with('/')
+}
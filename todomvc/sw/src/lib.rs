@@ -0,0 +1,139 @@
+//! Service worker of the installable todomvc app.
+//!
+//! Browsers only honour event handlers added while the worker script first
+//! runs, before this module is instantiated, so `sw.js` adds them and calls
+//! the functions exported here. It also passes the cache version: bumping it
+//! there changes the script, which is how browsers notice an update.
+//!
+//! Responses are served cache-first. A new version precaches the app while
+//! the old one keeps serving, and drops the old caches once it activates.
+
+use futures::future::{self, Either};
+use futures::Future;
+use js_sys::{Array, Promise};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+use web_sys::{Cache, ClientQueryOptions, ClientType, Request, RequestMode, Response};
+use web_sys::{ServiceWorkerGlobalScope, WindowClient};
+
+pub mod cache;
+
+fn scope() -> ServiceWorkerGlobalScope {
+  js_sys::global().unchecked_into()
+}
+
+fn open_cache(version: &str) -> impl Future<Item = Cache, Error = JsValue> {
+  let name = cache::cache_name(version);
+  future::result(scope().caches())
+    .and_then(move |caches| JsFuture::from(caches.open(&name)))
+    .map(|cache| cache.unchecked_into())
+}
+
+/// Precaches the app, the worker is only installed once all of it is.
+#[wasm_bindgen]
+pub fn install(version: String) -> Promise {
+  let urls: Array = cache::PRECACHE
+    .iter()
+    .map(|url| JsValue::from_str(url))
+    .collect();
+  let future = open_cache(&version)
+    .and_then(move |cache| JsFuture::from(cache.add_all_with_str_sequence(&urls)));
+  future_to_promise(future)
+}
+
+/// Deletes the caches of earlier versions and takes over the open pages.
+#[wasm_bindgen]
+pub fn activate(version: String) -> Promise {
+  let future = future::result(scope().caches())
+    .and_then(|caches| {
+      JsFuture::from(caches.keys()).and_then(move |keys| {
+        let keys = Array::from(&keys);
+        let names: Vec<String> = (0..keys.length())
+          .filter_map(|i| keys.get(i).as_string())
+          .collect();
+        let deleted: Vec<JsFuture> = cache::stale_caches(&names, &version)
+          .iter()
+          .map(|name| JsFuture::from(caches.delete(name)))
+          .collect();
+        future::join_all(deleted)
+      })
+    })
+    .and_then(|_| JsFuture::from(scope().clients().claim()));
+  future_to_promise(future)
+}
+
+/// Answers `request` from the cache, or from the network, caching what it
+/// returns. Navigations fall back to the app's page while offline.
+#[wasm_bindgen]
+pub fn respond(version: String, request: Request) -> Promise {
+  let scope = scope();
+  let origin = scope.location().origin();
+  if !cache::is_cacheable(&request.method(), &request.url(), &origin) {
+    return scope.fetch_with_request(&request);
+  }
+  let future = open_cache(&version).and_then(move |cache| {
+    JsFuture::from(cache.match_with_request(&request)).and_then(move |cached| {
+      if cached.is_instance_of::<Response>() {
+        Either::A(future::ok(cached))
+      } else {
+        Either::B(from_network(cache, request))
+      }
+    })
+  });
+  future_to_promise(future)
+}
+
+fn from_network(cache: Cache, request: Request) -> impl Future<Item = JsValue, Error = JsValue> {
+  let navigation = request.mode() == RequestMode::Navigate;
+  let fallback = cache.clone();
+  JsFuture::from(scope().fetch_with_request(&request))
+    .and_then(move |response| {
+      let response: Response = response.unchecked_into();
+      if response.ok() {
+        // Caching is best effort, the response is returned either way.
+        let _ = cache.put_with_request(&request, &response.clone()?);
+      }
+      Ok(response.into())
+    })
+    .or_else(move |err| {
+      if navigation {
+        Either::A(JsFuture::from(fallback.match_with_str(cache::OFFLINE_PAGE)))
+      } else {
+        Either::B(future::err(err))
+      }
+    })
+}
+
+/// Handles the messages of the app's pages, see `cache::SKIP_WAITING`.
+#[wasm_bindgen]
+pub fn message(data: JsValue) -> Result<(), JsValue> {
+  if data.as_string() == Some(cache::SKIP_WAITING.to_string()) {
+    // Activation is reported to the pages through `controllerchange`.
+    let _ = scope().skip_waiting()?;
+  }
+  Ok(())
+}
+
+/// Opens the list of a clicked reminder, whose notification carries its
+/// route, see `reminders` in the app: in an open page of the app if there is
+/// one, or else in a new one.
+#[wasm_bindgen]
+pub fn open_list(hash: String) -> Promise {
+  let clients = scope().clients();
+  let mut options = ClientQueryOptions::new();
+  options.type_(ClientType::Window);
+  // Relative to the worker, which is served from the app's directory.
+  let url = format!("./{}", hash);
+  let future = JsFuture::from(clients.match_all_with_options(&options)).and_then(move |pages| {
+    match Array::from(&pages).get(0).dyn_into::<WindowClient>() {
+      Ok(page) => {
+        // Only allowed while handling the click, so before navigating.
+        let _ = page.focus()?;
+        Ok(Either::A(JsFuture::from(page.navigate(&url)?)))
+      }
+      Err(_) => Ok(Either::B(JsFuture::from(clients.open_window(&url)))),
+    }
+  });
+  future_to_promise(future.and_then(|opened| opened))
+}
@@ -0,0 +1,34 @@
+use todomvc_sw::cache::{cache_name, is_cacheable, stale_caches, PRECACHE};
+
+fn names(names: &[&str]) -> Vec<String> {
+  names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn names_caches_by_version() {
+  assert_eq!(cache_name("3"), "todomvc-3");
+}
+
+#[test]
+fn finds_the_caches_of_other_versions() {
+  let caches = names(&["todomvc-1", "todomvc-2", "todomvc-3", "other-app-1"]);
+  assert_eq!(stale_caches(&caches, "3"), names(&["todomvc-1", "todomvc-2"]));
+  assert!(stale_caches(&names(&["todomvc-3"]), "3").is_empty());
+}
+
+#[test]
+fn caches_reads_from_its_origin_only() {
+  let origin = "https://todo.example";
+  assert!(is_cacheable("GET", "https://todo.example/", origin));
+  assert!(is_cacheable("get", "https://todo.example/pkg/todomvc.js", origin));
+  assert!(!is_cacheable("POST", "https://todo.example/", origin));
+  assert!(!is_cacheable("GET", "https://cdn.example/lib.js", origin));
+  assert!(!is_cacheable("GET", "https://todo.example.evil/", origin));
+}
+
+#[test]
+fn precaches_the_page_and_both_bundles() {
+  for url in &["./index.html", "./pkg/todomvc_bg.wasm", "./sw/pkg/todomvc_sw_bg.wasm"] {
+    assert!(PRECACHE.contains(url), "{} is not precached", url);
+  }
+}
@@ -0,0 +1,195 @@
+mod common;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use common::{MemoryStorage, STORE_NAME};
+use todomvc::changes::{diff, Change, ItemCounts};
+use todomvc::store::{Item, ItemList, ItemQuery, ItemUpdate, Store};
+
+fn subtask(id: &str, title: &str, parent: &str) -> Item {
+  Item {
+    parent: Some(parent.to_string()),
+    ..Item::new(id, title)
+  }
+}
+
+fn completed(item: Item) -> Item {
+  Item {
+    completed: true,
+    ..item
+  }
+}
+
+fn list(items: &[Item]) -> ItemList {
+  items.iter().cloned().collect()
+}
+
+/// A store and the change batches its subscriber received.
+fn observed() -> (Store, Rc<RefCell<Vec<Vec<Change>>>>) {
+  let mut store = Store::with_storage(Box::new(MemoryStorage::default()), STORE_NAME);
+  let received = Rc::new(RefCell::new(Vec::new()));
+  let log = received.clone();
+  store.subscribe(Box::new(move |changes| log.borrow_mut().push(changes.to_vec())));
+  (store, received)
+}
+
+#[test]
+fn diffs_by_id() {
+  let before = list(&[Item::new("1", "Milk"), Item::new("2", "Bread"), Item::new("3", "Eggs")]);
+  let after = list(&[
+    Item::new("3", "Eggs"),
+    completed(Item::new("1", "Milk")),
+    Item::new("4", "Tea"),
+  ]);
+  assert_eq!(
+    diff(&before, &after),
+    vec![
+      Change::Removed(Item::new("2", "Bread")),
+      Change::Updated {
+        before: Item::new("1", "Milk"),
+        after: completed(Item::new("1", "Milk")),
+      },
+      Change::Inserted(Item::new("4", "Tea")),
+    ]
+  );
+  assert!(diff(&before, &before).is_empty());
+}
+
+#[test]
+fn publishes_every_write() {
+  let (mut store, received) = observed();
+  assert_eq!(*received.borrow(), vec![vec![Change::Reset(Vec::new())]]);
+  received.borrow_mut().clear();
+
+  store.insert(Item::new("1", "Groceries"));
+  store.insert(subtask("2", "Milk", "1"));
+  store.update(ItemUpdate::Completed {
+    id: "2".to_string(),
+    completed: true,
+  });
+  store.remove(ItemQuery::Id { id: "1".to_string() });
+
+  let received = received.borrow();
+  assert_eq!(received.len(), 4);
+  assert_eq!(received[0], vec![Change::Inserted(Item::new("1", "Groceries"))]);
+  // Completing the last subtask completes its parent too.
+  assert_eq!(
+    received[2],
+    vec![
+      Change::Updated {
+        before: Item::new("1", "Groceries"),
+        after: completed(Item::new("1", "Groceries")),
+      },
+      Change::Updated {
+        before: subtask("2", "Milk", "1"),
+        after: completed(subtask("2", "Milk", "1")),
+      },
+    ]
+  );
+  assert_eq!(
+    received[3],
+    vec![
+      Change::Removed(completed(Item::new("1", "Groceries"))),
+      Change::Removed(completed(subtask("2", "Milk", "1"))),
+    ]
+  );
+}
+
+#[test]
+fn publishes_transactions_once_and_rollbacks_never() {
+  let (mut store, received) = observed();
+  received.borrow_mut().clear();
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.insert(Item::new("1", "Milk"));
+    tx.insert(Item::new("2", "Bread"));
+    Err(())
+  });
+  assert!(result.is_err());
+  assert!(received.borrow().is_empty());
+
+  store.batch(|tx| {
+    tx.insert(Item::new("1", "Milk"));
+    tx.insert(Item::new("2", "Bread"));
+    tx.update(ItemUpdate::Title {
+      id: "1".to_string(),
+      title: "Oat milk".to_string(),
+    });
+  });
+  assert_eq!(
+    *received.borrow(),
+    vec![vec![
+      Change::Inserted(Item::new("1", "Oat milk")),
+      Change::Inserted(Item::new("2", "Bread")),
+    ]]
+  );
+}
+
+#[test]
+fn switching_lists_resets() {
+  let (mut store, received) = observed();
+  store.insert(Item::new("1", "Milk"));
+  store.create_list("Work");
+  store.switch_list("Work");
+  store.switch_list("todos");
+
+  let received = received.borrow();
+  assert_eq!(received[2], vec![Change::Reset(Vec::new())]);
+  assert_eq!(received[3], vec![Change::Reset(vec![Item::new("1", "Milk")])]);
+}
+
+#[test]
+fn unsubscribes() {
+  let (mut store, received) = observed();
+  let calls = Rc::new(RefCell::new(0));
+  let counter = calls.clone();
+  let id = store.subscribe(Box::new(move |_| *counter.borrow_mut() += 1));
+  store.insert(Item::new("1", "Milk"));
+  store.unsubscribe(id);
+  store.insert(Item::new("2", "Bread"));
+  assert_eq!(*calls.borrow(), 2);
+  assert_eq!(received.borrow().len(), 3);
+}
+
+#[test]
+fn counts_follow_the_changes() {
+  let mut store = Store::with_storage(Box::new(MemoryStorage::default()), STORE_NAME);
+  let counts = Rc::new(RefCell::new(ItemCounts::new()));
+  let subscriber = counts.clone();
+  store.subscribe(Box::new(move |changes| subscriber.borrow_mut().apply(changes)));
+  let check = |store: &mut Store| {
+    assert_eq!(Some(counts.borrow().get()), store.count());
+  };
+
+  store.insert(Item::new("1", "Groceries"));
+  store.insert(Item::new("2", "Laundry"));
+  check(&mut store);
+  // Parents stop counting once they have subtasks.
+  store.insert(subtask("3", "Milk", "1"));
+  store.insert(subtask("4", "Bread", "1"));
+  store.insert(subtask("5", "Oat milk", "3"));
+  check(&mut store);
+  assert_eq!(counts.borrow().get(), (3, 3, 0));
+
+  store.update(ItemUpdate::Completed {
+    id: "5".to_string(),
+    completed: true,
+  });
+  check(&mut store);
+  store.outdent("5");
+  check(&mut store);
+  store.indent("2");
+  check(&mut store);
+  store.remove(ItemQuery::Id { id: "4".to_string() });
+  check(&mut store);
+  store.update(ItemUpdate::Completed {
+    id: "1".to_string(),
+    completed: true,
+  });
+  check(&mut store);
+  assert_eq!(counts.borrow().get(), (3, 0, 3));
+  store.remove(ItemQuery::Completed { completed: true });
+  check(&mut store);
+  assert_eq!(counts.borrow().get(), (0, 0, 0));
+}
@@ -0,0 +1,291 @@
+// Each test crate uses a different subset of the harness.
+#![allow(dead_code)]
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use todomvc::controller::{Controller, ControllerMessage};
+use todomvc::lists::ListInfo;
+use todomvc::platform::{Clock, Storage};
+use todomvc::preferences::Preferences;
+use todomvc::reminders::Reminder;
+use todomvc::scheduler::Scheduler;
+use todomvc::store::{ItemListTrait, Store};
+use todomvc::view::{Render, ViewMessage};
+use todomvc::Message;
+
+pub const STORE_NAME: &str = "todos-test";
+
+/// In-memory `Storage`, clones share their contents.
+#[derive(Clone, Default)]
+pub struct MemoryStorage {
+  items: Rc<RefCell<HashMap<String, String>>>,
+  reads: Rc<Cell<usize>>,
+  writes: Rc<Cell<usize>>,
+}
+
+impl MemoryStorage {
+  pub fn get(&self, key: &str) -> Option<String> {
+    self.items.borrow().get(key).cloned()
+  }
+
+  /// Number of `get_item` calls so far.
+  pub fn reads(&self) -> usize {
+    self.reads.get()
+  }
+
+  /// Number of `set_item` calls so far.
+  pub fn writes(&self) -> usize {
+    self.writes.get()
+  }
+}
+
+impl Storage for MemoryStorage {
+  fn get_item(&self, key: &str) -> Option<String> {
+    self.reads.set(self.reads.get() + 1);
+    self.get(key)
+  }
+
+  fn set_item(&self, key: &str, value: &str) {
+    self.writes.set(self.writes.get() + 1);
+    self
+      .items
+      .borrow_mut()
+      .insert(key.to_string(), value.to_string());
+  }
+
+  fn remove_item(&self, key: &str) {
+    self.items.borrow_mut().remove(key);
+  }
+}
+
+/// Clock that advances by one millisecond on every read.
+pub struct FakeClock {
+  now: Cell<f64>,
+  /// Milliseconds each reading advances the clock by.
+  step: f64,
+}
+
+impl FakeClock {
+  pub fn new(start: f64) -> FakeClock {
+    FakeClock {
+      now: Cell::new(start),
+      step: 1.0,
+    }
+  }
+
+  /// A clock that always reads `now`, as a fast machine may.
+  pub fn stopped(now: f64) -> FakeClock {
+    FakeClock {
+      now: Cell::new(now),
+      step: 0.0,
+    }
+  }
+}
+
+impl Clock for FakeClock {
+  fn now(&self) -> f64 {
+    let now = self.now.get();
+    self.now.set(now + self.step);
+    now
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Row {
+  pub id: String,
+  pub title: String,
+  pub completed: bool,
+}
+
+/// The page state the view messages describe, in place of a real DOM.
+#[derive(Debug, Default)]
+pub struct FakeDom {
+  pub rows: Vec<Row>,
+  pub items_left: Option<usize>,
+  pub clear_completed_visible: bool,
+  pub complete_all_checked: bool,
+  pub main_visible: bool,
+  pub new_todo_cleared: bool,
+  pub selected_filter: Option<String>,
+  pub preferences: Option<Preferences>,
+  pub lists: Vec<ListInfo>,
+  pub current_list: Option<String>,
+  pub hash: Option<String>,
+  pub reminders: Vec<Reminder>,
+  /// Every message received, in the order it was rendered.
+  pub log: Vec<String>,
+}
+
+impl FakeDom {
+  pub fn row(&self, id: &str) -> Option<&Row> {
+    self.rows.iter().find(|row| row.id == id)
+  }
+
+  pub fn titles(&self) -> Vec<&str> {
+    self.rows.iter().map(|row| row.title.as_str()).collect()
+  }
+}
+
+pub struct FakeView {
+  dom: Rc<RefCell<FakeDom>>,
+}
+
+impl Render for FakeView {
+  fn call(&mut self, message: ViewMessage) {
+    use todomvc::view::ViewMessage::*;
+    let mut dom = self.dom.borrow_mut();
+    let entry = match message {
+      UpdateFilterButtons(route) => {
+        let entry = format!("UpdateFilterButtons({})", route);
+        dom.selected_filter = Some(route);
+        entry
+      }
+      ClearNewTodo() => {
+        dom.new_todo_cleared = true;
+        "ClearNewTodo".to_string()
+      }
+      ShowItems(items) => {
+        dom.rows = items
+          .iter()
+          .map(|item| Row {
+            id: item.id.clone(),
+            title: item.title.clone(),
+            completed: item.completed,
+          })
+          .collect();
+        format!("ShowItems({})", dom.rows.len())
+      }
+      SetItemsLeft(count) => {
+        dom.items_left = Some(count);
+        format!("SetItemsLeft({})", count)
+      }
+      SetClearCompletedButtonVisibility(visible) => {
+        dom.clear_completed_visible = visible;
+        format!("SetClearCompletedButtonVisibility({})", visible)
+      }
+      SetCompleteAllCheckbox(checked) => {
+        dom.complete_all_checked = checked;
+        format!("SetCompleteAllCheckbox({})", checked)
+      }
+      SetMainVisibility(visible) => {
+        dom.main_visible = visible;
+        format!("SetMainVisibility({})", visible)
+      }
+      RemoveItem(id) => {
+        dom.rows.retain(|row| row.id != id);
+        format!("RemoveItem({})", id)
+      }
+      EditItemDone(id, title) => {
+        let entry = format!("EditItemDone({}, {})", id, title);
+        if let Some(row) = dom.rows.iter_mut().find(|row| row.id == id) {
+          row.title = title;
+        }
+        entry
+      }
+      SetItemComplete(id, completed) => {
+        let entry = format!("SetItemComplete({}, {})", id, completed);
+        if let Some(row) = dom.rows.iter_mut().find(|row| row.id == id) {
+          row.completed = completed;
+        }
+        entry
+      }
+      ApplyPreferences(preferences) => {
+        dom.preferences = Some(preferences);
+        "ApplyPreferences".to_string()
+      }
+      ShowLists(lists, current) => {
+        let entry = format!("ShowLists({}, {})", lists.len(), current);
+        dom.lists = lists;
+        dom.current_list = Some(current);
+        entry
+      }
+      Navigate(hash) => {
+        let entry = format!("Navigate({})", hash);
+        dom.hash = Some(hash);
+        entry
+      }
+      ScheduleReminders(reminders) => {
+        let entry = format!("ScheduleReminders({})", reminders.len());
+        dom.reminders = reminders;
+        entry
+      }
+    };
+    dom.log.push(entry);
+  }
+}
+
+/// Controller, scheduler and fake view wired up like `app` does in the
+/// browser.
+pub struct App {
+  pub sched: Rc<Scheduler>,
+  pub dom: Rc<RefCell<FakeDom>>,
+  pub storage: MemoryStorage,
+}
+
+impl App {
+  pub fn new() -> App {
+    App::with_storage(MemoryStorage::default())
+  }
+
+  pub fn with_storage(storage: MemoryStorage) -> App {
+    App::with_clock(storage, 1000.0)
+  }
+
+  /// An app whose clock starts at `now` milliseconds since the epoch, in UTC.
+  pub fn with_clock(storage: MemoryStorage, now: f64) -> App {
+    App::with_fake_clock(storage, FakeClock::new(now))
+  }
+
+  pub fn with_fake_clock(storage: MemoryStorage, clock: FakeClock) -> App {
+    let sched = Rc::new(Scheduler::new());
+    let store = Store::with_storage(Box::new(storage.clone()), STORE_NAME);
+    let clock = Box::new(clock);
+    let controller = Controller::new(store, clock, Rc::downgrade(&sched));
+    let dom = Rc::new(RefCell::new(FakeDom::default()));
+    sched.set_view(FakeView { dom: dom.clone() });
+    sched.set_controller(controller);
+    App {
+      sched,
+      dom,
+      storage,
+    }
+  }
+
+  /// Sends `message` to the controller and returns the view messages it
+  /// caused.
+  pub fn send(&self, message: ControllerMessage) -> Vec<String> {
+    let before = self.dom.borrow().log.len();
+    self.sched.add_message(Message::Controller(message));
+    self.dom.borrow().log[before..].to_vec()
+  }
+
+  /// The persisted items of the default list as `(title, completed, id)`.
+  pub fn stored(&self) -> Vec<(String, bool, String)> {
+    self.stored_at(STORE_NAME)
+  }
+
+  /// The items persisted under `key`, leaving out the optional fields.
+  pub fn stored_at(&self, key: &str) -> Vec<(String, bool, String)> {
+    let entries: Vec<Vec<serde_json::Value>> = match self.storage.get(key) {
+      Some(value) => serde_json::from_str(&value).unwrap(),
+      None => Vec::new(),
+    };
+    entries
+      .iter()
+      .map(|entry| {
+        (
+          entry[0].as_str().unwrap().to_string(),
+          entry[1].as_bool().unwrap(),
+          entry[2].as_str().unwrap().to_string(),
+        )
+      })
+      .collect()
+  }
+
+  pub fn add(&self, title: &str) -> String {
+    self.send(ControllerMessage::AddItem(title.to_string()));
+    self.stored().last().unwrap().2.clone()
+  }
+}
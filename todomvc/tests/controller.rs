@@ -0,0 +1,237 @@
+mod common;
+
+use common::{App, MemoryStorage, Row, STORE_NAME};
+use todomvc::controller::ControllerMessage::*;
+use todomvc::platform::Storage;
+use todomvc::preferences::{Density, Preferences, Theme};
+
+fn has(messages: &[String], expected: &str) -> bool {
+  messages.iter().any(|message| message == expected)
+}
+
+#[test]
+fn add_item() {
+  let app = App::new();
+  let messages = app.send(AddItem("Buy milk".to_string()));
+
+  assert!(has(&messages, "ClearNewTodo"));
+  assert!(has(&messages, "ShowItems(1)"));
+  assert!(has(&messages, "SetItemsLeft(1)"));
+  assert!(has(&messages, "SetClearCompletedButtonVisibility(false)"));
+  assert!(has(&messages, "SetCompleteAllCheckbox(false)"));
+  assert!(has(&messages, "SetMainVisibility(true)"));
+
+  let dom = app.dom.borrow();
+  assert_eq!(
+    dom.rows,
+    vec![Row {
+      id: "1000".to_string(),
+      title: "Buy milk".to_string(),
+      completed: false,
+    }]
+  );
+  assert!(dom.new_todo_cleared);
+  assert_eq!(
+    app.stored(),
+    vec![("Buy milk".to_string(), false, "1000".to_string())]
+  );
+}
+
+#[test]
+fn add_item_uses_clock_for_ids() {
+  let app = App::new();
+  assert_eq!(app.add("first"), "1000");
+  assert_eq!(app.add("second"), "1001");
+}
+
+#[test]
+fn set_page_filters_items() {
+  let app = App::new();
+  app.add("one");
+  let two = app.add("two");
+  app.add("three");
+  app.send(ToggleItem(two.clone(), true));
+
+  let messages = app.send(SetPage("#/active".to_string()));
+  assert!(has(&messages, "UpdateFilterButtons(active)"));
+  assert!(has(&messages, "ShowItems(2)"));
+  assert_eq!(app.dom.borrow().titles(), vec!["one", "three"]);
+
+  app.send(SetPage("#/completed".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+  assert_eq!(app.dom.borrow().selected_filter, Some("completed".to_string()));
+
+  app.send(SetPage("#/".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["one", "two", "three"]);
+  assert_eq!(app.dom.borrow().selected_filter, Some("".to_string()));
+}
+
+#[test]
+fn set_page_without_hash_uses_default_filter() {
+  let storage = MemoryStorage::default();
+  storage.set_item(
+    STORE_NAME,
+    r#"[["one",false,"1"],["two",true,"2"]]"#,
+  );
+  storage.set_item(
+    &format!("{}-preferences", STORE_NAME),
+    r#"["dark","compact","completed",true]"#,
+  );
+  let app = App::with_storage(storage);
+
+  let messages = app.send(SetPage("".to_string()));
+  assert!(has(&messages, "UpdateFilterButtons(completed)"));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+}
+
+#[test]
+fn edit_item_save() {
+  let app = App::new();
+  let id = app.add("Buy milk");
+
+  let messages = app.send(EditItemSave(id.clone(), "Buy oat milk".to_string()));
+  assert_eq!(
+    messages,
+    vec![format!("EditItemDone({}, Buy oat milk)", id)]
+  );
+  assert_eq!(app.dom.borrow().titles(), vec!["Buy oat milk"]);
+  assert_eq!(app.stored()[0].0, "Buy oat milk");
+}
+
+#[test]
+fn edit_item_save_empty_removes_item() {
+  let app = App::new();
+  let id = app.add("Buy milk");
+
+  let messages = app.send(EditItemSave(id.clone(), "".to_string()));
+  assert!(has(&messages, &format!("RemoveItem({})", id)));
+  assert!(has(&messages, "SetItemsLeft(0)"));
+  assert!(has(&messages, "SetMainVisibility(false)"));
+  assert!(app.dom.borrow().rows.is_empty());
+  assert!(app.stored().is_empty());
+}
+
+#[test]
+fn edit_item_cancel() {
+  let app = App::new();
+  let id = app.add("Buy milk");
+
+  let messages = app.send(EditItemCancel(id.clone()));
+  assert_eq!(messages, vec![format!("EditItemDone({}, Buy milk)", id)]);
+  assert_eq!(app.stored()[0].0, "Buy milk");
+}
+
+#[test]
+fn edit_item_cancel_unknown_id() {
+  let app = App::new();
+  app.add("Buy milk");
+
+  assert!(app.send(EditItemCancel("nope".to_string())).is_empty());
+}
+
+#[test]
+fn remove_item() {
+  let app = App::new();
+  let one = app.add("one");
+  app.add("two");
+
+  let messages = app.send(RemoveItem(one.clone()));
+  assert!(has(&messages, &format!("RemoveItem({})", one)));
+  assert!(has(&messages, "SetItemsLeft(1)"));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+  assert_eq!(app.stored().len(), 1);
+}
+
+#[test]
+fn remove_completed() {
+  let app = App::new();
+  let one = app.add("one");
+  app.add("two");
+  let three = app.add("three");
+  app.send(ToggleItem(one, true));
+  app.send(ToggleItem(three, true));
+  assert!(app.dom.borrow().clear_completed_visible);
+
+  let messages = app.send(RemoveCompleted());
+  assert!(has(&messages, "ShowItems(1)"));
+  assert!(has(&messages, "SetClearCompletedButtonVisibility(false)"));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+  assert_eq!(
+    app.stored(),
+    vec![("two".to_string(), false, "1001".to_string())]
+  );
+}
+
+#[test]
+fn toggle_item() {
+  let app = App::new();
+  let one = app.add("one");
+  app.add("two");
+
+  let messages = app.send(ToggleItem(one.clone(), true));
+  assert!(has(&messages, &format!("SetItemComplete({}, true)", one)));
+  assert!(has(&messages, "SetItemsLeft(1)"));
+  assert!(has(&messages, "SetClearCompletedButtonVisibility(true)"));
+  assert!(app.dom.borrow().row(&one).unwrap().completed);
+  assert!(app.stored()[0].1);
+
+  let messages = app.send(ToggleItem(one.clone(), false));
+  assert!(has(&messages, &format!("SetItemComplete({}, false)", one)));
+  assert!(has(&messages, "SetItemsLeft(2)"));
+  assert!(!app.dom.borrow().row(&one).unwrap().completed);
+  assert!(!app.stored()[0].1);
+}
+
+#[test]
+fn toggle_all() {
+  let app = App::new();
+  app.add("one");
+  app.add("two");
+
+  let messages = app.send(ToggleAll(true));
+  assert!(has(&messages, "SetItemsLeft(0)"));
+  assert!(has(&messages, "SetCompleteAllCheckbox(true)"));
+  {
+    let dom = app.dom.borrow();
+    assert!(dom.rows.iter().all(|row| row.completed));
+    assert!(dom.complete_all_checked);
+  }
+  assert!(app.stored().iter().all(|item| item.1));
+
+  let messages = app.send(ToggleAll(false));
+  assert!(has(&messages, "SetItemsLeft(2)"));
+  assert!(has(&messages, "SetCompleteAllCheckbox(false)"));
+  assert!(app.dom.borrow().rows.iter().all(|row| !row.completed));
+  assert!(app.stored().iter().all(|item| !item.1));
+}
+
+#[test]
+fn set_preferences() {
+  let app = App::new();
+  let preferences = Preferences {
+    theme: Theme::Dark,
+    density: Density::Compact,
+    default_filter: "active".to_string(),
+    confirm_delete: true,
+  };
+
+  let messages = app.send(SetPreferences(preferences.clone()));
+  assert_eq!(messages, vec!["ApplyPreferences".to_string()]);
+  assert_eq!(app.dom.borrow().preferences, Some(preferences));
+  assert_eq!(
+    app.storage.get(&format!("{}-preferences", STORE_NAME)),
+    Some(r#"["dark","compact","active",true]"#.to_string())
+  );
+}
+
+#[test]
+fn loads_items_from_storage() {
+  let storage = MemoryStorage::default();
+  storage.set_item(STORE_NAME, r#"[["one",true,"1"],["two",false,"2"]]"#);
+  let app = App::with_storage(storage);
+
+  let messages = app.send(SetPage("#/".to_string()));
+  assert!(has(&messages, "ShowItems(2)"));
+  assert!(has(&messages, "SetItemsLeft(1)"));
+  assert_eq!(app.dom.borrow().titles(), vec!["one", "two"]);
+}
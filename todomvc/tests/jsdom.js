@@ -0,0 +1,171 @@
+// DOM for the wasm-bindgen-test suite in `web.rs`, which runs under Node.
+// `jsdom` is installed from `package.json`.
+const { JSDOM } = require('jsdom');
+
+const GLOBALS = [
+  'CSSStyleDeclaration',
+  'DOMStringMap',
+  'DOMTokenList',
+  'Document',
+  'Element',
+  'Event',
+  'EventTarget',
+  'HTMLBodyElement',
+  'HTMLElement',
+  'HTMLInputElement',
+  'HTMLSelectElement',
+  'KeyboardEvent',
+  'Location',
+  'MediaQueryList',
+  'MouseEvent',
+  'Navigator',
+  'Node',
+  'NodeList',
+  'Notification',
+  'Storage',
+  'Window',
+];
+
+// `js_sys::global()` caches the global object the first time it is used,
+// possibly by the start function, so the jsdom window is installed as soon as
+// this module is loaded.
+const dom = new JSDOM('<!doctype html><html><body></body></html>', {
+  url: 'http://localhost/',
+});
+
+(function install() {
+  const window = dom.window;
+
+  // jsdom has no notifications, allow them and collect what is shown.
+  window.Notification = class Notification {
+    constructor(title, options) {
+      exports.notifications.push(Object.assign({ title }, options));
+    }
+    static requestPermission() {
+      return Promise.resolve('granted');
+    }
+  };
+  window.Notification.permission = 'granted';
+
+  // Reminders may be weeks away, their timers must not keep Node running.
+  const timers = new Map();
+  let next_timer = 1;
+  window.setTimeout = (callback, delay, ...args) => {
+    const id = next_timer++;
+    const timer = setTimeout(() => {
+      timers.delete(id);
+      callback(...args);
+    }, delay);
+    timer.unref();
+    timers.set(id, timer);
+    return id;
+  };
+  window.clearTimeout = (id) => {
+    clearTimeout(timers.get(id));
+    timers.delete(id);
+  };
+
+  global.globalThis = window;
+  global.self = window;
+  global.window = window;
+  for (const name of GLOBALS) {
+    if (window[name] !== undefined) {
+      global[name] = window[name];
+    }
+  }
+
+  // jsdom has no media queries.
+  window.matchMedia = (media) =>
+    Object.assign(new window.EventTarget(), { matches: false, media });
+
+  // Item ids come from `Date.now()`, keep them unique and predictable.
+  let now = 1000;
+  window.Date.now = global.Date.now = () => now++;
+})();
+
+exports.notifications = [];
+
+// Loads `html` as a fresh page. The window stays the same across tests.
+exports.load_page = function (html) {
+  const page = new JSDOM(html);
+  const document = dom.window.document;
+  document.documentElement.setAttribute('lang', 'en');
+  document.body.innerHTML = page.window.document.body.innerHTML;
+  dom.window.localStorage.clear();
+  exports.notifications.length = 0;
+  dom.window.history.replaceState(null, '', '/');
+};
+
+function element(selector) {
+  const el = dom.window.document.querySelector(selector);
+  if (el === null) {
+    throw new Error(`no element matches ${selector}`);
+  }
+  return el;
+}
+
+// Types `text` into an input and commits it like a user pressing Enter.
+exports.type_text = function (selector, text) {
+  const el = element(selector);
+  el.focus();
+  el.value = text;
+  el.dispatchEvent(new dom.window.Event('input', { bubbles: true }));
+  el.dispatchEvent(new dom.window.Event('change', { bubbles: true }));
+};
+
+exports.set_value = function (selector, text) {
+  element(selector).value = text;
+};
+
+exports.press_key = function (selector, type, key, key_code) {
+  const event = new dom.window.KeyboardEvent(type, {
+    key,
+    bubbles: true,
+    cancelable: true,
+  });
+  Object.defineProperty(event, 'keyCode', { value: key_code });
+  element(selector).dispatchEvent(event);
+};
+
+exports.click = function (selector) {
+  element(selector).click();
+};
+
+// Clicks holding a modifier key, `ctrlKey` or `shiftKey`.
+exports.modified_click = function (selector, modifier) {
+  const options = { bubbles: true, cancelable: true };
+  options[modifier] = true;
+  element(selector).dispatchEvent(new dom.window.MouseEvent('click', options));
+};
+
+exports.double_click = function (selector) {
+  const event = new dom.window.MouseEvent('dblclick', { bubbles: true });
+  element(selector).dispatchEvent(event);
+};
+
+exports.focus = function (selector) {
+  element(selector).focus();
+};
+
+// Navigates to `hash`. jsdom fires `hashchange` asynchronously, dispatch it
+// right away so the test can check the result.
+exports.set_hash = function (hash) {
+  const window = dom.window;
+  window.history.replaceState(null, '', hash);
+  window.dispatchEvent(new window.HashChangeEvent('hashchange'));
+};
+
+// Queues `answer` for `window.prompt`, jsdom doesn't implement dialogs. The
+// last answer is repeated once the queue runs out.
+const answers = [];
+let last_answer = null;
+dom.window.prompt = () => {
+  if (answers.length > 0) {
+    last_answer = answers.shift();
+  }
+  return last_answer;
+};
+
+exports.answer_prompt = function (answer) {
+  answers.push(answer);
+};
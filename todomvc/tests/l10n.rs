@@ -0,0 +1,83 @@
+use todomvc::l10n::PluralCategory::{self, *};
+use todomvc::l10n::L10n;
+
+fn categories(lang: &str, counts: &[usize]) -> Vec<PluralCategory> {
+  counts.iter().map(|&n| PluralCategory::of(lang, n)).collect()
+}
+
+fn negotiate(requested: &[&str]) -> String {
+  let requested: Vec<String> = requested.iter().map(|tag| tag.to_string()).collect();
+  L10n::negotiate(&requested).locale().to_string()
+}
+
+#[test]
+fn english_has_one_and_other() {
+  assert_eq!(categories("en", &[0, 1, 2]), [Other, One, Other]);
+  let l10n = L10n::new("en");
+  assert_eq!(l10n.plural("items-left", 0, &[]), "0 items left");
+  assert_eq!(l10n.plural("items-left", 1, &[]), "1 item left");
+  assert_eq!(l10n.plural("items-left", 2, &[]), "2 items left");
+}
+
+#[test]
+fn french_counts_zero_as_one() {
+  assert_eq!(categories("fr", &[0, 1, 2]), [One, One, Other]);
+  let l10n = L10n::new("fr");
+  assert_eq!(l10n.plural("items-left", 0, &[]), "0 tâche restante");
+  assert_eq!(l10n.plural("items-left", 2, &[]), "2 tâches restantes");
+}
+
+#[test]
+fn russian_follows_the_last_digits() {
+  assert_eq!(
+    categories("ru", &[1, 2, 5, 11, 21, 22, 111]),
+    [One, Few, Many, Many, One, Few, Many]
+  );
+  let l10n = L10n::new("ru");
+  assert_eq!(l10n.plural("items-left", 21, &[]), "осталась 21 задача");
+  assert_eq!(l10n.plural("items-left", 22, &[]), "осталось 22 задачи");
+  assert_eq!(l10n.plural("items-left", 11, &[]), "осталось 11 задач");
+}
+
+#[test]
+fn polish_only_has_one_for_one() {
+  assert_eq!(categories("pl", &[1, 2, 5, 12, 22]), [One, Few, Many, Many, Few]);
+}
+
+#[test]
+fn arabic_has_all_six_categories() {
+  assert_eq!(
+    categories("ar", &[0, 1, 2, 3, 11, 100]),
+    [Zero, One, Two, Few, Many, Other]
+  );
+}
+
+#[test]
+fn regional_tags_use_the_rules_of_the_language() {
+  assert_eq!(categories("fr-CA", &[0]), [One]);
+  assert_eq!(categories("ru_RU", &[22]), [Few]);
+}
+
+#[test]
+fn locales_without_a_catalog_fall_back_to_english_messages() {
+  let l10n = L10n::new("ar");
+  assert_eq!(l10n.plural("items-left", 2, &[]), "2 items left");
+}
+
+#[test]
+fn negotiates_the_first_supported_locale() {
+  assert_eq!(negotiate(&["tlh", "fr", "de"]), "fr");
+  assert_eq!(negotiate(&["RU"]), "ru");
+}
+
+#[test]
+fn negotiates_a_region_to_its_language() {
+  assert_eq!(negotiate(&["de-AT"]), "de");
+  assert_eq!(negotiate(&["de-AT", "fr"]), "de");
+}
+
+#[test]
+fn negotiation_falls_back_to_english() {
+  assert_eq!(negotiate(&["tlh-Latn"]), "en");
+  assert_eq!(negotiate(&[]), "en");
+}
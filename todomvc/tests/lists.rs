@@ -0,0 +1,226 @@
+mod common;
+
+use common::{App, MemoryStorage, STORE_NAME};
+use todomvc::controller::ControllerMessage::*;
+use todomvc::lists::{route, ListInfo, Route};
+use todomvc::platform::Storage;
+
+fn list(name: &str, archived: bool) -> ListInfo {
+  ListInfo {
+    name: name.to_string(),
+    archived,
+  }
+}
+
+fn list_key(name: &str) -> String {
+  format!("{}-list-{}", STORE_NAME, name)
+}
+
+fn lists_key() -> String {
+  format!("{}-lists", STORE_NAME)
+}
+
+#[test]
+fn parses_routes() {
+  let parse = |hash: &str| {
+    let Route { list, filter } = Route::parse(hash);
+    (list, filter)
+  };
+  assert_eq!(parse(""), (None, "".to_string()));
+  assert_eq!(parse("#/active"), (None, "active".to_string()));
+  assert_eq!(
+    parse("#/list/work/active"),
+    (Some("work".to_string()), "active".to_string())
+  );
+  assert_eq!(parse("#/list/work"), (Some("work".to_string()), "".to_string()));
+  assert_eq!(
+    parse("#/list/My%20list/"),
+    (Some("My list".to_string()), "".to_string())
+  );
+  assert_eq!(
+    parse("#/list/My list/completed"),
+    (Some("My list".to_string()), "completed".to_string())
+  );
+  assert_eq!(parse("#/list/50%/"), (Some("50%".to_string()), "".to_string()));
+}
+
+#[test]
+fn routes_round_trip() {
+  for name in &["work", "Home & garden", "a/b", "Ünïcödé ✓", "100%"] {
+    let hash = route(name, "completed");
+    assert_eq!(
+      Route::parse(&hash),
+      Route {
+        list: Some(name.to_string()),
+        filter: "completed".to_string(),
+      }
+    );
+  }
+  assert_eq!(route("My list", ""), "#/list/My%20list/");
+}
+
+#[test]
+fn starts_with_default_list() {
+  let app = App::new();
+  let messages = app.send(SetPage("#/".to_string()));
+  assert!(messages.contains(&"ShowLists(1, todos)".to_string()));
+  assert_eq!(app.dom.borrow().lists, vec![list("todos", false)]);
+
+  // Items of the default list keep the key used before there were lists.
+  app.add("one");
+  assert_eq!(app.stored().len(), 1);
+}
+
+#[test]
+fn create_list_opens_it() {
+  let app = App::new();
+  app.add("one");
+
+  let messages = app.send(CreateList("  Work ".to_string()));
+  assert!(messages.contains(&"Navigate(#/list/Work/)".to_string()));
+  assert!(messages.contains(&"ShowItems(0)".to_string()));
+  assert_eq!(app.dom.borrow().current_list, Some("Work".to_string()));
+  assert_eq!(
+    app.storage.get(&lists_key()),
+    Some(r#"[["todos",false],["Work",false]]"#.to_string())
+  );
+
+  app.send(AddItem("two".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+  assert_eq!(app.stored_at(&list_key("Work"))[0].0, "two");
+  assert_eq!(app.stored().len(), 1);
+}
+
+#[test]
+fn create_list_ignores_blank_and_duplicate_names() {
+  let app = App::new();
+  assert!(app.send(CreateList("   ".to_string())).is_empty());
+  assert!(app.send(CreateList("todos".to_string())).is_empty());
+  assert_eq!(app.storage.get(&lists_key()), None);
+}
+
+#[test]
+fn set_page_switches_list() {
+  let app = App::new();
+  app.add("one");
+  app.send(CreateList("Work".to_string()));
+  app.send(AddItem("two".to_string()));
+  app.send(AddItem("three".to_string()));
+  app.send(ToggleItem("1002".to_string(), true));
+
+  app.send(SetPage("#/list/todos/".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["one"]);
+  assert_eq!(app.dom.borrow().current_list, Some("todos".to_string()));
+
+  let messages = app.send(SetPage("#/list/Work/active".to_string()));
+  assert!(messages.contains(&"UpdateFilterButtons(active)".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+
+  // Bare filters stay on the current list.
+  app.send(SetPage("#/completed".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["three"]);
+
+  // So do unknown lists.
+  app.send(SetPage("#/list/nope/".to_string()));
+  assert_eq!(app.dom.borrow().current_list, Some("Work".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["two", "three"]);
+}
+
+#[test]
+fn rename_list_moves_items() {
+  let app = App::new();
+  app.send(CreateList("Work".to_string()));
+  app.send(AddItem("two".to_string()));
+
+  let messages = app.send(RenameList("Work".to_string(), "Office".to_string()));
+  assert!(messages.contains(&"Navigate(#/list/Office/)".to_string()));
+  assert_eq!(
+    app.dom.borrow().lists,
+    vec![list("todos", false), list("Office", false)]
+  );
+  assert_eq!(app.storage.get(&list_key("Work")), None);
+  assert_eq!(app.stored_at(&list_key("Office"))[0].0, "two");
+
+  // Renaming a list that isn't open leaves the location alone.
+  let messages = app.send(RenameList("todos".to_string(), "Home".to_string()));
+  assert_eq!(messages, vec!["ShowLists(2, Office)".to_string()]);
+  assert!(app
+    .send(RenameList("Home".to_string(), "Office".to_string()))
+    .is_empty());
+}
+
+#[test]
+fn delete_list_switches_to_remaining_list() {
+  let app = App::new();
+  app.add("one");
+  app.send(CreateList("Work".to_string()));
+  app.send(AddItem("two".to_string()));
+
+  let messages = app.send(DeleteList("Work".to_string()));
+  assert!(messages.contains(&"Navigate(#/list/todos/)".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["one"]);
+  assert_eq!(app.dom.borrow().lists, vec![list("todos", false)]);
+  assert_eq!(app.storage.get(&list_key("Work")), None);
+
+  // The last list stays.
+  assert!(app.send(DeleteList("todos".to_string())).is_empty());
+  assert_eq!(app.stored().len(), 1);
+}
+
+#[test]
+fn archived_lists_are_not_opened_first() {
+  let storage = MemoryStorage::default();
+  let app = App::with_storage(storage.clone());
+  app.send(CreateList("Work".to_string()));
+
+  let messages = app.send(ArchiveList("todos".to_string(), true));
+  assert_eq!(messages, vec!["ShowLists(2, Work)".to_string()]);
+  assert_eq!(
+    storage.get(&lists_key()),
+    Some(r#"[["todos",true],["Work",false]]"#.to_string())
+  );
+
+  let app = App::with_storage(storage);
+  app.send(SetPage("".to_string()));
+  assert_eq!(app.dom.borrow().current_list, Some("Work".to_string()));
+  assert_eq!(
+    app.dom.borrow().lists,
+    vec![list("todos", true), list("Work", false)]
+  );
+}
+
+#[test]
+fn move_item_between_lists() {
+  let app = App::new();
+  let one = app.add("one");
+  app.add("two");
+  app.send(CreateList("Work".to_string()));
+  app.send(SetPage("#/list/todos/".to_string()));
+
+  let messages = app.send(MoveItem(one.clone(), "Work".to_string()));
+  assert!(messages.contains(&format!("RemoveItem({})", one)));
+  assert!(messages.contains(&"SetItemsLeft(1)".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+  assert_eq!(
+    app.stored_at(&list_key("Work")),
+    vec![("one".to_string(), false, one.clone())]
+  );
+
+  // Neither the current list nor unknown lists are targets.
+  assert!(app.send(MoveItem("1001".to_string(), "todos".to_string())).is_empty());
+  assert!(app.send(MoveItem("1001".to_string(), "nope".to_string())).is_empty());
+
+  app.send(SetPage("#/list/Work/".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["one"]);
+}
+
+#[test]
+fn lists_survive_reload() {
+  let storage = MemoryStorage::default();
+  storage.set_item(&lists_key(), r#"[["todos",false],["Work",false]]"#);
+  storage.set_item(&list_key("Work"), r#"[["two",false,"2"]]"#);
+  let app = App::with_storage(storage);
+
+  app.send(SetPage("#/list/Work/".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["two"]);
+}
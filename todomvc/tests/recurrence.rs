@@ -0,0 +1,503 @@
+mod common;
+
+use common::{App, FakeClock, MemoryStorage, STORE_NAME};
+use todomvc::controller::ControllerMessage::*;
+use todomvc::l10n::L10n;
+use todomvc::recurrence::{DateTime, Frequency, Rule, Weekday};
+use todomvc::store::{Item, ItemList, ItemListTrait, ItemQuery, Store};
+use todomvc::template::Template;
+
+/// 2026-10-25 12:00 UTC
+const OCTOBER_25_NOON: f64 = 1_792_929_600_000.0;
+
+fn at(value: &str) -> DateTime {
+  DateTime::parse(value).unwrap()
+}
+
+fn rule(value: &str) -> Rule {
+  Rule::from_input(value).unwrap()
+}
+
+/// The next `n` occurrences of `rule` after `start`, completing each on time.
+fn series(rule: &str, start: &str, n: usize) -> Vec<String> {
+  let mut rule = self::rule(rule);
+  let mut current = at(start);
+  let mut dates = Vec::new();
+  while dates.len() < n {
+    match rule.next_occurrence(current, current) {
+      Some((next, next_rule)) => {
+        dates.push(next.human());
+        current = next;
+        rule = next_rule;
+      }
+      None => break,
+    }
+  }
+  dates
+}
+
+#[test]
+fn parses_dates() {
+  assert_eq!(at("2026-10-19 08:30").to_string(), "2026-10-19T08:30");
+  assert_eq!(at("2026-10-19T08:30").human(), "2026-10-19 08:30");
+  assert_eq!(at("2026-10-19").to_string(), "2026-10-19T09:00");
+  assert_eq!(at("2024-02-29").weekday(), Weekday::Thursday);
+  assert_eq!(at("2026-10-19").weekday(), Weekday::Monday);
+  for invalid in &["", "2026-13-01", "2026-02-29", "2026-10-19 24:00", "19.10.2026", "soon"] {
+    assert_eq!(DateTime::parse(invalid), None, "{}", invalid);
+  }
+  assert!(at("2026-10-19 23:59") < at("2026-10-20 00:00"));
+}
+
+#[test]
+fn converts_from_millis() {
+  assert_eq!(DateTime::from_millis(0.0).to_string(), "1970-01-01T00:00");
+  assert_eq!(
+    DateTime::from_millis(OCTOBER_25_NOON).to_string(),
+    "2026-10-25T12:00"
+  );
+  assert_eq!(DateTime::from_millis(-60_000.0).to_string(), "1969-12-31T23:59");
+}
+
+#[test]
+fn parses_presets_and_rules() {
+  assert_eq!(rule("daily").to_string(), "FREQ=DAILY");
+  assert_eq!(rule(" Weekdays ").to_string(), "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR");
+  assert_eq!(rule("monthly").frequency, Frequency::Monthly);
+  assert_eq!(
+    rule("rrule:freq=monthly;byday=-1fr;count=3").to_string(),
+    "FREQ=MONTHLY;BYDAY=-1FR;COUNT=3"
+  );
+  assert_eq!(
+    rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO;UNTIL=20261231").to_string(),
+    "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO;UNTIL=20261231T235900"
+  );
+  let stored = "FREQ=MONTHLY;BYMONTHDAY=1,-1;UNTIL=20270101T120000";
+  assert_eq!(rule(stored).to_string(), stored);
+}
+
+#[test]
+fn rejects_rules_outside_the_subset() {
+  for invalid in &[
+    "",
+    "sometimes",
+    "INTERVAL=2",
+    "FREQ=HOURLY",
+    "FREQ=DAILY;INTERVAL=0",
+    "FREQ=DAILY;COUNT=0",
+    "FREQ=WEEKLY;BYDAY=XX",
+    "FREQ=WEEKLY;BYDAY=1MO",
+    "FREQ=DAILY;BYMONTHDAY=1",
+    "FREQ=MONTHLY;BYMONTHDAY=32",
+    "FREQ=MONTHLY;BYDAY=0FR",
+    "FREQ=YEARLY;BYDAY=MO",
+    "FREQ=DAILY;UNTIL=tomorrow",
+    "FREQ=DAILY;WKST=SU",
+  ] {
+    assert_eq!(Rule::from_input(invalid), None, "{}", invalid);
+  }
+}
+
+#[test]
+fn rejects_multibyte_input_without_panicking() {
+  for invalid in &[
+    "RRRRRé",
+    "RRRRRé;FREQ=DAILY",
+    "é",
+    "FREQ=WEEKLY;BYDAY=1éa",
+    "FREQ=WEEKLY;BYDAY=éMO",
+    "FREQ=WEEKLY;BYDAY=Mé",
+    "FREQ=DAILY;UNTIL=20261031T1é2",
+    "FREQ=DAILY;UNTIL=20261031Té",
+    "FREQ=DAILY;UNTIL=2026103é",
+    "每天",
+  ] {
+    assert_eq!(Rule::from_input(invalid), None, "{}", invalid);
+  }
+}
+
+#[test]
+fn daily_and_interval() {
+  assert_eq!(
+    series("daily", "2026-12-30 07:15", 3),
+    vec!["2026-12-31 07:15", "2027-01-01 07:15", "2027-01-02 07:15"]
+  );
+  assert_eq!(
+    series("FREQ=DAILY;INTERVAL=3", "2026-02-27", 2),
+    vec!["2026-03-02 09:00", "2026-03-05 09:00"]
+  );
+  assert_eq!(
+    series("FREQ=DAILY;BYDAY=SA,SU", "2026-10-19", 3),
+    vec!["2026-10-24 09:00", "2026-10-25 09:00", "2026-10-31 09:00"]
+  );
+}
+
+#[test]
+fn weekdays_skip_the_weekend() {
+  assert_eq!(
+    series("weekdays", "2026-10-23 18:00", 2),
+    vec!["2026-10-26 18:00", "2026-10-27 18:00"]
+  );
+}
+
+#[test]
+fn weekly_on_several_days() {
+  assert_eq!(
+    series("FREQ=WEEKLY;BYDAY=WE,MO", "2026-10-19", 4),
+    vec![
+      "2026-10-21 09:00",
+      "2026-10-26 09:00",
+      "2026-10-28 09:00",
+      "2026-11-02 09:00"
+    ]
+  );
+  assert_eq!(
+    series("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR", "2026-10-19", 3),
+    vec!["2026-10-23 09:00", "2026-11-02 09:00", "2026-11-06 09:00"]
+  );
+  // Without BYDAY it is the weekday of the current occurrence.
+  assert_eq!(series("weekly", "2026-12-29", 1), vec!["2027-01-05 09:00"]);
+}
+
+#[test]
+fn monthly_skips_months_without_the_day() {
+  assert_eq!(
+    series("monthly", "2026-01-31", 3),
+    vec!["2026-03-31 09:00", "2026-05-31 09:00", "2026-07-31 09:00"]
+  );
+  assert_eq!(
+    series("FREQ=MONTHLY;BYMONTHDAY=-1", "2026-01-31", 3),
+    vec!["2026-02-28 09:00", "2026-03-31 09:00", "2026-04-30 09:00"]
+  );
+}
+
+#[test]
+fn monthly_by_weekday() {
+  assert_eq!(
+    series("FREQ=MONTHLY;BYDAY=-1FR", "2026-10-19", 2),
+    vec!["2026-10-30 09:00", "2026-11-27 09:00"]
+  );
+  assert_eq!(
+    series("FREQ=MONTHLY;BYDAY=2TU", "2026-10-19", 2),
+    vec!["2026-11-10 09:00", "2026-12-08 09:00"]
+  );
+  // Friday the 13th.
+  assert_eq!(
+    series("FREQ=MONTHLY;BYDAY=FR;BYMONTHDAY=13", "2026-01-01", 2),
+    vec!["2026-02-13 09:00", "2026-03-13 09:00"]
+  );
+}
+
+#[test]
+fn yearly_skips_missing_leap_days() {
+  assert_eq!(
+    series("yearly", "2024-02-29 10:00", 2),
+    vec!["2028-02-29 10:00", "2032-02-29 10:00"]
+  );
+}
+
+#[test]
+fn count_and_until_end_the_series() {
+  assert_eq!(
+    series("FREQ=DAILY;COUNT=3", "2026-10-19", 10),
+    vec!["2026-10-20 09:00", "2026-10-21 09:00"]
+  );
+  let (_, rule) = rule("FREQ=DAILY;COUNT=3")
+    .next_occurrence(at("2026-10-19"), at("2026-10-19"))
+    .unwrap();
+  assert_eq!(rule.count, Some(2));
+
+  assert_eq!(
+    series("FREQ=WEEKLY;UNTIL=20261102", "2026-10-19", 10),
+    vec!["2026-10-26 09:00", "2026-11-02 09:00"]
+  );
+}
+
+#[test]
+fn late_completion_skips_missed_occurrences() {
+  let daily = rule("daily");
+  let (next, _) = daily
+    .next_occurrence(at("2026-10-19 09:00"), at("2026-10-25 12:00"))
+    .unwrap();
+  assert_eq!(next.human(), "2026-10-26 09:00");
+
+  let (next, _) = rule("FREQ=WEEKLY;INTERVAL=2")
+    .next_occurrence(at("2026-10-19 09:00"), at("2026-11-04 12:00"))
+    .unwrap();
+  assert_eq!(next.human(), "2026-11-16 09:00");
+
+  // Decades behind is no slower.
+  let (next, _) = daily
+    .next_occurrence(at("1970-01-01 09:00"), at("2026-10-25 08:00"))
+    .unwrap();
+  assert_eq!(next.human(), "2026-10-25 09:00");
+}
+
+/// Stored items as `(title, completed, due, rule)`.
+fn schedules(app: &App) -> Vec<(String, bool, Option<String>, Option<String>)> {
+  let value = app.storage.get(STORE_NAME).unwrap();
+  let entries: Vec<Vec<serde_json::Value>> = serde_json::from_str(&value).unwrap();
+  let field = |entry: &Vec<serde_json::Value>, i: usize| {
+    entry
+      .get(i)
+      .and_then(|value| value.as_str())
+      .map(String::from)
+  };
+  entries
+    .iter()
+    .map(|entry| {
+      (
+        field(entry, 0).unwrap(),
+        entry[1].as_bool().unwrap(),
+        field(entry, 4),
+        field(entry, 5),
+      )
+    })
+    .collect()
+}
+
+fn schedule(app: &App, id: &str, due: &str, repeat: &str) {
+  app.send(SetSchedule(
+    id.to_string(),
+    DateTime::parse(due),
+    Rule::from_input(repeat),
+  ));
+}
+
+#[test]
+fn schedules_are_stored() {
+  let app = App::new();
+  let id = app.add("water plants");
+  schedule(&app, &id, "2026-10-19 08:00", "weekdays");
+  assert_eq!(
+    app.storage.get(STORE_NAME).unwrap(),
+    r#"[["water plants",false,"1000",null,"2026-10-19T08:00","FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR"]]"#
+  );
+
+  // Clearing both drops the extra fields.
+  schedule(&app, &id, "", "");
+  assert_eq!(
+    app.storage.get(STORE_NAME).unwrap(),
+    r#"[["water plants",false,"1000"]]"#
+  );
+}
+
+#[test]
+fn repeating_without_due_date_starts_now() {
+  let app = App::with_clock(MemoryStorage::default(), OCTOBER_25_NOON);
+  let id = app.add("stretch");
+  schedule(&app, &id, "", "daily");
+  assert_eq!(
+    schedules(&app)[0].2,
+    Some("2026-10-25T12:00".to_string())
+  );
+}
+
+#[test]
+fn completing_a_repeating_item_adds_the_next_occurrence() {
+  let app = App::new();
+  let id = app.add("water plants");
+  schedule(&app, &id, "2026-10-23 08:00", "FREQ=WEEKLY;BYDAY=MO,FR;COUNT=2");
+
+  let messages = app.send(ToggleItem(id.clone(), true));
+  assert!(messages.contains(&"ShowItems(2)".to_string()));
+  assert_eq!(
+    schedules(&app),
+    vec![
+      (
+        "water plants".to_string(),
+        true,
+        Some("2026-10-23T08:00".to_string()),
+        None
+      ),
+      (
+        "water plants".to_string(),
+        false,
+        Some("2026-10-26T08:00".to_string()),
+        Some("FREQ=WEEKLY;BYDAY=MO,FR;COUNT=1".to_string())
+      ),
+    ]
+  );
+
+  // The completed occurrence no longer repeats.
+  app.send(ToggleItem(id.clone(), false));
+  app.send(ToggleItem(id, true));
+  assert_eq!(schedules(&app).len(), 2);
+
+  // The last one of the series adds nothing.
+  let next = app.stored()[1].2.clone();
+  app.send(ToggleItem(next, true));
+  assert_eq!(schedules(&app).len(), 2);
+}
+
+#[test]
+fn completing_late_skips_to_the_next_future_occurrence() {
+  let app = App::with_clock(MemoryStorage::default(), OCTOBER_25_NOON);
+  let id = app.add("stand-up");
+  schedule(&app, &id, "2026-10-19 09:30", "weekdays");
+
+  app.send(ToggleItem(id, true));
+  assert_eq!(
+    schedules(&app)[1].2,
+    Some("2026-10-26T09:30".to_string())
+  );
+}
+
+#[test]
+fn toggle_all_and_subtasks_repeat_too() {
+  let app = App::new();
+  let parent = app.add("chores");
+  app.send(AddSubtask(parent.clone(), "dishes".to_string()));
+  let dishes = app.stored()[1].2.clone();
+  schedule(&app, &dishes, "2026-10-19 20:00", "daily");
+  app.add("other");
+
+  app.send(ToggleAll(true));
+  let stored = app.stored();
+  assert_eq!(stored.len(), 4);
+  assert_eq!(stored[3].0, "dishes");
+  // The new occurrence is another subtask, which reopens its parent.
+  assert!(!stored[0].1);
+  assert!(!stored[3].1);
+  assert_eq!(schedules(&app)[3].2, Some("2026-10-20T20:00".to_string()));
+}
+
+#[test]
+fn occurrences_created_in_the_same_millisecond_get_their_own_ids() {
+  let app = App::with_fake_clock(MemoryStorage::default(), FakeClock::stopped(OCTOBER_25_NOON));
+  let water = app.add("water plants");
+  let feed = app.add("feed cat");
+  assert_ne!(water, feed);
+  schedule(&app, &water, "2026-10-26 08:00", "daily");
+  schedule(&app, &feed, "2026-10-26 18:00", "daily");
+
+  app.send(ToggleAll(true));
+  let stored = app.stored();
+  assert_eq!(stored.len(), 4);
+  let mut ids: Vec<&String> = stored.iter().map(|(_, _, id)| id).collect();
+  ids.sort();
+  ids.dedup();
+  assert_eq!(ids.len(), 4);
+
+  // Each occurrence can be completed on its own.
+  let next = stored[2].2.clone();
+  app.send(ToggleItem(next, true));
+  let completed: Vec<bool> = app.stored().iter().map(|(_, completed, _)| *completed).collect();
+  assert_eq!(completed[2..4], [true, false]);
+}
+
+#[test]
+fn reminders_cover_open_items_of_all_lists() {
+  let storage = MemoryStorage::default();
+  let app = App::with_storage(storage.clone());
+  let one = app.add("one");
+  let two = app.add("two");
+  schedule(&app, &one, "2026-10-19 08:00", "");
+  schedule(&app, &two, "2026-10-20 08:00", "daily");
+  app.send(CreateList("Work".to_string()));
+  app.send(AddItem("three".to_string()));
+  app.send(CreateList("Archive".to_string()));
+  app.send(AddItem("four".to_string()));
+  let stored = app.stored_at(&format!("{}-list-Work", STORE_NAME));
+  schedule(&app, &stored[0].2, "2026-10-21 08:00", "");
+  let stored = app.stored_at(&format!("{}-list-Archive", STORE_NAME));
+  app.send(SetPage("#/list/Archive/".to_string()));
+  schedule(&app, &stored[0].2, "2026-10-22 08:00", "");
+  app.send(ArchiveList("Archive".to_string(), true));
+  app.send(SetPage("#/list/todos/".to_string()));
+  app.send(ToggleItem(one, true));
+
+  // Loading the page schedules them.
+  let app = App::with_storage(storage);
+  let messages = app.send(SetPage("#/list/todos/".to_string()));
+  assert!(messages.contains(&"ScheduleReminders(1)".to_string()));
+  let reminders: Vec<(String, String, String)> = app
+    .dom
+    .borrow()
+    .reminders
+    .iter()
+    .map(|reminder| (reminder.title.clone(), reminder.list.clone(), reminder.due.human()))
+    .collect();
+  assert_eq!(
+    reminders,
+    vec![("two".to_string(), "todos".to_string(), "2026-10-20 08:00".to_string())]
+  );
+}
+
+#[test]
+fn other_lists_are_read_once_until_they_change() {
+  let storage = MemoryStorage::default();
+  let mut store = Store::with_storage(Box::new(storage.clone()), STORE_NAME);
+  let due = |id: &str, title: &str, due: &str| Item {
+    id: id.to_string(),
+    title: title.to_string(),
+    completed: false,
+    parent: None,
+    due: DateTime::parse(due),
+    recurrence: None,
+    notes: None,
+  };
+  store.create_list("Work");
+  store.switch_list("Work");
+  store.insert(due("1", "report", "2026-10-21 08:00"));
+  store.switch_list("todos");
+  store.insert(due("2", "groceries", "2026-10-20 08:00"));
+  let titles = |store: &Store| -> Vec<(String, String)> {
+    store
+      .scheduled()
+      .into_iter()
+      .map(|(list, item)| (list, item.title))
+      .collect()
+  };
+  let pair = |list: &str, title: &str| (list.to_string(), title.to_string());
+
+  assert_eq!(titles(&store), vec![pair("todos", "groceries"), pair("Work", "report")]);
+  let reads = storage.reads();
+  store.insert(due("3", "laundry", "2026-10-22 08:00"));
+  assert_eq!(titles(&store).len(), 3);
+  assert_eq!(storage.reads(), reads);
+
+  // Moving an item writes the other list, switching makes it the current one.
+  store.move_item("3", "Work");
+  assert_eq!(titles(&store)[2], pair("Work", "laundry"));
+  store.switch_list("Work");
+  store.remove(ItemQuery::Id { id: "1".to_string() });
+  assert_eq!(titles(&store), vec![pair("todos", "groceries"), pair("Work", "laundry")]);
+  store.archive_list("todos", true);
+  assert_eq!(titles(&store), vec![pair("Work", "laundry")]);
+}
+
+#[test]
+fn reminders_are_only_sent_when_they_change() {
+  let app = App::new();
+  let id = app.add("one");
+  assert!(app.dom.borrow().log.contains(&"ScheduleReminders(0)".to_string()));
+
+  let messages = app.send(SetPage("#/active".to_string()));
+  assert!(!messages.iter().any(|message| message.starts_with("ScheduleReminders")));
+
+  let messages = app.send(SetSchedule(id.clone(), DateTime::parse("2026-10-19"), None));
+  assert!(messages.contains(&"ScheduleReminders(1)".to_string()));
+
+  let messages = app.send(EditItemSave(id.clone(), "renamed".to_string()));
+  assert!(messages.contains(&"ScheduleReminders(1)".to_string()));
+  assert_eq!(app.dom.borrow().reminders[0].title, "renamed");
+  let messages = app.send(ToggleItem(id, true));
+  assert!(messages.contains(&"ScheduleReminders(0)".to_string()));
+}
+
+#[test]
+fn rows_show_the_schedule() {
+  let mut items = ItemList::new();
+  items.push(Item {
+    due: DateTime::parse("2026-10-19 08:00"),
+    recurrence: Rule::from_input("daily"),
+    ..Item::new("1", "water plants")
+  });
+  let html = Template::item_list(&L10n::new("en"), items);
+  assert!(html.contains("data-due=\"2026-10-19 08:00\""));
+  assert!(html.contains("data-repeat=\"FREQ=DAILY\""));
+  assert!(html.contains(
+    "<span class=\"due\" aria-label=\"Due 2026-10-19 08:00, repeats\">2026-10-19 08:00 ↻</span>"
+  ));
+}
@@ -0,0 +1,226 @@
+mod common;
+
+use common::{App, MemoryStorage, STORE_NAME};
+use todomvc::controller::ControllerMessage::*;
+use todomvc::l10n::L10n;
+use todomvc::search::{highlights, normalize, tokenize, SearchIndex};
+use todomvc::store::{Item, ItemList, ItemListTrait};
+use todomvc::template::Template;
+
+fn terms(text: &str) -> Vec<String> {
+  tokenize(text).into_iter().map(|token| token.term).collect()
+}
+
+fn ids(index: &SearchIndex, query: &str) -> Vec<String> {
+  index.search(query).into_iter().map(|(id, _)| id).collect()
+}
+
+#[test]
+fn normalizes_case_accents_and_width() {
+  assert_eq!(normalize("Café"), "cafe");
+  assert_eq!(normalize("CAFE\u{301}"), "cafe");
+  assert_eq!(normalize("ｃａｆｅ"), "cafe");
+  assert_eq!(normalize("Straße"), "straße");
+  assert_eq!(normalize("Ёлка"), "елка");
+  assert_eq!(normalize("ﬁle"), "file");
+}
+
+#[test]
+fn tokenizes_words_with_their_position() {
+  assert_eq!(
+    terms("Buy milk, eggs & 2 loaves!"),
+    vec!["buy", "milk", "eggs", "2", "loaves"]
+  );
+  assert_eq!(terms("e-mail Zoë's"), vec!["e", "mail", "zoe", "s"]);
+  assert_eq!(terms("우유 사기"), vec!["우유", "사기"]);
+  assert!(terms("  -- !").is_empty());
+
+  let text = "Call Zoe\u{301} now";
+  let tokens = tokenize(text);
+  assert_eq!(tokens[1].term, "zoe");
+  assert_eq!(&text[tokens[1].range.clone()], "Zoe\u{301}");
+}
+
+#[test]
+fn matches_every_word_by_prefix() {
+  let mut index = SearchIndex::new();
+  index.insert("1", "Buy milk");
+  index.insert("2", "Buy bread and milk");
+  index.insert("3", "Call the bank");
+
+  assert_eq!(ids(&index, "mil"), vec!["1", "2"]);
+  assert_eq!(ids(&index, "buy bread"), vec!["2"]);
+  assert_eq!(ids(&index, "BREAD   buy"), vec!["2"]);
+  assert_eq!(ids(&index, "b"), vec!["1", "2", "3"]);
+  assert!(ids(&index, "milkshake").is_empty());
+  assert!(ids(&index, "buy tea").is_empty());
+  assert!(ids(&index, "").is_empty());
+  assert!(ids(&index, " ? ").is_empty());
+}
+
+#[test]
+fn ranks_whole_words_rare_words_and_short_titles_first() {
+  let mut index = SearchIndex::new();
+  index.insert("long", "Plan the team offsite with everyone in the office");
+  index.insert("prefix", "Planning");
+  index.insert("exact", "Plan");
+  assert_eq!(ids(&index, "plan"), vec!["exact", "long", "prefix"]);
+
+  // A rarer word counts for more than a common one.
+  let mut index = SearchIndex::new();
+  index.insert("1", "Email Anna");
+  index.insert("2", "Email Bob");
+  index.insert("3", "Email Carl");
+  index.insert("4", "Anna email email");
+  let results = index.search("email anna");
+  assert_eq!(results.len(), 2);
+  let common = index.search("email");
+  assert!(results[0].1 > common[0].1);
+}
+
+#[test]
+fn updates_incrementally() {
+  let mut index = SearchIndex::new();
+  index.insert("1", "Buy milk");
+  index.insert("2", "Water plants");
+  assert_eq!(index.len(), 2);
+
+  index.insert("1", "Buy oat milk");
+  assert_eq!(ids(&index, "oat"), vec!["1"]);
+  index.insert("1", "Buy bread");
+  assert!(ids(&index, "milk").is_empty());
+
+  index.remove("2");
+  index.remove("unknown");
+  assert!(ids(&index, "water").is_empty());
+  assert_eq!(index.len(), 1);
+
+  index.clear();
+  assert!(index.is_empty());
+}
+
+#[test]
+fn highlights_matched_words() {
+  let title = "Buy Café au lait";
+  let ranges: Vec<&str> = highlights(title, "caf LAIT x")
+    .into_iter()
+    .map(|range| &title[range])
+    .collect();
+  assert_eq!(ranges, vec!["Café", "lait"]);
+  assert!(highlights(title, "").is_empty());
+}
+
+#[test]
+fn search_ranks_the_shown_items() {
+  let app = App::new();
+  app.add("Plan the offsite");
+  app.add("Milk");
+  let plan = app.add("Plan");
+  app.add("Planning poker");
+
+  let messages = app.send(Search("plan".to_string()));
+  assert!(messages.contains(&"ShowItems(3)".to_string()));
+  assert_eq!(
+    app.dom.borrow().titles(),
+    vec!["Plan", "Plan the offsite", "Planning poker"]
+  );
+  // Counts are of the whole list.
+  assert_eq!(app.dom.borrow().items_left, Some(4));
+
+  // Filters still apply.
+  app.send(ToggleItem(plan, true));
+  app.send(SetPage("#/active".to_string()));
+  assert_eq!(
+    app.dom.borrow().titles(),
+    vec!["Plan the offsite", "Planning poker"]
+  );
+
+  // The same words again change nothing, clearing shows everything.
+  assert!(app.send(Search(" plan ".to_string())).is_empty());
+  app.send(Search("".to_string()));
+  assert_eq!(
+    app.dom.borrow().titles(),
+    vec!["Plan the offsite", "Milk", "Planning poker"]
+  );
+}
+
+#[test]
+fn search_follows_store_changes() {
+  let app = App::new();
+  let milk = app.add("Buy milk");
+  app.send(Search("oat".to_string()));
+  assert!(app.dom.borrow().titles().is_empty());
+
+  app.send(AddItem("Oat flakes".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["Oat flakes"]);
+
+  app.send(EditItemSave(milk.clone(), "Buy oat milk".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["Oat flakes", "Buy oat milk"]);
+
+  app.send(RemoveItem(milk));
+  assert_eq!(app.dom.borrow().titles(), vec!["Oat flakes"]);
+
+  // Other lists have their own index.
+  app.send(CreateList("Work".to_string()));
+  assert!(app.dom.borrow().titles().is_empty());
+  app.send(AddItem("Oat milk for the office".to_string()));
+  app.send(SetPage("#/list/todos/".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["Oat flakes"]);
+}
+
+#[test]
+fn rows_highlight_matches() {
+  let l10n = L10n::new("en");
+  let mut items = ItemList::new();
+  items.push(Item::new("1", "Fish & <chips> for Zoë"));
+  items.push(Item::new("2", "Fish"));
+  let html = Template::search_results(&l10n, items, "chip zoe");
+  assert!(html.contains(
+    "<label>Fish &amp; &lt;<mark>chips</mark>&gt; for <mark>Zoë</mark></label>"
+  ));
+  assert!(html.contains("<label>Fish</label>"));
+}
+
+#[test]
+fn search_looks_through_notes() {
+  let storage = MemoryStorage::default();
+  let app = App::with_storage(storage.clone());
+  let milk = app.add("Buy milk");
+  app.add("Call the bank");
+  app.send(SetNotes(milk.clone(), "Oat, not the sweetened one".to_string()));
+  // Notes go last, after the unset optional fields.
+  let stored = storage.get(STORE_NAME).unwrap();
+  let entries: Vec<Vec<serde_json::Value>> = serde_json::from_str(&stored).unwrap();
+  assert_eq!(entries[0].len(), 7);
+  assert_eq!(entries[0][6], "Oat, not the sweetened one");
+  assert_eq!(entries[1].len(), 3);
+
+  app.send(Search("oat".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["Buy milk"]);
+  app.send(Search("milk sweet".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["Buy milk"]);
+
+  // Notes are read back and indexed with the rest of the list.
+  let reopened = App::with_storage(storage.clone());
+  reopened.send(Search("oat".to_string()));
+  assert_eq!(reopened.dom.borrow().titles(), vec!["Buy milk"]);
+
+  // Blank notes are removed.
+  app.send(SetNotes(milk, "  ".to_string()));
+  assert!(app.dom.borrow().titles().is_empty());
+  assert!(!storage.get(STORE_NAME).unwrap().contains("Oat"));
+}
+
+#[test]
+fn rows_highlight_matches_in_notes() {
+  let mut items = ItemList::new();
+  items.push(Item {
+    notes: Some("Ask for <Zoë>".to_string()),
+    ..Item::new("1", "Call the bank")
+  });
+  items.push(Item::new("2", "Fish"));
+  let html = Template::search_results(&L10n::new("en"), items, "zoe");
+  assert!(html.contains("data-notes=\"Ask for &lt;Zoë&gt;\""));
+  assert!(html.contains("<p class=\"notes\">Ask for &lt;<mark>Zoë</mark>&gt;</p>"));
+  assert!(!html.contains("<p class=\"notes\"></p>"));
+}
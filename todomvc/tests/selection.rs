@@ -0,0 +1,172 @@
+mod common;
+
+use common::{App, STORE_NAME};
+use todomvc::controller::ControllerMessage::*;
+use todomvc::selection::Selection;
+
+fn ids(ids: &[&str]) -> Vec<String> {
+  ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[test]
+fn toggles_single_rows() {
+  let mut selection = Selection::new();
+  selection.toggle("1");
+  selection.toggle("3");
+  assert_eq!(selection.ids(), &ids(&["1", "3"])[..]);
+  selection.toggle("1");
+  assert_eq!(selection.ids(), &ids(&["3"])[..]);
+  assert!(!selection.contains("1"));
+  selection.clear();
+  assert!(selection.is_empty());
+}
+
+#[test]
+fn extends_from_the_anchor_in_both_directions() {
+  let shown = ids(&["1", "2", "3", "4", "5"]);
+  let mut selection = Selection::new();
+  selection.toggle("2");
+  selection.extend("4", &shown);
+  assert_eq!(selection.ids(), &ids(&["2", "3", "4"])[..]);
+
+  let mut selection = Selection::new();
+  selection.toggle("4");
+  selection.extend("1", &shown);
+  assert_eq!(selection.len(), 4);
+  assert!(!selection.contains("5"));
+
+  // Without an anchor only the clicked row is picked, and becomes one.
+  let mut selection = Selection::new();
+  selection.extend("3", &shown);
+  assert_eq!(selection.ids(), &ids(&["3"])[..]);
+  selection.extend("5", &shown);
+  assert_eq!(selection.ids(), &ids(&["3", "4", "5"])[..]);
+}
+
+#[test]
+fn forgets_rows_that_are_no_longer_shown() {
+  let mut selection = Selection::new();
+  selection.toggle("1");
+  selection.toggle("2");
+  selection.retain(&ids(&["1", "3"]));
+  assert_eq!(selection.ids(), &ids(&["1"])[..]);
+
+  // The anchor went away too.
+  selection.toggle("3");
+  selection.retain(&ids(&["1", "4", "5"]));
+  selection.extend("5", &ids(&["1", "4", "5"]));
+  assert_eq!(selection.ids(), &ids(&["1", "5"])[..]);
+}
+
+#[test]
+fn completes_and_reopens_selected_items_with_one_write() {
+  let app = App::new();
+  let a = app.add("A");
+  app.add("B");
+  let c = app.add("C");
+  let writes = app.storage.writes();
+
+  let messages = app.send(CompleteItems(vec![a.clone(), c.clone()], true));
+  assert_eq!(app.storage.writes(), writes + 1);
+  assert!(messages.contains(&"ShowItems(3)".to_string()));
+  let completed: Vec<bool> = app.stored().iter().map(|item| item.1).collect();
+  assert_eq!(completed, vec![true, false, true]);
+  assert_eq!(app.dom.borrow().items_left, Some(1));
+
+  app.send(CompleteItems(vec![a, c], false));
+  assert_eq!(app.storage.writes(), writes + 2);
+  assert!(app.stored().iter().all(|item| !item.1));
+}
+
+#[test]
+fn removes_selected_items_with_one_write() {
+  let app = App::new();
+  let a = app.add("A");
+  app.add("B");
+  let c = app.add("C");
+  app.send(AddSubtask(a.clone(), "A.1".to_string()));
+  let writes = app.storage.writes();
+
+  app.send(RemoveItems(vec![a, c, "unknown".to_string()]));
+  assert_eq!(app.storage.writes(), writes + 1);
+  let titles: Vec<String> = app.stored().into_iter().map(|item| item.0).collect();
+  assert_eq!(titles, vec!["B"]);
+  assert_eq!(app.dom.borrow().titles(), vec!["B"]);
+}
+
+#[test]
+fn moves_selected_items_writing_each_list_once() {
+  let app = App::new();
+  app.send(CreateList("Work".to_string()));
+  app.send(SetPage(format!("#/list/{}/", "todos")));
+  let a = app.add("A");
+  let b = app.add("B");
+  app.add("C");
+  app.send(AddSubtask(a.clone(), "A.1".to_string()));
+  let writes = app.storage.writes();
+
+  app.send(MoveItems(vec![b, a], "Work".to_string()));
+  assert_eq!(app.storage.writes(), writes + 2);
+  let moved: Vec<String> = app
+    .stored_at(&format!("{}-list-Work", STORE_NAME))
+    .into_iter()
+    .map(|item| item.0)
+    .collect();
+  assert_eq!(moved, vec!["B", "A", "A.1"]);
+  assert_eq!(app.dom.borrow().titles(), vec!["C"]);
+
+  // Moving to the current or an unknown list does nothing.
+  let writes = app.storage.writes();
+  assert!(app.send(MoveItems(ids(&["1002"]), "todos".to_string())).is_empty());
+  assert!(app.send(MoveItems(ids(&["1002"]), "Home".to_string())).is_empty());
+  assert_eq!(app.storage.writes(), writes);
+}
+
+#[test]
+fn tags_selected_items_once() {
+  let app = App::new();
+  let a = app.add("Call Anna");
+  let b = app.add("Call Bob #work");
+  app.add("Water plants");
+  let writes = app.storage.writes();
+
+  app.send(TagItems(vec![a.clone(), b.clone()], " #work ".to_string()));
+  assert_eq!(app.storage.writes(), writes + 1);
+  let titles: Vec<String> = app.stored().into_iter().map(|item| item.0).collect();
+  assert_eq!(titles, vec!["Call Anna #work", "Call Bob #work", "Water plants"]);
+
+  // Tags are single words.
+  app.send(TagItems(vec![a.clone()], "next week".to_string()));
+  assert_eq!(app.stored()[0].0, "Call Anna #work #next-week");
+
+  let writes = app.storage.writes();
+  assert!(app.send(TagItems(vec![a], " # ".to_string())).is_empty());
+  assert_eq!(app.storage.writes(), writes);
+
+  // And can be searched for.
+  app.send(Search("work".to_string()));
+  assert_eq!(
+    app.dom.borrow().titles(),
+    vec!["Call Bob #work", "Call Anna #work #next-week"]
+  );
+}
+
+#[test]
+fn completing_a_repeating_item_in_a_batch_adds_its_next_occurrence() {
+  let app = App::new();
+  let a = app.add("Stand-up");
+  let b = app.add("Report");
+  app.send(SetSchedule(
+    a.clone(),
+    todomvc::recurrence::DateTime::parse("2026-10-19 09:00"),
+    todomvc::recurrence::Rule::from_input("daily"),
+  ));
+  let writes = app.storage.writes();
+
+  app.send(CompleteItems(vec![a, b], true));
+  assert_eq!(app.storage.writes(), writes + 1);
+  let stored = app.stored();
+  assert_eq!(stored.len(), 3);
+  assert_eq!(stored[2].0, "Stand-up");
+  assert!(!stored[2].1);
+}
@@ -0,0 +1,85 @@
+use todomvc::l10n::L10n;
+use todomvc::ssr::render_page;
+use todomvc::store::{Item, ItemList, ItemListTrait};
+use todomvc::template::Template;
+
+fn items() -> ItemList {
+  let mut items = ItemList::new();
+  items.push(Item::new("1", "Buy milk"));
+  items.push(Item {
+    completed: true,
+    ..Item::new("2", "Walk the <dog>")
+  });
+  items.push(Item::new("3", "Write tests"));
+  items
+}
+
+fn en() -> Vec<String> {
+  vec!["en-US".to_string()]
+}
+
+/// Inner HTML of the first element opened by `open`.
+fn inner<'a>(page: &'a str, open: &str, close: &str) -> &'a str {
+  let start = page.find(open).expect(open) + open.len();
+  let start = start + page[start..].find('>').unwrap() + 1;
+  let end = start + page[start..].find(close).unwrap();
+  &page[start..end]
+}
+
+#[test]
+fn rows_match_client_rendering() {
+  let l10n = L10n::new("en");
+  let page = render_page(&items(), "", &en());
+  assert_eq!(
+    inner(&page, "<ul class=\"todo-list\"", "</ul>"),
+    Template::item_list(&l10n, items())
+  );
+  assert!(page.contains("Walk the &lt;dog&gt;"));
+}
+
+#[test]
+fn counter_matches_client_rendering() {
+  let l10n = L10n::new("en");
+  let page = render_page(&items(), "", &en());
+  assert_eq!(
+    inner(&page, "<span class=\"todo-count\"", "</span>"),
+    Template::item_counter(&l10n, 2)
+  );
+}
+
+#[test]
+fn route_filters_rows_and_selects_link() {
+  let l10n = L10n::new("en");
+  let page = render_page(&items(), "active", &en());
+
+  let mut active = ItemList::new();
+  active.push(Item::new("1", "Buy milk"));
+  active.push(Item::new("3", "Write tests"));
+  assert_eq!(
+    inner(&page, "<ul class=\"todo-list\"", "</ul>"),
+    Template::item_list(&l10n, active)
+  );
+  assert!(page.contains("<a href=\"#/active\" class=\"selected\""));
+  assert!(!page.contains("<a href=\"#/\" class=\"selected\""));
+}
+
+#[test]
+fn empty_list_hides_main() {
+  let page = render_page(&ItemList::new(), "", &en());
+  assert!(page.contains("<section hidden class=\"main\">"));
+  assert!(page.contains("<button class=\"clear-completed\" hidden"));
+}
+
+#[test]
+fn page_is_marked_for_hydration() {
+  let page = render_page(&items(), "", &en());
+  assert!(page.contains("<section class=\"todoapp\" data-ssr=\"true\">"));
+  assert!(page.contains("<section class=\"main\">"));
+}
+
+#[test]
+fn page_is_localized() {
+  let page = render_page(&items(), "", &["de-AT".to_string(), "en".to_string()]);
+  assert!(page.contains("<html lang=\"de\">"));
+  assert!(page.contains("2 Aufgaben offen"));
+}
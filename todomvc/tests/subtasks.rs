@@ -0,0 +1,284 @@
+mod common;
+
+use common::{App, MemoryStorage, STORE_NAME};
+use todomvc::controller::ControllerMessage::*;
+use todomvc::l10n::L10n;
+use todomvc::platform::Storage;
+use todomvc::store::{Item, ItemList, ItemListTrait};
+use todomvc::template::Template;
+
+/// Stored items as `(id, completed, parent)`.
+fn tree(app: &App) -> Vec<(String, bool, Option<String>)> {
+  let value = app.storage.get(STORE_NAME).unwrap();
+  let entries: Vec<Vec<serde_json::Value>> = serde_json::from_str(&value).unwrap();
+  entries
+    .iter()
+    .map(|entry| {
+      (
+        entry[2].as_str().unwrap().to_string(),
+        entry[1].as_bool().unwrap(),
+        entry.get(3).map(|parent| parent.as_str().unwrap().to_string()),
+      )
+    })
+    .collect()
+}
+
+fn completed(app: &App, id: &str) -> bool {
+  tree(app).iter().find(|item| item.0 == id).unwrap().1
+}
+
+fn subtask(app: &App, parent: &str, title: &str) -> String {
+  app.send(AddSubtask(parent.to_string(), title.to_string()));
+  tree(app).last().unwrap().0.clone()
+}
+
+/// A parent with two subtasks, the first of which has one of its own.
+fn nested_app() -> (App, [String; 4]) {
+  let app = App::new();
+  let parent = app.add("parent");
+  let a = subtask(&app, &parent, "a");
+  let b = subtask(&app, &parent, "b");
+  let a1 = subtask(&app, &a, "a1");
+  (app, [parent, a, b, a1])
+}
+
+#[test]
+fn subtasks_store_their_parent() {
+  let (app, [parent, a, _, a1]) = nested_app();
+  let value = app.storage.get(STORE_NAME).unwrap();
+  assert!(value.starts_with(r#"[["parent",false,"1000"],["a",false,"1001","1000"]"#));
+  assert_eq!(tree(&app)[3], (a1, false, Some(a)));
+
+  // Reloading keeps the tree.
+  let reloaded = App::with_storage(app.storage.clone());
+  reloaded.send(SetPage("#/".to_string()));
+  assert_eq!(reloaded.dom.borrow().titles(), vec!["parent", "a", "b", "a1"]);
+  assert_eq!(tree(&reloaded)[1].2, Some(parent));
+}
+
+#[test]
+fn subtasks_of_unknown_items_are_ignored() {
+  let app = App::new();
+  app.add("one");
+  assert!(app.send(AddSubtask("nope".to_string(), "two".to_string())).is_empty());
+  assert_eq!(tree(&app).len(), 1);
+}
+
+#[test]
+fn parent_completes_with_its_subtasks() {
+  let (app, [parent, a, b, a1]) = nested_app();
+
+  app.send(ToggleItem(a1.clone(), true));
+  assert!(completed(&app, &a));
+  assert!(!completed(&app, &parent));
+
+  let messages = app.send(ToggleItem(b.clone(), true));
+  assert!(messages.contains(&"ShowItems(4)".to_string()));
+  assert!(completed(&app, &parent));
+  assert!(app.dom.borrow().row(&parent).unwrap().completed);
+
+  // Reopening a subtask reopens its ancestors.
+  app.send(ToggleItem(a1, false));
+  assert!(!completed(&app, &a));
+  assert!(!completed(&app, &parent));
+  assert!(completed(&app, &b));
+}
+
+#[test]
+fn completing_a_parent_completes_its_subtree() {
+  let (app, [parent, ..]) = nested_app();
+
+  app.send(ToggleItem(parent.clone(), true));
+  assert!(tree(&app).iter().all(|item| item.1));
+
+  app.send(ToggleItem(parent, false));
+  assert!(tree(&app).iter().all(|item| !item.1));
+}
+
+#[test]
+fn new_subtask_reopens_parent() {
+  let app = App::new();
+  let parent = app.add("parent");
+  app.send(ToggleItem(parent.clone(), true));
+
+  subtask(&app, &parent, "a");
+  assert!(!completed(&app, &parent));
+}
+
+#[test]
+fn items_left_counts_tasks_without_subtasks() {
+  let (app, [_, _, b, _]) = nested_app();
+  assert_eq!(app.dom.borrow().items_left, Some(2));
+
+  app.send(ToggleItem(b, true));
+  assert_eq!(app.dom.borrow().items_left, Some(1));
+}
+
+#[test]
+fn removing_a_parent_removes_its_subtree() {
+  let (app, [parent, a, ..]) = nested_app();
+  app.add("other");
+
+  app.send(RemoveItem(a));
+  assert_eq!(app.dom.borrow().titles(), vec!["parent", "b", "other"]);
+
+  app.send(RemoveItem(parent));
+  assert_eq!(app.dom.borrow().titles(), vec!["other"]);
+  assert_eq!(tree(&app).len(), 1);
+}
+
+#[test]
+fn removing_the_last_open_subtask_completes_parent() {
+  let (app, [parent, a, b, _]) = nested_app();
+  app.send(ToggleItem(b, true));
+
+  app.send(RemoveItem(a));
+  assert!(completed(&app, &parent));
+}
+
+#[test]
+fn remove_completed_takes_completed_subtrees() {
+  let (app, [parent, a, b, _]) = nested_app();
+  app.send(ToggleItem(a, true));
+
+  app.send(RemoveCompleted());
+  assert_eq!(app.dom.borrow().titles(), vec!["parent", "b"]);
+
+  app.send(ToggleItem(b, true));
+  assert!(completed(&app, &parent));
+  app.send(RemoveCompleted());
+  assert!(tree(&app).is_empty());
+}
+
+#[test]
+fn toggle_all_covers_every_level() {
+  let (app, _) = nested_app();
+
+  app.send(ToggleAll(true));
+  assert!(tree(&app).iter().all(|item| item.1));
+  assert_eq!(app.dom.borrow().items_left, Some(0));
+
+  app.send(ToggleAll(false));
+  assert!(tree(&app).iter().all(|item| !item.1));
+}
+
+#[test]
+fn filters_keep_subtasks_with_their_parent() {
+  let (app, [_, a, ..]) = nested_app();
+  let done = app.add("done");
+  app.send(ToggleItem(a, true));
+  app.send(ToggleItem(done, true));
+
+  app.send(SetPage("#/active".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["parent", "a", "b", "a1"]);
+
+  app.send(SetPage("#/completed".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["done"]);
+}
+
+#[test]
+fn indent_and_outdent() {
+  let app = App::new();
+  let one = app.add("one");
+  let two = app.add("two");
+  let three = app.add("three");
+
+  // The first item has no sibling above it.
+  assert!(app.send(Indent(one.clone())).is_empty());
+
+  app.send(Indent(two.clone()));
+  app.send(Indent(three.clone()));
+  assert_eq!(tree(&app)[1], (two.clone(), false, Some(one.clone())));
+  assert_eq!(tree(&app)[2], (three.clone(), false, Some(one.clone())));
+
+  app.send(Outdent(two.clone()));
+  assert_eq!(
+    tree(&app),
+    vec![
+      (one.clone(), false, None),
+      (two.clone(), false, None),
+      (three, false, Some(one)),
+    ]
+  );
+  assert!(app.send(Outdent(two)).is_empty());
+}
+
+#[test]
+fn indent_under_completed_sibling_reopens_it() {
+  let app = App::new();
+  let one = app.add("one");
+  let two = app.add("two");
+  app.send(ToggleItem(one.clone(), true));
+
+  app.send(Indent(two));
+  assert!(!completed(&app, &one));
+}
+
+#[test]
+fn move_item_takes_subtasks_along() {
+  let (app, [parent, a, b, a1]) = nested_app();
+  app.send(CreateList("Work".to_string()));
+  app.send(SetPage("#/list/todos/".to_string()));
+
+  app.send(MoveItem(a.clone(), "Work".to_string()));
+  assert_eq!(app.dom.borrow().titles(), vec!["parent", "b"]);
+  assert_eq!(
+    tree(&app),
+    vec![(parent, false, None), (b, false, Some("1000".to_string()))]
+  );
+
+  let work = app.storage.get(&format!("{}-list-Work", STORE_NAME)).unwrap();
+  assert_eq!(
+    work,
+    format!(r#"[["a",false,"{}"],["a1",false,"{}","{}"]]"#, a, a1, a)
+  );
+}
+
+#[test]
+fn parent_cycles_in_storage_terminate() {
+  let storage = MemoryStorage::default();
+  storage.set_item(
+    STORE_NAME,
+    r#"[["a",false,"1","2"],["b",false,"2","1"],["c",false,"3"]]"#,
+  );
+  let app = App::with_storage(storage);
+  app.send(SetPage("#/active".to_string()));
+  app.send(RemoveItem("1".to_string()));
+  assert_eq!(tree(&app), vec![("3".to_string(), false, None)]);
+}
+
+#[test]
+fn rows_nest_subtasks_with_progress() {
+  let mut items = ItemList::new();
+  items.push(Item::new("1", "parent"));
+  items.push(Item {
+    completed: true,
+    parent: Some("1".to_string()),
+    ..Item::new("2", "a")
+  });
+  items.push(Item {
+    parent: Some("1".to_string()),
+    ..Item::new("3", "b")
+  });
+  let html = Template::item_list(&L10n::new("en"), items);
+
+  let subtasks = html.find("<ul class=\"subtasks\">").unwrap();
+  assert!(html.find("data-id=\"1\"").unwrap() < subtasks);
+  assert!(html.find("data-id=\"2\"").unwrap() > subtasks);
+  assert!(html.contains("aria-expanded=\"true\""));
+  assert!(html.contains("aria-label=\"1 of 2 subtasks done\">1/2</span>"));
+  assert_eq!(html.matches("<li ").count(), 3);
+}
+
+#[test]
+fn orphaned_subtasks_render_at_top_level() {
+  let mut items = ItemList::new();
+  items.push(Item {
+    completed: true,
+    parent: Some("1".to_string()),
+    ..Item::new("2", "a")
+  });
+  let html = Template::item_list(&L10n::new("en"), items);
+  assert!(html.starts_with("<li data-id=\"2\""));
+  assert!(!html.contains("subtasks"));
+}
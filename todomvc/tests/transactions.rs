@@ -0,0 +1,223 @@
+mod common;
+
+use common::{App, MemoryStorage, STORE_NAME};
+use todomvc::controller::ControllerMessage::*;
+use todomvc::store::{Item, ItemListTrait, ItemQuery, ItemUpdate, Store};
+
+fn store(storage: &MemoryStorage) -> Store {
+  Store::with_storage(Box::new(storage.clone()), STORE_NAME)
+}
+
+fn titles(store: &mut Store) -> Vec<String> {
+  let data = store.find(ItemQuery::EmptyItemQuery).unwrap();
+  data.iter().map(|item| item.title.clone()).collect()
+}
+
+#[test]
+fn commits_with_one_write() {
+  let storage = MemoryStorage::default();
+  let mut store = store(&storage);
+
+  let result: Result<usize, ()> = store.transaction(|tx| {
+    tx.insert(Item::new("1", "Milk"));
+    tx.insert(Item::new("2", "Bread"));
+    tx.update(ItemUpdate::Completed {
+      id: "1".to_string(),
+      completed: true,
+    });
+    tx.remove(ItemQuery::Id { id: "2".to_string() });
+    Ok(3)
+  });
+  assert_eq!(result, Ok(3));
+  assert_eq!(storage.writes(), 1);
+  assert_eq!(
+    storage.get(STORE_NAME),
+    Some(r#"[["Milk",true,"1"]]"#.to_string())
+  );
+
+  // Nothing changed, nothing written.
+  let result: Result<(), ()> = store.transaction(|_| Ok(()));
+  assert_eq!(result, Ok(()));
+  assert_eq!(storage.writes(), 1);
+}
+
+#[test]
+fn rolls_back_on_error() {
+  let storage = MemoryStorage::default();
+  let mut store = store(&storage);
+  store.insert(Item::new("1", "Milk"));
+  store.insert(Item::new("2", "Bread"));
+  let writes = storage.writes();
+  let before = storage.get(STORE_NAME);
+
+  let result: Result<(), String> = store.transaction(|tx| {
+    tx.update(ItemUpdate::Title {
+      id: "1".to_string(),
+      title: "Oat milk".to_string(),
+    });
+    tx.remove(ItemQuery::Id { id: "2".to_string() });
+    tx.insert(Item::new("3", "Eggs"));
+    Err("out of eggs".to_string())
+  });
+  assert_eq!(result, Err("out of eggs".to_string()));
+  assert_eq!(storage.writes(), writes);
+  assert_eq!(storage.get(STORE_NAME), before);
+  assert_eq!(titles(&mut store), vec!["Milk", "Bread"]);
+
+  // The search index is rolled back with the items.
+  let found = store.search("oat", ItemQuery::EmptyItemQuery);
+  assert_eq!(found.length(), 0);
+  let found = store.search("bread", ItemQuery::EmptyItemQuery);
+  assert_eq!(found.length(), 1);
+}
+
+#[test]
+fn writes_other_lists_on_commit_only() {
+  let storage = MemoryStorage::default();
+  let mut store = store(&storage);
+  store.create_list("Work");
+  store.insert(Item::new("1", "Report"));
+  store.insert(Item::new("2", "Slides"));
+  let work = format!("{}-list-Work", STORE_NAME);
+  let writes = storage.writes();
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.move_item("1", "Work");
+    tx.move_item("2", "Work");
+    Err(())
+  });
+  assert!(result.is_err());
+  assert_eq!(storage.writes(), writes);
+  assert_eq!(storage.get(&work), None);
+  assert_eq!(titles(&mut store), vec!["Report", "Slides"]);
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.move_item("1", "Work");
+    tx.move_item("2", "Work");
+    Ok(())
+  });
+  assert!(result.is_ok());
+  // The current list and "Work", once each.
+  assert_eq!(storage.writes(), writes + 2);
+  assert!(titles(&mut store).is_empty());
+  assert!(store.switch_list("Work"));
+  assert_eq!(titles(&mut store), vec!["Report", "Slides"]);
+}
+
+#[test]
+fn renaming_a_list_keeps_the_changes_of_a_transaction() {
+  let storage = MemoryStorage::default();
+  let mut store = store(&storage);
+  store.create_list("Work");
+  store.insert(Item::new("1", "Report"));
+  let (work, office) = (
+    format!("{}-list-Work", STORE_NAME),
+    format!("{}-list-Office", STORE_NAME),
+  );
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.move_item("1", "Work");
+    assert!(tx.rename_list("Work", "Office"));
+    Ok(())
+  });
+  assert!(result.is_ok());
+  assert_eq!(storage.get(&work), None);
+  assert!(titles(&mut store).is_empty());
+  assert!(store.switch_list("Office"));
+  assert_eq!(titles(&mut store), vec!["Report"]);
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.insert(Item::new("2", "Slides"));
+    assert!(tx.rename_list("Office", "Work"));
+    Ok(())
+  });
+  assert!(result.is_ok());
+  assert_eq!(storage.get(&office), None);
+  assert_eq!(store.list(), "Work");
+  assert_eq!(titles(&mut store), vec!["Report", "Slides"]);
+  let mut reloaded = Store::with_storage(Box::new(storage.clone()), STORE_NAME);
+  assert!(reloaded.switch_list("Work"));
+  assert_eq!(titles(&mut reloaded), vec!["Report", "Slides"]);
+}
+
+#[test]
+fn renaming_a_list_in_a_rolled_back_transaction_keeps_the_committed_items() {
+  let storage = MemoryStorage::default();
+  let mut store = store(&storage);
+  store.create_list("Work");
+  store.insert(Item::new("1", "Report"));
+  let work = format!("{}-list-Work", STORE_NAME);
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.move_item("1", "Work");
+    assert!(tx.rename_list("Work", "Office"));
+    Err(())
+  });
+  assert!(result.is_err());
+  assert_eq!(storage.get(&work), None);
+  assert_eq!(titles(&mut store), vec!["Report"]);
+  assert!(store.switch_list("Office"));
+  assert!(titles(&mut store).is_empty());
+}
+
+#[test]
+fn deleting_a_list_drops_the_changes_of_a_transaction() {
+  let storage = MemoryStorage::default();
+  let mut store = store(&storage);
+  store.create_list("Work");
+  store.insert(Item::new("1", "Report"));
+  let work = format!("{}-list-Work", STORE_NAME);
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.move_item("1", "Work");
+    assert!(tx.delete_list("Work"));
+    Ok(())
+  });
+  assert!(result.is_ok());
+  assert_eq!(storage.get(&work), None);
+}
+
+#[test]
+fn nested_transactions_join_the_outer_one() {
+  let storage = MemoryStorage::default();
+  let mut store = store(&storage);
+
+  let result: Result<(), ()> = store.transaction(|tx| {
+    tx.insert(Item::new("1", "Milk"));
+    let inner: Result<(), ()> = tx.transaction(|tx| {
+      tx.insert(Item::new("2", "Bread"));
+      Ok(())
+    });
+    assert_eq!(storage.writes(), 0);
+    inner.and(Err(()))
+  });
+  assert!(result.is_err());
+  assert!(titles(&mut store).is_empty());
+  assert_eq!(storage.writes(), 0);
+
+  let count = store.batch(|tx| {
+    tx.insert(Item::new("1", "Milk"));
+    tx.batch(|tx| tx.insert(Item::new("2", "Bread")));
+    2
+  });
+  assert_eq!(count, 2);
+  assert_eq!(storage.writes(), 1);
+}
+
+#[test]
+fn toggle_all_writes_once() {
+  let app = App::new();
+  let parent = app.add("Groceries");
+  app.send(AddSubtask(parent, "Milk".to_string()));
+  app.add("Laundry");
+  app.add("Dishes");
+  let writes = app.storage.writes();
+
+  app.send(ToggleAll(true));
+  assert_eq!(app.storage.writes(), writes + 1);
+  assert!(app.stored().iter().all(|item| item.1));
+
+  app.send(ToggleAll(false));
+  assert_eq!(app.storage.writes(), writes + 2);
+  assert!(app.stored().iter().all(|item| !item.1));
+}
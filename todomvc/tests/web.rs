@@ -0,0 +1,348 @@
+//! Headless end-to-end tests, run under Node with jsdom:
+//!
+//! ```sh
+//! npm install
+//! wasm-pack test --node
+//! ```
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_test::*;
+
+const ENTER_KEY: u32 = 13;
+const ESCAPE_KEY: u32 = 27;
+
+#[wasm_bindgen(module = "/tests/jsdom.js")]
+extern "C" {
+  fn load_page(html: &str);
+  fn type_text(selector: &str, text: &str);
+  fn set_value(selector: &str, text: &str);
+  fn press_key(selector: &str, event_type: &str, key: &str, key_code: u32);
+  fn click(selector: &str);
+  fn modified_click(selector: &str, modifier: &str);
+  fn double_click(selector: &str);
+  fn set_hash(hash: &str);
+  fn answer_prompt(answer: &str);
+}
+
+fn document() -> web_sys::Document {
+  web_sys::window().unwrap().document().unwrap()
+}
+
+/// Loads `index.html` and starts an app storing its items under `name`.
+fn start(name: &str) {
+  load_page(include_str!("../index.html"));
+  todomvc::app(name).unwrap();
+}
+
+fn texts(selector: &str) -> Vec<String> {
+  let nodes = document().query_selector_all(selector).unwrap();
+  (0..nodes.length())
+    .filter_map(|i| nodes.get(i))
+    .map(|node| node.text_content().unwrap_or_default())
+    .collect()
+}
+
+fn element(selector: &str) -> web_sys::Element {
+  document().query_selector(selector).unwrap().unwrap()
+}
+
+fn has_class(selector: &str, class: &str) -> bool {
+  element(selector).class_list().contains(class)
+}
+
+fn is_hidden(selector: &str) -> bool {
+  element(selector)
+    .dyn_into::<web_sys::HtmlElement>()
+    .unwrap()
+    .hidden()
+}
+
+fn input_value(selector: &str) -> String {
+  element(selector)
+    .dyn_into::<web_sys::HtmlInputElement>()
+    .unwrap()
+    .value()
+}
+
+fn selected_list() -> String {
+  element(".list-switcher")
+    .dyn_into::<web_sys::HtmlSelectElement>()
+    .unwrap()
+    .value()
+}
+
+fn count() -> String {
+  element(".todo-count").text_content().unwrap().trim().to_string()
+}
+
+/// Persisted items as `(title, completed, id)`.
+fn stored(name: &str) -> Vec<(String, bool, String)> {
+  let storage = web_sys::window().unwrap().local_storage().unwrap().unwrap();
+  match storage.get_item(name).unwrap() {
+    Some(value) => serde_json::from_str(&value).unwrap(),
+    None => Vec::new(),
+  }
+}
+
+fn item(title: &str, completed: bool, id: &str) -> (String, bool, String) {
+  (title.to_string(), completed, id.to_string())
+}
+
+#[wasm_bindgen_test]
+fn add_items() {
+  start("web-add");
+  assert!(is_hidden(".main"));
+
+  type_text(".new-todo", "Buy milk");
+  type_text(".new-todo", "  Walk the dog  ");
+
+  assert_eq!(texts(".todo-list li label"), vec!["Buy milk", "Walk the dog"]);
+  assert_eq!(input_value(".new-todo"), "");
+  assert_eq!(count(), "2 items left");
+  assert!(!is_hidden(".main"));
+  assert_eq!(
+    stored("web-add"),
+    vec![item("Buy milk", false, "1000"), item("Walk the dog", false, "1001")]
+  );
+}
+
+#[wasm_bindgen_test]
+fn blank_titles_are_ignored() {
+  start("web-blank");
+  type_text(".new-todo", "   ");
+
+  assert!(texts(".todo-list li").is_empty());
+  assert!(stored("web-blank").is_empty());
+}
+
+#[wasm_bindgen_test]
+fn edit_item_with_enter() {
+  start("web-edit");
+  type_text(".new-todo", "Buy milk");
+
+  double_click(".todo-list li label");
+  assert!(has_class(".todo-list li", "editing"));
+  assert_eq!(input_value(".todo-list li .edit"), "Buy milk");
+
+  set_value(".todo-list li .edit", "Buy oat milk");
+  press_key(".todo-list li .edit", "keypress", "Enter", ENTER_KEY);
+
+  assert!(!has_class(".todo-list li", "editing"));
+  assert!(document().query_selector(".todo-list li .edit").unwrap().is_none());
+  assert_eq!(texts(".todo-list li label"), vec!["Buy oat milk"]);
+  assert_eq!(stored("web-edit")[0].0, "Buy oat milk");
+}
+
+#[wasm_bindgen_test]
+fn escape_cancels_edit() {
+  start("web-escape");
+  type_text(".new-todo", "Buy milk");
+
+  double_click(".todo-list li label");
+  set_value(".todo-list li .edit", "Something else");
+  press_key(".todo-list li .edit", "keyup", "Escape", ESCAPE_KEY);
+
+  assert!(!has_class(".todo-list li", "editing"));
+  assert_eq!(texts(".todo-list li label"), vec!["Buy milk"]);
+  assert_eq!(stored("web-escape")[0].0, "Buy milk");
+}
+
+#[wasm_bindgen_test]
+fn toggle_item() {
+  start("web-toggle");
+  type_text(".new-todo", "one");
+  type_text(".new-todo", "two");
+
+  click(".todo-list li .toggle");
+
+  assert!(has_class(".todo-list li", "completed"));
+  assert_eq!(count(), "1 item left");
+  assert!(!is_hidden(".clear-completed"));
+  assert_eq!(
+    stored("web-toggle"),
+    vec![item("one", true, "1000"), item("two", false, "1001")]
+  );
+}
+
+#[wasm_bindgen_test]
+fn toggle_all() {
+  start("web-toggle-all");
+  type_text(".new-todo", "one");
+  type_text(".new-todo", "two");
+
+  click(".toggle-all");
+  assert_eq!(count(), "0 items left");
+  assert!(stored("web-toggle-all").iter().all(|item| item.1));
+  assert_eq!(
+    document()
+      .query_selector_all(".todo-list li.completed")
+      .unwrap()
+      .length(),
+    2
+  );
+
+  click(".toggle-all");
+  assert_eq!(count(), "2 items left");
+  assert!(stored("web-toggle-all").iter().all(|item| !item.1));
+}
+
+#[wasm_bindgen_test]
+fn clear_completed() {
+  start("web-clear");
+  type_text(".new-todo", "one");
+  type_text(".new-todo", "two");
+  click(".todo-list li .toggle");
+
+  click(".clear-completed");
+
+  assert_eq!(texts(".todo-list li label"), vec!["two"]);
+  assert!(is_hidden(".clear-completed"));
+  assert_eq!(stored("web-clear"), vec![item("two", false, "1001")]);
+}
+
+#[wasm_bindgen_test]
+fn destroy_item() {
+  start("web-destroy");
+  type_text(".new-todo", "one");
+
+  click(".todo-list li .destroy");
+
+  assert!(texts(".todo-list li").is_empty());
+  assert!(is_hidden(".main"));
+  assert!(stored("web-destroy").is_empty());
+}
+
+#[wasm_bindgen_test]
+fn hash_routing() {
+  start("web-routing");
+  type_text(".new-todo", "one");
+  type_text(".new-todo", "two");
+  click(".todo-list li .toggle");
+
+  set_hash("#/active");
+  assert_eq!(texts(".todo-list li label"), vec!["two"]);
+  assert!(has_class(".filters [data-filter=\"active\"]", "selected"));
+  assert!(!has_class(".filters [data-filter=\"\"]", "selected"));
+
+  set_hash("#/completed");
+  assert_eq!(texts(".todo-list li label"), vec!["one"]);
+  assert!(has_class(".filters [data-filter=\"completed\"]", "selected"));
+
+  set_hash("#/");
+  assert_eq!(texts(".todo-list li label"), vec!["one", "two"]);
+  assert!(has_class(".filters [data-filter=\"\"]", "selected"));
+}
+
+#[wasm_bindgen_test]
+fn create_and_switch_lists() {
+  start("web-lists");
+  type_text(".new-todo", "one");
+  assert_eq!(texts(".list-switcher option"), vec!["todos"]);
+  assert!(is_hidden(".delete-list"));
+
+  answer_prompt("Work");
+  click(".new-list");
+  assert_eq!(texts(".list-switcher option"), vec!["todos", "Work"]);
+  assert_eq!(selected_list(), "Work");
+  assert!(texts(".todo-list li").is_empty());
+  assert_eq!(
+    element(".filters [data-filter=\"active\"]").get_attribute("href"),
+    Some("#/list/Work/active".to_string())
+  );
+
+  type_text(".new-todo", "two");
+  set_hash("#/list/todos/");
+  assert_eq!(texts(".todo-list li label"), vec!["one"]);
+  assert_eq!(selected_list(), "todos");
+
+  answer_prompt("Work");
+  click(".todo-list li .move");
+  assert!(texts(".todo-list li").is_empty());
+  set_hash("#/list/Work/");
+  assert_eq!(texts(".todo-list li label"), vec!["two", "one"]);
+}
+
+#[wasm_bindgen_test]
+fn subtasks() {
+  start("web-subtasks");
+  type_text(".new-todo", "parent");
+
+  answer_prompt("child");
+  click(".todo-list li .add-subtask");
+  assert_eq!(texts(".todo-list .subtasks li label"), vec!["child"]);
+  assert_eq!(texts(".todo-list li .progress"), vec!["0/1"]);
+
+  click(".todo-list .subtasks li .toggle");
+  assert!(has_class(".todo-list li", "completed"));
+  assert_eq!(texts(".todo-list li .progress"), vec!["1/1"]);
+
+  click(".todo-list li .collapse");
+  assert!(has_class(".todo-list li", "collapsed"));
+  // Collapsed rows stay collapsed when the list is redrawn.
+  type_text(".new-todo", "other");
+  assert!(has_class(".todo-list li", "collapsed"));
+  assert_eq!(count(), "1 item left");
+}
+
+#[wasm_bindgen_test]
+fn repeating_items() {
+  start("web-repeating");
+  type_text(".new-todo", "water plants");
+
+  answer_prompt("2026-10-19 08:00");
+  answer_prompt("daily");
+  click(".todo-list li .schedule");
+  assert_eq!(texts(".todo-list li .due"), vec!["2026-10-19 08:00 ↻"]);
+  assert_eq!(
+    element(".todo-list li").get_attribute("data-repeat"),
+    Some("FREQ=DAILY".to_string())
+  );
+
+  click(".todo-list li .toggle");
+  assert_eq!(
+    texts(".todo-list li .due"),
+    vec!["2026-10-19 08:00", "2026-10-20 08:00 ↻"]
+  );
+  assert_eq!(count(), "1 item left");
+}
+
+#[wasm_bindgen_test]
+fn batch_selection() {
+  start("web-batch");
+  type_text(".new-todo", "one");
+  type_text(".new-todo", "two");
+  type_text(".new-todo", "three");
+  assert!(is_hidden(".batch"));
+
+  modified_click(".todo-list li:nth-child(1) label", "ctrlKey");
+  modified_click(".todo-list li:nth-child(3) label", "shiftKey");
+  assert_eq!(texts(".todo-list li.selected label"), vec!["one", "two", "three"]);
+  modified_click(".todo-list li:nth-child(2) label", "ctrlKey");
+  assert!(!is_hidden(".batch"));
+  assert_eq!(texts(".batch-count"), vec!["2 selected"]);
+
+  click(".batch-complete");
+  assert_eq!(count(), "1 item left");
+  // Still selected, so the same button reopens them.
+  assert_eq!(texts(".batch-complete"), vec!["Reopen"]);
+
+  answer_prompt("errands");
+  click(".batch-tag");
+  assert_eq!(
+    texts(".todo-list li label"),
+    vec!["one #errands", "two", "three #errands"]
+  );
+
+  click(".batch-delete");
+  assert_eq!(texts(".todo-list li label"), vec!["two"]);
+  assert!(is_hidden(".batch"));
+  assert_eq!(stored("web-batch").len(), 1);
+}
+
+#[wasm_bindgen_test]
+fn negotiates_the_locale_of_the_browser() {
+  // jsdom asks for `en-US`, then `en`.
+  assert_eq!(todomvc::l10n::L10n::from_navigator().locale(), "en");
+}